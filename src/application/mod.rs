@@ -0,0 +1,62 @@
+use std::{
+    fmt::{Debug, Display},
+    hash::Hash,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::talk::{Command, CommandResult};
+
+/// Operation that clients issue to a replicated application, carried by a `Command`.
+/// Operations travel in messages and are totally ordered, e.g. to execute the CSet of a round
+/// in the same order on every replica.
+pub trait Operation:
+    'static + Clone + Debug + Display + Eq + Hash + Ord + Serialize + DeserializeOwned + Send + Sync
+{
+    /// Data returned by a successful operation, e.g. the balance of an account
+    type Output: 'static
+        + Clone
+        + Debug
+        + Display
+        + Eq
+        + Hash
+        + Ord
+        + Serialize
+        + DeserializeOwned
+        + Send
+        + Sync;
+
+    /// Name of the operation, regardless of its arguments, e.g. to group latencies
+    fn kind(&self) -> &'static str;
+}
+
+/// Deterministic state machine replicated by the replicas.
+/// Commands of the NCSet are executed speculatively on the fast path, so that the application
+/// must undo them if the decision of the round does not include them. Commands that do not
+/// conflict commute: they may be executed in any order, and still lead to the same state.
+/// Each replica has its own instance, built with `Default`.
+pub trait Application: 'static + Default + Send + Sync {
+    type Action: Operation;
+    /// Complete state of the application, compared across the replicas
+    type Snapshot: 'static + Clone + Debug + Eq + Hash + Serialize + DeserializeOwned + Send + Sync;
+    type Error: Debug;
+
+    /// Executes the command and returns its result
+    fn execute(&mut self, command: &Command<Self::Action>) -> CommandResult<Self::Action>;
+
+    /// Undoes the speculative execution of the command, which returned `result`.
+    /// Commands are rolled back in the reverse order of their execution.
+    fn rollback(
+        &mut self,
+        command: &Command<Self::Action>,
+        result: &CommandResult<Self::Action>,
+    ) -> Result<(), Self::Error>;
+
+    /// Returns true if the commands do not commute. The relation must be symmetric, and a command
+    /// never conflicts with itself.
+    fn conflict(x: &Command<Self::Action>, y: &Command<Self::Action>) -> bool;
+
+    fn snapshot(&self) -> Self::Snapshot;
+
+    fn restore(&mut self, snapshot: Self::Snapshot);
+}
@@ -2,6 +2,8 @@ use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
 
+use crate::application::Operation;
+
 use super::banking::Money;
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -12,6 +14,20 @@ pub enum Action {
     Withdraw(Money),
 }
 
+impl Operation for Action {
+    type Output = Money;
+
+    /// Name of the variant, regardless of the amount
+    fn kind(&self) -> &'static str {
+        match self {
+            Action::Register => "Register",
+            Action::Get => "Get",
+            Action::Deposit(_) => "Deposit",
+            Action::Withdraw(_) => "Withdraw",
+        }
+    }
+}
+
 impl Display for Action {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match *self {
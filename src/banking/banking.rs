@@ -1,6 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::{error::BankingError, peer::peer::PeerId};
+use rand::Rng;
+use rand_distr::{Distribution, Uniform};
+
+use crate::{
+    application::Application,
+    error::BankingError,
+    network::{network::Network, Counterexample},
+    peer::{handler::Adversarial, peer::PeerId},
+    relation::{conflict::ConflictingRelation, Relation},
+    simulation::{ApplicationKind, Simulated},
+    talk::{Command, CommandResult},
+};
+
+use super::action::Action;
 
 pub type Money = u64;
 
@@ -9,7 +22,7 @@ pub type Money = u64;
 /// This can be compared to a saving account: a customer frequently
 /// deposit money, and rarely withdraw it.
 /// Each replica has its own banking instance.
-
+#[derive(Default)]
 pub struct Banking {
     clients: HashMap<PeerId, Money>,
 }
@@ -87,6 +100,116 @@ impl Banking {
     }
 }
 
+/// Each command acts on the account of its issuer, and the commands conflict according to
+/// `ConflictingRelation`
+impl Application for Banking {
+    type Action = Action;
+    type Snapshot = BTreeMap<PeerId, Money>;
+    type Error = BankingError;
+
+    fn execute(&mut self, command: &Command) -> CommandResult {
+        let id = *command.issuer();
+        match command.action() {
+            Action::Register => {
+                if self.register(id) {
+                    CommandResult::Success(None)
+                } else {
+                    CommandResult::Failure(format!("Client #{} is already registered", id))
+                }
+            }
+            Action::Get => self
+                .get(&id)
+                .map(|amount| CommandResult::Success(Some(amount)))
+                .unwrap_or(CommandResult::Failure(format!(
+                    "Client #{} is not registered",
+                    id
+                ))),
+            Action::Deposit(amount) => self
+                .deposit(&id, *amount)
+                .map(|_res| CommandResult::Success(None))
+                .unwrap_or(CommandResult::Failure(format!(
+                    "Client #{} cannot deposit because he is not registered",
+                    id
+                ))),
+            Action::Withdraw(amount) => self
+                .withdraw(&id, *amount)
+                .map(|_amount| CommandResult::Success(None))
+                .unwrap_or_else(|err| match err {
+                    BankingError::ClientNotFound => {
+                        CommandResult::Failure(format!("Client #{} is not registered", id))
+                    }
+                    BankingError::UnsufficientBalance => {
+                        CommandResult::Failure(format!("Unsufficient balance"))
+                    }
+                }),
+        }
+    }
+
+    fn rollback(&mut self, command: &Command, result: &CommandResult) -> Result<(), BankingError> {
+        let id = command.issuer();
+        match result {
+            // Only rollback the effect if the command was successful
+            CommandResult::Success(_) => match command.action() {
+                Action::Register => {
+                    self.unregister(id);
+                    Ok(())
+                }
+                Action::Get => Ok(()),
+                Action::Deposit(amount) => self.withdraw(id, *amount),
+                Action::Withdraw(amount) => self.deposit(id, *amount),
+            },
+            CommandResult::Failure(_) => Ok(()),
+        }
+    }
+
+    fn conflict(x: &Command, y: &Command) -> bool {
+        ConflictingRelation::is_related(x, y)
+    }
+
+    fn snapshot(&self) -> Self::Snapshot {
+        self.clients
+            .iter()
+            .map(|(id, money)| (*id, *money))
+            .collect()
+    }
+
+    fn restore(&mut self, snapshot: Self::Snapshot) {
+        self.clients = snapshot.into_iter().collect();
+    }
+}
+
+impl Adversarial for Banking {
+    /// A withdrawal of the whole balance, which conflicts with every action of the client
+    fn conflicting(_action: &Action) -> Action {
+        Action::Withdraw(Money::MAX)
+    }
+}
+
+impl Simulated for Banking {
+    const KIND: ApplicationKind = ApplicationKind::Banking;
+
+    /// Registers the client, and reads its balance
+    fn setup(_client: PeerId) -> Vec<Action> {
+        vec![Action::Register, Action::Get]
+    }
+
+    /// A `Get` and a `Withdraw` if `conflict`, otherwise a `Get` or a `Deposit`
+    fn generate<R: Rng>(rng: &mut R, _client: PeerId, conflict: bool) -> Vec<Action> {
+        if conflict {
+            vec![Action::Get, Action::Withdraw(10)]
+        } else if Uniform::new(0.0, 1.0).sample(rng) < 0.2 {
+            vec![Action::Get]
+        } else {
+            vec![Action::Deposit(10)]
+        }
+    }
+
+    /// Checks the history of each client against a model of its account
+    fn check_history(network: &Network<Self>) -> Result<(), Counterexample> {
+        network.check_linearizability()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -167,4 +290,32 @@ mod tests {
         assert_eq!(banking.get(&1).unwrap(), 30);
         assert_eq!(banking.get(&2), None);
     }
+
+    #[test]
+    fn rollback_undoes_the_execution() {
+        let mut banking = Banking::default();
+        let register = Command::new(1, Action::Register);
+        let deposit = Command::new(1, Action::Deposit(10));
+        let withdraw = Command::new(1, Action::Withdraw(20));
+        let results: Vec<CommandResult> = [&register, &deposit, &withdraw]
+            .iter()
+            .map(|command| banking.execute(command))
+            .collect();
+        assert_eq!(results[2], CommandResult::Failure(String::new()));
+        let snapshot = banking.snapshot();
+        assert_eq!(snapshot.get(&1), Some(&10));
+
+        banking.rollback(&withdraw, &results[2]).unwrap();
+        banking.rollback(&deposit, &results[1]).unwrap();
+        assert_eq!(banking.get(&1), Some(0));
+        banking.rollback(&register, &results[0]).unwrap();
+        assert_eq!(banking.snapshot(), BTreeMap::new());
+
+        banking.restore(snapshot.clone());
+        assert_eq!(banking.snapshot(), snapshot);
+        assert!(Banking::conflict(
+            &withdraw,
+            &Command::new(1, Banking::conflicting(&Action::Get))
+        ));
+    }
 }
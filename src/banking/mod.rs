@@ -1,3 +1,2 @@
 pub mod action;
 pub mod banking;
-pub mod transaction;
@@ -1,12 +1,14 @@
-use std::ops::Range;
+use std::{collections::HashMap, ops::Range, sync::Arc};
 
 use crate::{network::NetworkInfo, peer::peer::PeerId};
-use talk::crypto::Identity;
+use talk::crypto::{Identity, KeyCard, KeyChain};
 
 #[derive(Debug, Clone)]
 pub struct IdentityTable {
     clients: Vec<Identity>,
     replicas: Vec<Identity>,
+    ids: HashMap<Identity, PeerId>,
+    keycards: HashMap<Identity, KeyCard>,
     client_range: Range<PeerId>,
     faulty_client_range: Range<PeerId>,
     replica_range: Range<PeerId>,
@@ -31,6 +33,20 @@ impl IdentityTable {
         self.replicas.get(id)
     }
 
+    /// Returns the `PeerId` of the given peer
+    pub fn peer_id(&self, peer: &Identity) -> Option<PeerId> {
+        self.ids.get(peer).cloned()
+    }
+
+    /// Returns the public key material used to verify the signatures of the given peer
+    pub fn keycard(&self, peer: &Identity) -> Option<&KeyCard> {
+        self.keycards.get(peer)
+    }
+
+    pub fn is_replica(&self, peer: &Identity) -> bool {
+        self.replicas.contains(peer)
+    }
+
     pub fn is_faulty(&self, id: &PeerId) -> bool {
         self.faulty_client_range.contains(id) || self.faulty_replica_range.contains(id)
     }
@@ -50,8 +66,14 @@ impl IdentityTable {
     }
 }
 
+/// Builds the `IdentityTable` of a network. The builder acts as the key distribution center
+/// of the simulation: it holds the talk `KeyChain` of every peer it registers, the one behind the
+/// `Identity` its transport authenticates, so that a signature binds to the sender of a message.
+/// The `IdentityTable` it builds only holds the public `KeyCard` of the peers, while each peer
+/// gets its own `KeyChain` from the builder.
 pub struct IdentityTableBuilder {
     peers_mapping: Vec<Identity>,
+    keychains: HashMap<Identity, Arc<KeyChain>>,
     network_info: NetworkInfo,
 }
 
@@ -59,22 +81,44 @@ impl IdentityTableBuilder {
     pub fn new(network_info: NetworkInfo) -> Self {
         IdentityTableBuilder {
             peers_mapping: Vec::new(),
+            keychains: HashMap::new(),
             network_info,
         }
     }
 
-    pub fn add_peer(&mut self, peer: Identity) -> &mut Self {
+    /// Registers the peer of the given `KeyChain`, whose `Identity` is the one of its `KeyCard`
+    pub fn add_peer(&mut self, keychain: KeyChain) -> &mut Self {
+        let peer = keychain.keycard().identity();
+        self.keychains.insert(peer.clone(), Arc::new(keychain));
         self.peers_mapping.push(peer);
         self
     }
 
+    /// Returns the private key material of the given peer, to be handed to this peer only
+    pub fn keychain(&self, peer: &Identity) -> Option<Arc<KeyChain>> {
+        self.keychains.get(peer).cloned()
+    }
+
     pub fn build(&self) -> IdentityTable {
         let (client_range, faulty_client_range, replica_range, faulty_replica_range) =
             self.network_info.compute_ranges();
         let (clients, replicas) = self.peers_mapping.split_at(faulty_client_range.end);
+        let keycards = self
+            .keychains
+            .iter()
+            .map(|(peer, keychain)| (peer.clone(), keychain.keycard()))
+            .collect();
+        let ids = self
+            .peers_mapping
+            .iter()
+            .enumerate()
+            .map(|(id, peer)| (peer.clone(), id))
+            .collect();
         IdentityTable {
             clients: clients.to_vec(),
             replicas: replicas.to_vec(),
+            ids,
+            keycards,
             client_range,
             faulty_client_range,
             replica_range,
@@ -84,4 +128,36 @@ impl IdentityTableBuilder {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use crate::{
+        banking::action::Action, crypto::statements::IssuedCommand, talk::Command,
+        tests::util::Utils,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn signatures_bind_to_the_identity_of_the_signer() {
+        let network_info = NetworkInfo::with_default_report_folder(1, 1, 0, 0, 10, 1);
+        let (keys, _senders, _receivers) = Utils::mock_network(2).await;
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info);
+        for key in keys.iter() {
+            identity_table_builder.add_peer(Utils::keychain(key));
+        }
+        let identity_table = identity_table_builder.build();
+
+        let command = Command::new(0, Action::Register);
+        let statement = IssuedCommand { command: &command };
+        let signature = identity_table_builder
+            .keychain(&keys[0])
+            .unwrap()
+            .sign(&statement)
+            .unwrap();
+        // The key material of a peer is the one behind its identity on the network
+        for (key, signer) in keys.iter().zip([true, false]) {
+            let keycard = identity_table.keycard(key).unwrap();
+            assert_eq!(keycard.identity(), *key);
+            assert_eq!(signature.verify(keycard, &statement).is_ok(), signer);
+        }
+    }
+}
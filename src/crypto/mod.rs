@@ -1 +1,2 @@
 pub mod identity_table;
+pub mod statements;
@@ -0,0 +1,89 @@
+use serde::Serialize;
+use talk::crypto::Statement;
+
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    database::replica_database::Set,
+    peer::consensus::pbft::ViewChange,
+    talk::{Command, CommandId, CommandResult, Phase, RoundNumber, ViewNumber},
+};
+
+#[derive(Serialize)]
+pub enum Header {
+    CommandAcknowledgement,
+    IssuedCommand,
+    PbftPrepare,
+    PbftViewChange,
+    PbftProposal,
+}
+
+/// Statement signed by a replica when it acknowledges the result of a command to its issuer.
+#[derive(Serialize)]
+#[serde(bound = "")]
+pub struct CommandAcknowledgement<'a, O: Operation = Action> {
+    pub id: &'a CommandId,
+    pub round: &'a RoundNumber,
+    pub result: &'a CommandResult<O>,
+    pub phase: &'a Phase,
+}
+
+impl<'a, O: Operation> Statement for CommandAcknowledgement<'a, O> {
+    type Header = Header;
+    const HEADER: Header = Header::CommandAcknowledgement;
+}
+
+/// Statement signed by a client when it issues a command to the replicas.
+#[derive(Serialize)]
+#[serde(bound = "")]
+pub struct IssuedCommand<'a, O: Operation = Action> {
+    pub command: &'a Command<O>,
+}
+
+impl<'a, O: Operation> Statement for IssuedCommand<'a, O> {
+    type Header = Header;
+    const HEADER: Header = Header::IssuedCommand;
+}
+
+/// Statement signed by a replica when it proposes its (NCSet, CSet) to the consensus of a round.
+/// The leader relays the proposals it aggregates, for every replica to recompute the value.
+#[derive(Serialize)]
+#[serde(bound = "")]
+pub struct PbftProposal<'a, O: Operation = Action> {
+    pub round: &'a RoundNumber,
+    pub nc_set: &'a Set<O>,
+    pub c_set: &'a Set<O>,
+}
+
+impl<'a, O: Operation> Statement for PbftProposal<'a, O> {
+    type Header = Header;
+    const HEADER: Header = Header::PbftProposal;
+}
+
+/// Statement signed by a replica when it prepares the (NCSet, CSet) pre-prepared in a view of
+/// the consensus. A quorum of them proves that the value may have been committed.
+#[derive(Serialize)]
+#[serde(bound = "")]
+pub struct PbftPrepare<'a, O: Operation = Action> {
+    pub round: &'a RoundNumber,
+    pub view: &'a ViewNumber,
+    pub nc_set: &'a Set<O>,
+    pub c_set: &'a Set<O>,
+}
+
+impl<'a, O: Operation> Statement for PbftPrepare<'a, O> {
+    type Header = Header;
+    const HEADER: Header = Header::PbftPrepare;
+}
+
+/// Statement signed by a replica when it moves to a new view of the consensus
+#[derive(Serialize)]
+#[serde(bound = "")]
+pub struct PbftViewChange<'a, O: Operation = Action> {
+    pub view_change: &'a ViewChange<O>,
+}
+
+impl<'a, O: Operation> Statement for PbftViewChange<'a, O> {
+    type Header = Header;
+    const HEADER: Header = Header::PbftViewChange;
+}
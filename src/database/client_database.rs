@@ -1,18 +1,29 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::talk::{Phase, RoundNumber};
+use talk::crypto::{primitives::sign::Signature, Identity};
+
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    talk::{Phase, RoundNumber},
+};
 
 use super::*;
 
-pub type RequestResult = (RoundNumber, CommandResult, Phase);
-type RequestDatabase = HashMap<CommandId, HashMap<RequestResult, usize>>;
-pub struct ClientDatabase {
-    requests: RequestDatabase,
+pub type RequestResult<O = Action> = (RoundNumber, CommandResult<O>, Phase);
+/// Signed acknowledgements of a result, at most one per replica
+pub type Votes = HashMap<Identity, Signature>;
+type RequestDatabase<O> = HashMap<CommandId, HashMap<RequestResult<O>, Votes>>;
+pub struct ClientDatabase<O: Operation = Action> {
+    requests: RequestDatabase<O>,
+    replicas: HashSet<Identity>,
 }
-impl ClientDatabase {
-    pub fn new() -> Self {
+impl<O: Operation> ClientDatabase<O> {
+    /// Creates a database that only accepts the votes of the given replicas
+    pub fn new(replicas: Vec<Identity>) -> Self {
         ClientDatabase {
-            requests: HashMap::<CommandId, HashMap<RequestResult, usize>>::new(),
+            requests: HashMap::new(),
+            replicas: replicas.into_iter().collect(),
         }
     }
 
@@ -26,33 +37,49 @@ impl ClientDatabase {
         Ok(())
     }
 
-    /// Update a request by adding the given `Message` in the database.
-    /// The request must be in the database.
+    /// Update a request by adding the vote of `replica` for the given result.
+    /// Returns the number of distinct replicas that voted for the result.
+    /// It fails if the request is not in the database, if `replica` is not a replica
+    /// or if it already voted for this result.
     pub fn update_request(
         &mut self,
         request_id: &CommandId,
-        request: RequestResult,
+        request: RequestResult<O>,
+        replica: Identity,
+        signature: Signature,
     ) -> Result<usize, DatabaseError> {
-        let nbr = self
+        if !self.replicas.contains(&replica) {
+            return Err(DatabaseError::new("Only replicas can vote for a request"));
+        }
+        let request_db = self
             .requests
             .get_mut(request_id)
-            .map(|request_db| {
-                request_db
-                    .entry(request)
-                    .and_modify(|content| *content += 1)
-                    .or_insert(1)
-            })
-            .map(|nbr| nbr.clone());
-        nbr.ok_or(DatabaseError::from(format!(
-            "Cannot find the request #{}",
-            *request_id
-        )))
+            .ok_or(DatabaseError::from(format!(
+                "Cannot find the request #{}",
+                *request_id
+            )))?;
+        let votes = request_db.entry(request).or_insert_with(HashMap::new);
+        if votes.contains_key(&replica) {
+            return Err(DatabaseError::from(format!(
+                "The replica already voted for the request #{}",
+                *request_id
+            )));
+        }
+        votes.insert(replica, signature);
+        Ok(votes.len())
     }
 
     pub fn contains_request(&self, request: &CommandId) -> bool {
         self.requests.contains_key(request)
     }
 
+    pub fn votes(&self, request_id: &CommandId, request: &RequestResult<O>) -> Option<&Votes> {
+        self.requests
+            .get(request_id)
+            .map(|request_db| request_db.get(request))
+            .flatten()
+    }
+
     pub fn complete_request(&mut self, request_id: &CommandId) -> Result<(), DatabaseError> {
         self.requests
             .remove(request_id)
@@ -63,36 +90,39 @@ impl ClientDatabase {
     pub fn is_request_completed(
         &self,
         request_id: &CommandId,
-        request: &RequestResult,
+        request: &RequestResult<O>,
         bound: usize,
     ) -> Result<bool, DatabaseError> {
         self.requests
             .get(request_id)
             .map(|request_db| request_db.get(request))
             .flatten()
-            .map(|nbr| nbr.eq(&bound))
+            .map(|votes| votes.len().eq(&bound))
             .ok_or(DatabaseError::new(&format!(
                 "Cannot retrieve the request {}",
                 *request_id
             )))
     }
 
-    pub fn requests(&self) -> &RequestDatabase {
+    pub fn requests(&self) -> &RequestDatabase<O> {
         &self.requests
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use talk::crypto::KeyChain;
     use uuid::Uuid;
 
-    use crate::{banking::action::Action, talk::Command};
+    use crate::{
+        banking::action::Action, crypto::statements::CommandAcknowledgement, talk::Command,
+    };
 
     use super::*;
 
     #[test]
     fn add_request_works() {
-        let mut db = ClientDatabase::new();
+        let mut db: ClientDatabase = ClientDatabase::new(Vec::new());
         let request = Command::new(0, Action::Register);
         assert_eq!(db.contains_request(request.id()), false);
         db.add_request(request.id().clone()).unwrap();
@@ -103,16 +133,54 @@ mod tests {
 
     #[test]
     fn request_id_is_resistant() {
-        let mut db = ClientDatabase::new();
+        let mut db: ClientDatabase = ClientDatabase::new(Vec::new());
         for _ in 0..10000 {
             db.add_request(Uuid::new_v4()).unwrap();
         }
         print!("ID: {}", Uuid::new_v4());
     }
 
+    #[test]
+    fn update_request_counts_one_vote_per_replica() {
+        let keychain = KeyChain::random();
+        let signer = keychain.keycard().identity();
+        let other = KeyChain::random().keycard().identity();
+        let outsider = KeyChain::random().keycard().identity();
+        let mut db = ClientDatabase::new(vec![signer.clone(), other.clone()]);
+        let request = Command::generate_id();
+        db.add_request(request).unwrap();
+
+        let result: RequestResult = (0, CommandResult::Success(None), Phase::ACK);
+        let statement = CommandAcknowledgement {
+            id: &request,
+            round: &result.0,
+            result: &result.1,
+            phase: &result.2,
+        };
+        let signature = keychain.sign(&statement).unwrap();
+
+        let count = db
+            .update_request(&request, result.clone(), signer.clone(), signature)
+            .unwrap();
+        assert_eq!(count, 1);
+        let res = db.update_request(&request, result.clone(), signer.clone(), signature);
+        assert_eq!(res.is_err(), true);
+        let res = db.update_request(&request, result.clone(), outsider, signature);
+        assert_eq!(res.is_err(), true);
+        let count = db
+            .update_request(&request, result.clone(), other, signature)
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(db.is_request_completed(&request, &result, 2).unwrap(), true);
+        assert_eq!(db.votes(&request, &result).unwrap().len(), 2);
+
+        let res = db.update_request(&Command::generate_id(), result, signer, signature);
+        assert_eq!(res.is_err(), true);
+    }
+
     #[test]
     fn complete_request_correctly_removes() {
-        let mut db = ClientDatabase::new();
+        let mut db: ClientDatabase = ClientDatabase::new(Vec::new());
         let request = Command::generate_id();
         db.add_request(request).unwrap();
         db.add_request(Command::generate_id()).unwrap();
@@ -1,5 +1,6 @@
 pub mod client_database;
 pub mod replica_database;
+pub mod transaction;
 
 use crate::talk::CommandResult;
 use crate::{error::DatabaseError, talk::CommandId};
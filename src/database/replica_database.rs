@@ -1,12 +1,19 @@
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use talk::crypto::primitives::sign::Signature;
 
 use crate::{
-    banking::transaction::Transaction,
+    application::Operation,
+    banking::action::Action,
     talk::{Command, CommandResult},
 };
 
-pub type Set = BTreeSet<Command>;
-pub type ResultBuffer = HashMap<Command, CommandResult>;
+use super::transaction::Transaction;
+
+pub type Set<O = Action> = BTreeSet<Command<O>>;
+/// Commands relayed by a replica, along with the signature of their issuer, if known
+pub type SignedSet<O = Action> = BTreeMap<Command<O>, Option<Signature>>;
+pub type ResultBuffer<O = Action> = HashMap<Command<O>, CommandResult<O>>;
 /// Defines the data structures for a `ReplicaHanlder`.
 /// `received` contains all the messages broadcasted in the network so far.
 /// `delivered` contains all the messages that have been delivered (i.e. validated) by the `Replica` in
@@ -14,16 +21,16 @@ pub type ResultBuffer = HashMap<Command, CommandResult>;
 /// `pending` defines the set of non-conflicting messages acknowledged by the replica in the current round.
 /// `ack_delievered` defines the set of messages delivered in the ACK phase of the current round.
 /// `round` is the current round.
-pub struct ReplicaDatabase {
-    received: Set,
-    delivered: Set,
-    pending: Set,
-    results: ResultBuffer,
+pub struct ReplicaDatabase<O: Operation = Action> {
+    received: Set<O>,
+    delivered: Set<O>,
+    pending: Set<O>,
+    results: ResultBuffer<O>,
     round: usize,
-    log: Vec<Transaction>,
+    log: Vec<Transaction<O>>,
 }
 
-impl ReplicaDatabase {
+impl<O: Operation> ReplicaDatabase<O> {
     pub fn new() -> Self {
         ReplicaDatabase {
             received: BTreeSet::new(),
@@ -36,20 +43,20 @@ impl ReplicaDatabase {
     }
 
     /// Returns true if the value was not present
-    pub fn receive_command(&mut self, command: Command) -> bool {
+    pub fn receive_command(&mut self, command: Command<O>) -> bool {
         self.received.insert(command)
     }
 
-    pub fn receive_set(&mut self, set: &mut Set) {
+    pub fn receive_set(&mut self, set: &mut Set<O>) {
         self.received.append(set)
     }
 
-    pub fn delivered_all(&mut self, set: &Set) {
+    pub fn delivered_all(&mut self, set: &Set<O>) {
         self.delivered = self.delivered.union(&set.clone()).cloned().collect();
     }
 
     /// Set pending set to {set}
-    pub fn set_pending(&mut self, set: Set) -> () {
+    pub fn set_pending(&mut self, set: Set<O>) -> () {
         self.pending = set;
     }
 
@@ -62,34 +69,34 @@ impl ReplicaDatabase {
     }
 
     /// Returns false if a command was overwritten
-    pub fn add_result(&mut self, cmd: Command, res: CommandResult) -> bool {
+    pub fn add_result(&mut self, cmd: Command<O>, res: CommandResult<O>) -> bool {
         self.results
             .insert(cmd, res)
             .map(|_previous| false)
             .unwrap_or(true)
     }
 
-    pub fn remove_result(&mut self, cmd: &Command) -> Option<CommandResult> {
+    pub fn remove_result(&mut self, cmd: &Command<O>) -> Option<CommandResult<O>> {
         self.results.remove(cmd)
     }
 
-    pub fn received(&self) -> &Set {
+    pub fn received(&self) -> &Set<O> {
         &self.received
     }
 
-    pub fn delivered(&self) -> &Set {
+    pub fn delivered(&self) -> &Set<O> {
         &self.delivered
     }
 
-    pub fn pending(&self) -> &Set {
+    pub fn pending(&self) -> &Set<O> {
         &self.pending
     }
 
-    pub fn pending_mut(&mut self) -> &mut Set {
+    pub fn pending_mut(&mut self) -> &mut Set<O> {
         &mut self.pending
     }
 
-    pub fn results(&self) -> &ResultBuffer {
+    pub fn results(&self) -> &ResultBuffer<O> {
         &self.results
     }
 
@@ -101,27 +108,27 @@ impl ReplicaDatabase {
         self.round += 1;
     }
 
-    pub fn received_mut(&mut self) -> &mut Set {
+    pub fn received_mut(&mut self) -> &mut Set<O> {
         &mut self.received
     }
 
-    pub fn delivered_mut(&mut self) -> &mut Set {
+    pub fn delivered_mut(&mut self) -> &mut Set<O> {
         &mut self.delivered
     }
 
-    pub fn results_mut(&mut self) -> &mut ResultBuffer {
+    pub fn results_mut(&mut self) -> &mut ResultBuffer<O> {
         &mut self.results
     }
 
-    pub fn log(&mut self, transaction: Transaction) {
+    pub fn log(&mut self, transaction: Transaction<O>) {
         self.log.push(transaction)
     }
 
-    pub fn logs_mut(&mut self) -> &mut Vec<Transaction> {
+    pub fn logs_mut(&mut self) -> &mut Vec<Transaction<O>> {
         &mut self.log
     }
 
-    pub fn logs(&self) -> &Vec<Transaction> {
+    pub fn logs(&self) -> &Vec<Transaction<O>> {
         &self.log
     }
 }
@@ -3,26 +3,27 @@ use std::fmt::Display;
 use uuid::Uuid;
 
 use crate::{
+    application::Operation,
+    banking::action::Action,
     peer::peer::PeerId,
     talk::{Command, CommandResult},
 };
 
-use super::action::Action;
-
-pub struct Transaction {
+/// Execution of a command by a replica, kept in its log
+pub struct Transaction<O: Operation = Action> {
     id: Uuid,
     issuer: PeerId,
-    action: Action,
-    result: CommandResult,
+    action: O,
+    result: CommandResult<O>,
     status: Status,
 }
 
-impl Transaction {
+impl<O: Operation> Transaction<O> {
     pub fn new(
         id: Uuid,
         issuer: PeerId,
-        action: Action,
-        result: CommandResult,
+        action: O,
+        result: CommandResult<O>,
         status: Status,
     ) -> Self {
         Transaction {
@@ -38,7 +39,7 @@ impl Transaction {
         &self.id
     }
 
-    pub fn from_command(command: &Command, result: &CommandResult) -> Self {
+    pub fn from_command(command: &Command<O>, result: &CommandResult<O>) -> Self {
         Transaction::new(
             *command.id(),
             *command.issuer(),
@@ -53,7 +54,7 @@ impl Transaction {
     }
 }
 
-impl Display for Transaction {
+impl<O: Operation> Display for Transaction<O> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
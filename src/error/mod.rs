@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use crate::talk::FeedbackSender;
 
 #[derive(Debug, Clone)]
@@ -35,3 +37,123 @@ pub enum BankingError {
     ClientNotFound,
     UnsufficientBalance,
 }
+
+/// Reasons for `KvStore` to refuse a rollback
+#[derive(Debug, PartialEq, Eq)]
+pub enum KvError {
+    /// The result does not have the shape of a successful result of the command
+    MismatchedResult,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConsensusError {
+    Closed,
+}
+
+/// Reasons for a scenario file to be rejected
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    Invalid(String),
+}
+
+impl Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScenarioError::Io(error) => write!(f, "cannot read the scenario: {}", error),
+            ScenarioError::Parse(error) => write!(f, "cannot parse the scenario: {}", error),
+            ScenarioError::Invalid(reason) => write!(f, "invalid scenario: {}", reason),
+        }
+    }
+}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(error: std::io::Error) -> Self {
+        ScenarioError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ScenarioError {
+    fn from(error: serde_json::Error) -> Self {
+        ScenarioError::Parse(error)
+    }
+}
+
+/// Reasons for a replica to drop a `Message::Command`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandRejection {
+    Unsigned,
+    UnknownIssuer,
+    SenderMismatch,
+    InvalidSignature,
+    NotReplica,
+}
+
+impl Display for CommandRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            CommandRejection::Unsigned => "the command is not signed",
+            CommandRejection::UnknownIssuer => "the issuer is not a client",
+            CommandRejection::SenderMismatch => "the sender is not the issuer",
+            CommandRejection::InvalidSignature => "the signature is invalid",
+            CommandRejection::NotReplica => "the broadcast does not come from a replica",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Reasons for the model checker to reject a schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// The step at the given position is not enabled once the previous steps are taken
+    Disabled(usize),
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::Disabled(position) => {
+                write!(f, "step {} of the schedule is not enabled", position)
+            }
+        }
+    }
+}
+
+/// Reasons for a `Topology` not to fit a network of the given size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopologyError {
+    /// The latency matrix does not have a row and a column per peer
+    MatrixSize { size: usize },
+    /// The regions do not locate every peer, and only them
+    RegionCount { regions: usize, size: usize },
+    /// The RTTs are not given between every pair of regions
+    RttsSize,
+    /// The peer is located in a region without RTTs
+    UnknownRegion { peer: usize, region: usize },
+}
+
+impl Display for TopologyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TopologyError::MatrixSize { size } => {
+                write!(f, "the latency matrix is not {} x {}", size, size)
+            }
+            TopologyError::RegionCount { regions, size } => {
+                write!(
+                    f,
+                    "{} peers are located in a region instead of {}",
+                    regions, size
+                )
+            }
+            TopologyError::RttsSize => write!(f, "the RTT matrix is not square"),
+            TopologyError::UnknownRegion { peer, region } => {
+                write!(
+                    f,
+                    "peer #{} is located in the unknown region {}",
+                    peer, region
+                )
+            }
+        }
+    }
+}
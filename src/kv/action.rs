@@ -0,0 +1,130 @@
+use std::{collections::BTreeSet, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::application::Operation;
+
+pub type Key = String;
+pub type Value = String;
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KvAction {
+    Get(Key),
+    Put(Key, Value),
+    Delete(Key),
+    /// Replaces the value of the key by `new` if it currently holds `expected`, where `None`
+    /// stands for a missing key
+    CompareAndSwap {
+        key: Key,
+        expected: Option<Value>,
+        new: Value,
+    },
+    /// Executes the actions in order, and atomically: if one of them fails, the previous ones
+    /// are undone and the whole batch fails
+    Batch(Vec<KvAction>),
+}
+
+/// Output of a successful `KvAction`
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Reply {
+    /// Value read by `Get`, or replaced by `Put` and `Delete`
+    Value(Value),
+    /// Outputs of the actions of a `Batch`, in order
+    Batch(Vec<Option<Reply>>),
+}
+
+impl KvAction {
+    /// Keys the action may read without writing them
+    pub fn reads(&self) -> BTreeSet<&Key> {
+        match self {
+            KvAction::Get(key) => vec![key].into_iter().collect(),
+            KvAction::Batch(actions) => actions.iter().flat_map(|action| action.reads()).collect(),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    /// Keys the action may write. A `CompareAndSwap` writes its key even if it ends up failing.
+    pub fn writes(&self) -> BTreeSet<&Key> {
+        match self {
+            KvAction::Get(_) => BTreeSet::new(),
+            KvAction::Put(key, _) | KvAction::Delete(key) => vec![key].into_iter().collect(),
+            KvAction::CompareAndSwap { key, .. } => vec![key].into_iter().collect(),
+            KvAction::Batch(actions) => actions.iter().flat_map(|action| action.writes()).collect(),
+        }
+    }
+
+    pub fn keys(&self) -> BTreeSet<&Key> {
+        self.reads().union(&self.writes()).cloned().collect()
+    }
+}
+
+impl Operation for KvAction {
+    type Output = Reply;
+
+    /// Name of the variant, regardless of the keys and values
+    fn kind(&self) -> &'static str {
+        match self {
+            KvAction::Get(_) => "Get",
+            KvAction::Put(_, _) => "Put",
+            KvAction::Delete(_) => "Delete",
+            KvAction::CompareAndSwap { .. } => "CompareAndSwap",
+            KvAction::Batch(_) => "Batch",
+        }
+    }
+}
+
+impl Display for KvAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            KvAction::Get(key) => format!("Get {}", key),
+            KvAction::Put(key, value) => format!("Put {}={}", key, value),
+            KvAction::Delete(key) => format!("Delete {}", key),
+            KvAction::CompareAndSwap { key, new, .. } => format!("CAS {}={}", key, new),
+            KvAction::Batch(actions) => format!("Batch of {}", actions.len()),
+        };
+
+        write!(f, "{:<16.16}", str)
+    }
+}
+
+impl Display for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reply::Value(value) => write!(f, "{}", value),
+            Reply::Batch(replies) => {
+                let replies: Vec<String> = replies
+                    .iter()
+                    .map(|reply| match reply {
+                        Some(reply) => reply.to_string(),
+                        None => String::from("-"),
+                    })
+                    .collect();
+                write!(f, "[{}]", replies.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_access_the_keys_of_their_actions() {
+        let batch = KvAction::Batch(vec![
+            KvAction::Get(String::from("a")),
+            KvAction::Put(String::from("b"), String::from("1")),
+            KvAction::CompareAndSwap {
+                key: String::from("c"),
+                expected: None,
+                new: String::from("2"),
+            },
+        ]);
+        let a = String::from("a");
+        let (b, c) = (String::from("b"), String::from("c"));
+        assert_eq!(batch.reads(), vec![&a].into_iter().collect());
+        assert_eq!(batch.writes(), vec![&b, &c].into_iter().collect());
+        assert_eq!(batch.keys().len(), 3);
+        assert_eq!(batch.kind(), "Batch");
+    }
+}
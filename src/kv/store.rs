@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use rand_distr::{Distribution, Uniform};
+
+use crate::{
+    application::Application,
+    error::KvError,
+    peer::{handler::Adversarial, peer::PeerId},
+    relation::{key::KeyRelation, Relation},
+    simulation::{ApplicationKind, Simulated},
+    talk::{Command, CommandResult},
+};
+
+use super::action::{Key, KvAction, Reply, Value};
+
+/// Represents a key-value store, distributed over some sets of replicas.
+/// Every client accesses the same keys, and the operations writing a key are ordered.
+/// Successful writes return the value they replaced, so that a speculative execution can be
+/// undone from its result alone.
+/// Each replica has its own store instance.
+#[derive(Default)]
+pub struct KvStore {
+    entries: BTreeMap<Key, Value>,
+}
+
+impl KvStore {
+    pub fn new() -> Self {
+        KvStore {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    pub fn entries(&self) -> &BTreeMap<Key, Value> {
+        &self.entries
+    }
+
+    /// Applies the action and returns its output, or the reason of its failure.
+    /// A failed action leaves the store unchanged.
+    fn apply(&mut self, action: &KvAction) -> Result<Option<Reply>, String> {
+        match action {
+            KvAction::Get(key) => Ok(self.entries.get(key).cloned().map(Reply::Value)),
+            KvAction::Put(key, value) => Ok(self
+                .entries
+                .insert(key.clone(), value.clone())
+                .map(Reply::Value)),
+            KvAction::Delete(key) => Ok(self.entries.remove(key).map(Reply::Value)),
+            KvAction::CompareAndSwap { key, expected, new } => {
+                let current = self.entries.get(key);
+                if current != expected.as_ref() {
+                    return Err(format!(
+                        "Key `{}` holds {:?} instead of {:?}",
+                        key, current, expected
+                    ));
+                }
+                self.entries.insert(key.clone(), new.clone());
+                Ok(None)
+            }
+            KvAction::Batch(actions) => {
+                let mut outputs: Vec<Option<Reply>> = Vec::new();
+                for (index, action) in actions.iter().enumerate() {
+                    match self.apply(action) {
+                        Ok(output) => outputs.push(output),
+                        Err(reason) => {
+                            // Undo the actions already applied, in reverse order
+                            for (action, output) in actions.iter().zip(outputs.iter()).rev() {
+                                self.undo(action, output)
+                                    .expect("An applied action is always undone");
+                            }
+                            return Err(format!(
+                                "Action #{} of the batch failed: {}",
+                                index, reason
+                            ));
+                        }
+                    }
+                }
+                Ok(Some(Reply::Batch(outputs)))
+            }
+        }
+    }
+
+    /// Undoes the successful application of the action, which returned `output`
+    fn undo(&mut self, action: &KvAction, output: &Option<Reply>) -> Result<(), KvError> {
+        match (action, output) {
+            (KvAction::Get(_), _) => Ok(()),
+            (KvAction::Put(key, _), previous) | (KvAction::Delete(key), previous) => {
+                match previous {
+                    Some(Reply::Value(value)) => {
+                        self.entries.insert(key.clone(), value.clone());
+                    }
+                    None => {
+                        self.entries.remove(key);
+                    }
+                    Some(Reply::Batch(_)) => return Err(KvError::MismatchedResult),
+                }
+                Ok(())
+            }
+            (KvAction::CompareAndSwap { key, expected, .. }, None) => {
+                match expected {
+                    Some(value) => self.entries.insert(key.clone(), value.clone()),
+                    None => self.entries.remove(key),
+                };
+                Ok(())
+            }
+            (KvAction::Batch(actions), Some(Reply::Batch(outputs)))
+                if actions.len() == outputs.len() =>
+            {
+                for (action, output) in actions.iter().zip(outputs.iter()).rev() {
+                    self.undo(action, output)?;
+                }
+                Ok(())
+            }
+            _ => Err(KvError::MismatchedResult),
+        }
+    }
+}
+
+/// The commands conflict according to `KeyRelation`
+impl Application for KvStore {
+    type Action = KvAction;
+    type Snapshot = BTreeMap<Key, Value>;
+    type Error = KvError;
+
+    fn execute(&mut self, command: &Command<KvAction>) -> CommandResult<KvAction> {
+        match self.apply(command.action()) {
+            Ok(output) => CommandResult::Success(output),
+            Err(reason) => CommandResult::Failure(reason),
+        }
+    }
+
+    fn rollback(
+        &mut self,
+        command: &Command<KvAction>,
+        result: &CommandResult<KvAction>,
+    ) -> Result<(), KvError> {
+        match result {
+            // A failed command left the store unchanged
+            CommandResult::Success(output) => self.undo(command.action(), output),
+            CommandResult::Failure(_) => Ok(()),
+        }
+    }
+
+    fn conflict(x: &Command<KvAction>, y: &Command<KvAction>) -> bool {
+        KeyRelation::is_related(x, y)
+    }
+
+    fn snapshot(&self) -> Self::Snapshot {
+        self.entries.clone()
+    }
+
+    fn restore(&mut self, snapshot: Self::Snapshot) {
+        self.entries = snapshot;
+    }
+}
+
+impl Adversarial for KvStore {
+    /// A deletion of every key the action accesses
+    fn conflicting(action: &KvAction) -> KvAction {
+        let mut deletions: Vec<KvAction> = action
+            .keys()
+            .into_iter()
+            .map(|key| KvAction::Delete(key.clone()))
+            .collect();
+        match deletions.len() {
+            0 => KvAction::Delete(Key::new()),
+            1 => deletions.remove(0),
+            _ => KvAction::Batch(deletions),
+        }
+    }
+}
+
+impl Simulated for KvStore {
+    const KIND: ApplicationKind = ApplicationKind::Kv;
+
+    /// A `Get` and a `Put` of the key shared by every client if `conflict`, otherwise a `Get` or
+    /// a `Put` of the key of the client
+    fn generate<R: Rng>(rng: &mut R, client: PeerId, conflict: bool) -> Vec<KvAction> {
+        if conflict {
+            let shared = Key::from("shared");
+            vec![
+                KvAction::Get(shared.clone()),
+                KvAction::Put(shared, client.to_string()),
+            ]
+        } else if Uniform::new(0.0, 1.0).sample(rng) < 0.2 {
+            vec![KvAction::Get(format!("k{}", client))]
+        } else {
+            vec![KvAction::Put(format!("k{}", client), Value::from("10"))]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::generator::Generator;
+
+    use super::*;
+
+    fn execute(store: &mut KvStore, action: KvAction) -> CommandResult<KvAction> {
+        store.execute(&Command::new(0, action))
+    }
+
+    fn value(value: &str) -> Option<Reply> {
+        Some(Reply::Value(String::from(value)))
+    }
+
+    #[test]
+    fn writes_return_the_replaced_value() {
+        let mut store = KvStore::new();
+        let key = || String::from("a");
+        let put = |v: &str| KvAction::Put(key(), String::from(v));
+
+        assert_eq!(
+            execute(&mut store, KvAction::Get(key())),
+            CommandResult::Success(None)
+        );
+        assert_eq!(execute(&mut store, put("1")), CommandResult::Success(None));
+        assert_eq!(
+            execute(&mut store, put("2")),
+            CommandResult::Success(value("1"))
+        );
+        assert_eq!(
+            execute(&mut store, KvAction::Get(key())),
+            CommandResult::Success(value("2"))
+        );
+        assert_eq!(
+            execute(&mut store, KvAction::Delete(key())),
+            CommandResult::Success(value("2"))
+        );
+        assert_eq!(store.get(&key()), None);
+    }
+
+    #[test]
+    fn compare_and_swap_only_replaces_the_expected_value() {
+        let mut store = KvStore::new();
+        let cas = |expected: Option<&str>, new: &str| KvAction::CompareAndSwap {
+            key: String::from("a"),
+            expected: expected.map(String::from),
+            new: String::from(new),
+        };
+
+        assert_eq!(
+            execute(&mut store, cas(None, "1")),
+            CommandResult::Success(None)
+        );
+        assert!(matches!(
+            execute(&mut store, cas(None, "2")),
+            CommandResult::Failure(_)
+        ));
+        assert!(matches!(
+            execute(&mut store, cas(Some("2"), "3")),
+            CommandResult::Failure(_)
+        ));
+        assert_eq!(
+            execute(&mut store, cas(Some("1"), "3")),
+            CommandResult::Success(None)
+        );
+        assert_eq!(store.get(&String::from("a")), Some(&String::from("3")));
+    }
+
+    #[test]
+    fn failed_batches_leave_the_store_unchanged() {
+        let mut store = KvStore::new();
+        let (a, b) = (String::from("a"), String::from("b"));
+        execute(&mut store, KvAction::Put(a.clone(), String::from("1")));
+
+        let batch = KvAction::Batch(vec![
+            KvAction::Put(b.clone(), String::from("2")),
+            KvAction::Delete(a.clone()),
+            KvAction::CompareAndSwap {
+                key: a.clone(),
+                expected: Some(String::from("1")),
+                new: String::from("3"),
+            },
+        ]);
+        let snapshot = store.snapshot();
+        assert!(matches!(
+            execute(&mut store, batch),
+            CommandResult::Failure(_)
+        ));
+        assert_eq!(store.snapshot(), snapshot);
+
+        let batch = KvAction::Batch(vec![
+            KvAction::Put(b.clone(), String::from("2")),
+            KvAction::Get(a.clone()),
+        ]);
+        assert_eq!(
+            execute(&mut store, batch),
+            CommandResult::Success(Some(Reply::Batch(vec![None, value("1")])))
+        );
+        assert_eq!(store.get(&b), Some(&String::from("2")));
+    }
+
+    #[test]
+    fn rollback_undoes_the_executions() {
+        Generator::check(|generator| {
+            let mut store = KvStore::new();
+            for _ in 0..5 {
+                store.execute(&generator.kv_command(2));
+            }
+            let snapshot = store.snapshot();
+
+            let commands: Vec<Command<KvAction>> =
+                (0..8).map(|_| generator.kv_command(2)).collect();
+            let results: Vec<CommandResult<KvAction>> = commands
+                .iter()
+                .map(|command| store.execute(command))
+                .collect();
+            for (command, result) in commands.iter().zip(results.iter()).rev() {
+                store
+                    .rollback(command, result)
+                    .map_err(|err| format!("Cannot rollback {:?}: {:?}", command, err))?;
+            }
+            if store.snapshot() != snapshot {
+                return Err(format!("Rolling back {:?} changed the store", commands));
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn commands_that_do_not_conflict_commute() {
+        Generator::check(|generator| {
+            let mut store = KvStore::new();
+            for _ in 0..5 {
+                store.execute(&generator.kv_command(2));
+            }
+            let (x, y) = (generator.kv_command(2), generator.kv_command(2));
+            if KvStore::conflict(&x, &y) {
+                return Ok(());
+            }
+
+            let mut other = KvStore::new();
+            other.restore(store.snapshot());
+            let results = (store.execute(&x), store.execute(&y));
+            let swapped = (other.execute(&y), other.execute(&x));
+            if results != (swapped.1, swapped.0) || store.snapshot() != other.snapshot() {
+                return Err(format!("{:?} and {:?} do not commute", x, y));
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn conflicting_action_conflicts() {
+        Generator::check(|generator| {
+            let command = generator.kv_command(2);
+            let conflicting = Command::new(0, KvStore::conflicting(command.action()));
+            if !command.action().keys().is_empty() && !KvStore::conflict(&command, &conflicting) {
+                return Err(format!(
+                    "{:?} does not conflict with {:?}",
+                    conflicting, command
+                ));
+            }
+            Ok(())
+        });
+    }
+}
@@ -1,10 +1,13 @@
+pub mod application;
 pub mod banking;
 pub mod crypto;
 pub mod database;
 pub mod error;
+pub mod kv;
 pub mod network;
 pub mod peer;
 pub mod relation;
+pub mod simulation;
 pub mod talk;
 pub mod types;
 
@@ -0,0 +1,281 @@
+use std::{env, fs, path::Path, process};
+
+use rand::{thread_rng, Rng};
+
+use genericbft::{
+    banking::banking::Banking,
+    kv::store::KvStore,
+    network::{ChromeTrace, Trace},
+    simulation::{
+        scenarios::{ScenarioParameters, SweepParameter},
+        ApplicationKind, ScenarioFile, Scenarios, Simulated,
+    },
+    talk::CommandId,
+};
+
+const DEFAULT_OUTPUT_FOLDER: &str = "reports/scenarios";
+
+const USAGE: &str = "Usage:
+    genericbft run <scenario.json> [--out <folder>]
+        Runs the simulation described by a scenario file, of the application it names with
+        \"application\" (Banking by default). The run is replayed from the seed of its report
+        only with \"virtual_time\": true
+    genericbft sweep <parameter> <low> <high> <step> [--out <folder>] [--seed <seed>]
+            [--precision <n>] [--transmission-delay <ms>] [--consensus-duration <s>]
+            [--conflict-probability <p>] [--clients <n>] [--replicas <n>] [--faulty-replicas <n>]
+            [--application <banking|kv>]
+        Runs a scenario for every value of the parameter in [low, high), where the parameter is
+        one of transmission-delay, consensus-duration, conflict-probability or replicas.
+        With --seed, the sweep runs in virtual time to be replayable, which requires building
+        with `--features virtual-time`
+    genericbft trace <trace.jsonl> [--peer <id>] [--command <id>] [--chrome <timeline.json>]
+        Prints the events of a trace, written by the scenarios with \"trace\": true,
+        optionally only those of a peer or those involving a command, or exports its timeline
+        to the Chrome trace-event format, to open in chrome://tracing or ui.perfetto.dev
+    genericbft help
+        Prints this message
+
+The reports (text, CSV and JSON Lines) are written to <folder> (default: reports/scenarios),
+and the logs to <folder>/logs.";
+
+/// Command line arguments of a subcommand: positional arguments and `--name value` options
+struct Arguments {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Arguments {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => match args.next() {
+                    Some(value) => options.push((String::from(name), value)),
+                    None => return Err(format!("Missing value of --{}", name)),
+                },
+                None => positional.push(arg),
+            }
+        }
+        Ok(Arguments {
+            positional,
+            options,
+        })
+    }
+
+    fn positional(&self, index: usize, name: &str) -> Result<&String, String> {
+        self.positional
+            .get(index)
+            .ok_or_else(|| format!("Missing argument <{}>", name))
+    }
+
+    /// Parses the value of the option `name`, if given
+    fn option<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.options.iter().find(|(option, _)| option == name) {
+            Some((_, value)) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid value of --{}: {:?}", name, value)),
+            None => Ok(None),
+        }
+    }
+
+    /// Rejects the options that the subcommand does not know
+    fn check_options(&self, known: &[&str]) -> Result<(), String> {
+        match self
+            .options
+            .iter()
+            .find(|(name, _)| !known.contains(&name.as_str()))
+        {
+            Some((name, _)) => Err(format!("Unknown option --{}", name)),
+            None => Ok(()),
+        }
+    }
+
+    fn output_folder(&self) -> Result<String, String> {
+        let folder = self
+            .option("out")?
+            .unwrap_or_else(|| String::from(DEFAULT_OUTPUT_FOLDER));
+        fs::create_dir_all(format!("{}/logs", folder))
+            .map_err(|e| format!("Couldn't create {}: {}", folder, e))?;
+        Ok(folder)
+    }
+}
+
+fn parse_number(value: &str, name: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid <{}>: {:?}", name, value))
+}
+
+async fn run(arguments: Arguments) -> Result<(), String> {
+    arguments.check_options(&["out"])?;
+    let path = arguments.positional(0, "scenario.json")?;
+    let json = fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path, e))?;
+    match ApplicationKind::of_scenario(&json).map_err(|e| e.to_string())? {
+        ApplicationKind::Banking => run_scenario::<Banking>(&arguments, path, &json).await,
+        ApplicationKind::Kv => run_scenario::<KvStore>(&arguments, path, &json).await,
+    }
+}
+
+/// Runs the scenario file `json` of the application `A`, read from `path`
+async fn run_scenario<A: Simulated>(
+    arguments: &Arguments,
+    path: &str,
+    json: &str,
+) -> Result<(), String> {
+    let mut scenario = ScenarioFile::<A>::from_json(json).map_err(|e| e.to_string())?;
+    let folder = arguments.output_folder()?;
+    scenario
+        .network
+        .report_folder
+        .get_or_insert_with(|| format!("{}/logs", folder));
+
+    let name = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("scenario"));
+    let report = format!("{}/{}.txt", folder, name);
+    if scenario.network.virtual_time {
+        pause_clock()?;
+    }
+    // The report starts with the seed of the simulation, to replay it
+    let seed = *scenario.seed.get_or_insert_with(|| thread_rng().gen());
+    let mut scenarios = Scenarios::with_seed(&report, seed);
+    let time_taken = scenarios.scenario_file(&scenario).await;
+    println!(
+        "{}: complete in {:#?}, see {}",
+        scenario.title, time_taken, report
+    );
+    Ok(())
+}
+
+/// Pauses the clock: sleeps and timeouts then complete as soon as every task is idle, so that
+/// the run only depends on the seed
+#[cfg(feature = "virtual-time")]
+fn pause_clock() -> Result<(), String> {
+    tokio::time::pause();
+    Ok(())
+}
+
+#[cfg(not(feature = "virtual-time"))]
+fn pause_clock() -> Result<(), String> {
+    Err(String::from(
+        "Virtual time requires building with `--features virtual-time`",
+    ))
+}
+
+async fn sweep(arguments: Arguments) -> Result<(), String> {
+    arguments.check_options(&[
+        "out",
+        "seed",
+        "precision",
+        "transmission-delay",
+        "consensus-duration",
+        "conflict-probability",
+        "clients",
+        "replicas",
+        "faulty-replicas",
+        "application",
+    ])?;
+    let parameter: SweepParameter = arguments.positional(0, "parameter")?.parse()?;
+    let low = parse_number(arguments.positional(1, "low")?, "low")?;
+    let high = parse_number(arguments.positional(2, "high")?, "high")?;
+    let step = parse_number(arguments.positional(3, "step")?, "step")?;
+    if step <= 0.0 {
+        return Err(String::from("The step must be positive"));
+    }
+    let folder = arguments.output_folder()?;
+
+    let default = ScenarioParameters::default();
+    let parameters = ScenarioParameters {
+        report_folder: format!("{}/logs", folder),
+        precision: arguments.option("precision")?.unwrap_or(default.precision),
+        transmission_delay: arguments
+            .option("transmission-delay")?
+            .unwrap_or(default.transmission_delay),
+        consensus_duration: arguments
+            .option("consensus-duration")?
+            .unwrap_or(default.consensus_duration),
+        probability_of_conflict: arguments
+            .option("conflict-probability")?
+            .unwrap_or(default.probability_of_conflict),
+        clients: arguments.option("clients")?.unwrap_or(default.clients),
+        replicas: arguments.option("replicas")?.unwrap_or(default.replicas),
+        faulty_replicas: arguments
+            .option("faulty-replicas")?
+            .unwrap_or(default.faulty_replicas),
+        application: arguments
+            .option("application")?
+            .unwrap_or(default.application),
+    };
+
+    let report = format!("{}/sweep_{}.txt", folder, parameter);
+    let mut scenarios = match arguments.option("seed")? {
+        Some(seed) => {
+            // The seed only replays the random decisions if the tasks run in the same order
+            pause_clock()?;
+            Scenarios::with_seed(&report, seed)
+        }
+        None => Scenarios::new(&report),
+    };
+    let results = scenarios
+        .sweep(parameter, low, high, step, &parameters)
+        .await;
+    for (value, time_taken) in results {
+        println!("f({}) = {:#?}", value, time_taken);
+    }
+    println!("See {}", report);
+    Ok(())
+}
+
+fn trace(arguments: Arguments) -> Result<(), String> {
+    arguments.check_options(&["peer", "command", "chrome"])?;
+    let path = arguments.positional(0, "trace.jsonl")?;
+    let trace = Trace::from_file(path).map_err(|e| format!("Couldn't load {}: {}", path, e))?;
+    if let Some(timeline) = arguments.option::<String>("chrome")? {
+        ChromeTrace::new(&trace)
+            .write(&timeline)
+            .map_err(|e| format!("Couldn't write {}: {}", timeline, e))?;
+        println!("See {}", timeline);
+        return Ok(());
+    }
+    let peer: Option<usize> = arguments.option("peer")?;
+    let command: Option<CommandId> = arguments.option("command")?;
+    let events = match &command {
+        Some(command) => trace.command(command),
+        None => trace.events().iter().collect(),
+    };
+    for event in events
+        .into_iter()
+        .filter(|event| peer.map_or(true, |peer| event.peer == peer))
+    {
+        println!("{}", event);
+    }
+    Ok(())
+}
+
+// Virtual time requires the `current_thread` runtime
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut args = env::args().skip(1);
+    let command = args.next();
+    let result = match Arguments::parse(args) {
+        Ok(arguments) => match command.as_deref() {
+            Some("run") => run(arguments).await,
+            Some("sweep") => sweep(arguments).await,
+            Some("trace") => trace(arguments),
+            Some("help") | Some("--help") | Some("-h") => {
+                println!("{}", USAGE);
+                Ok(())
+            }
+            Some(command) => Err(format!("Unknown command {:?}", command)),
+            None => Err(String::from("Missing command")),
+        },
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}\n\n{}", e, USAGE);
+        process::exit(1);
+    }
+}
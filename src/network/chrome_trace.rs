@@ -0,0 +1,417 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    network::trace::{Event, Trace, TracedCommand, Tracer},
+    peer::peer::PeerId,
+    talk::{CommandId, RoundNumber},
+};
+
+/// Event of the Chrome trace-event format, read by chrome://tracing and https://ui.perfetto.dev
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChromeEvent {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub cat: String,
+    /// Phase of the event: `X` for a span, `i` for an instant and `M` for metadata
+    pub ph: String,
+    /// Start of the event, in µs
+    pub ts: f64,
+    /// Duration of a span, in µs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dur: Option<f64>,
+    pub pid: u64,
+    /// Track of the event: the id of the peer
+    pub tid: PeerId,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub args: Value,
+}
+
+/// Timeline of a run in the Chrome trace-event format, with one track per peer:
+/// - the lifetime of the requests of a client, from the instruction to the quorum,
+/// - the `Phase::ACK` and `Phase::CHK` of every round of a replica, with its executions and
+///   rollbacks,
+/// - the consensus windows of the coordinator, from the first proposal to the validation.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChromeTrace {
+    pub trace_events: Vec<ChromeEvent>,
+    pub display_time_unit: String,
+}
+
+/// Round of a replica, starting at `start` and in `Phase::CHK` from `proposal`
+struct OpenRound {
+    round: RoundNumber,
+    start: Duration,
+    proposal: Option<Duration>,
+}
+
+impl ChromeTrace {
+    pub fn new(trace: &Trace) -> Self {
+        let end = trace
+            .events()
+            .last()
+            .map(|event| event.time)
+            .unwrap_or_default();
+        let mut chrome = ChromeTrace {
+            trace_events: Vec::new(),
+            display_time_unit: String::from("ms"),
+        };
+        let mut roles: BTreeMap<PeerId, &str> = BTreeMap::new();
+        let mut requests: HashMap<(PeerId, CommandId), (Duration, TracedCommand)> = HashMap::new();
+        let mut rounds: BTreeMap<PeerId, OpenRound> = BTreeMap::new();
+        let mut windows: BTreeMap<RoundNumber, (Duration, usize)> = BTreeMap::new();
+
+        for event in trace.events() {
+            let (peer, time) = (event.peer, event.time);
+            if let Some(role) = Self::role(&event.event) {
+                roles.insert(peer, role);
+            }
+            if let Some(round) = event.round {
+                rounds.entry(peer).or_insert(OpenRound {
+                    round,
+                    start: time,
+                    proposal: None,
+                });
+            }
+
+            match &event.event {
+                Event::Instruction(command) => {
+                    requests.insert((peer, *command.id()), (time, command.clone()));
+                }
+                Event::Complete {
+                    command,
+                    round,
+                    phase,
+                } => {
+                    if let Some((start, command)) = requests.remove(&(peer, *command)) {
+                        chrome.span(
+                            Tracer::kind(&command),
+                            "request",
+                            peer,
+                            start,
+                            time,
+                            json!({ "command": command.id(), "round": round, "phase": phase }),
+                        );
+                    }
+                }
+                Event::Execute { command, result } => chrome.instant(
+                    &format!("execute {}", Tracer::kind(&command)),
+                    "execution",
+                    peer,
+                    time,
+                    json!({ "command": command.id(), "result": result }),
+                ),
+                Event::Rollback(command) => chrome.instant(
+                    &format!("rollback {}", Tracer::kind(&command)),
+                    "rollback",
+                    peer,
+                    time,
+                    json!({ "command": command.id() }),
+                ),
+                Event::Propose { nc_set, c_set } => {
+                    if let Some(open) = rounds.get_mut(&peer) {
+                        let args = json!({ "nc_set": nc_set.len(), "c_set": c_set.len() });
+                        chrome.span(
+                            &format!("ACK {}", open.round),
+                            "ACK",
+                            peer,
+                            open.start,
+                            time,
+                            args,
+                        );
+                        open.proposal = Some(time);
+                    }
+                }
+                Event::NewRound(round) => {
+                    if let Some(open) = rounds.insert(
+                        peer,
+                        OpenRound {
+                            round: *round,
+                            start: time,
+                            proposal: None,
+                        },
+                    ) {
+                        chrome.close_round(peer, &open, time);
+                    }
+                }
+                Event::Lost { to, .. } => {
+                    chrome.instant("lost", "network", peer, time, json!({ "to": to }))
+                }
+                Event::Gather(round) => windows.entry(*round).or_insert((time, 0)).1 += 1,
+                Event::Validate(round) => {
+                    if let Some((start, proposals)) = windows.remove(round) {
+                        chrome.span(
+                            &format!("round {}", round),
+                            "consensus",
+                            peer,
+                            start,
+                            time,
+                            json!({ "proposals": proposals }),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // The activity that did not finish lasts until the end of the trace
+        for ((client, _), (start, command)) in requests {
+            chrome.span(
+                Tracer::kind(&command),
+                "request",
+                client,
+                start,
+                end,
+                json!({ "command": command.id(), "completed": false }),
+            );
+        }
+        for (replica, open) in rounds.iter() {
+            chrome.close_round(*replica, open, end);
+        }
+
+        for (peer, role) in roles {
+            chrome.metadata(
+                "thread_name",
+                peer,
+                json!({ "name": format!("{} #{}", role, peer) }),
+            );
+            chrome.metadata("thread_sort_index", peer, json!({ "sort_index": peer }));
+        }
+        chrome
+    }
+
+    /// Role of the peer that records the event, if the event tells it
+    fn role(event: &Event) -> Option<&'static str> {
+        match event {
+            Event::Instruction(_) | Event::Complete { .. } => Some("client"),
+            Event::Execute { .. }
+            | Event::Rollback(_)
+            | Event::Propose { .. }
+            | Event::Decide { .. }
+            | Event::NewRound(_) => Some("replica"),
+            Event::Gather(_) | Event::Validate(_) => Some("coordinator"),
+            _ => None,
+        }
+    }
+
+    /// Ends the round of the replica, in `Phase::CHK` if it proposed and in `Phase::ACK` otherwise
+    fn close_round(&mut self, replica: PeerId, open: &OpenRound, end: Duration) {
+        match open.proposal {
+            Some(proposal) => self.span(
+                &format!("CHK {}", open.round),
+                "CHK",
+                replica,
+                proposal,
+                end,
+                Value::Null,
+            ),
+            None => self.span(
+                &format!("ACK {}", open.round),
+                "ACK",
+                replica,
+                open.start,
+                end,
+                Value::Null,
+            ),
+        }
+    }
+
+    fn span(
+        &mut self,
+        name: &str,
+        category: &str,
+        peer: PeerId,
+        start: Duration,
+        end: Duration,
+        args: Value,
+    ) {
+        self.trace_events.push(ChromeEvent {
+            name: String::from(name),
+            cat: String::from(category),
+            ph: String::from("X"),
+            ts: Self::micros(start),
+            dur: Some(Self::micros(end.saturating_sub(start))),
+            pid: 0,
+            tid: peer,
+            args,
+        });
+    }
+
+    fn instant(&mut self, name: &str, category: &str, peer: PeerId, time: Duration, args: Value) {
+        self.trace_events.push(ChromeEvent {
+            name: String::from(name),
+            cat: String::from(category),
+            ph: String::from("i"),
+            ts: Self::micros(time),
+            dur: None,
+            pid: 0,
+            tid: peer,
+            args,
+        });
+    }
+
+    fn metadata(&mut self, name: &str, peer: PeerId, args: Value) {
+        self.trace_events.push(ChromeEvent {
+            name: String::from(name),
+            cat: String::new(),
+            ph: String::from("M"),
+            ts: 0.0,
+            dur: None,
+            pid: 0,
+            tid: peer,
+            args,
+        });
+    }
+
+    fn micros(time: Duration) -> f64 {
+        time.as_secs_f64() * 1_000_000.0
+    }
+
+    /// Spans of the given category
+    pub fn spans<'a>(&'a self, category: &'a str) -> impl Iterator<Item = &'a ChromeEvent> {
+        self.trace_events
+            .iter()
+            .filter(move |event| event.ph == "X" && event.cat == category)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut file, self)?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        banking::action::Action,
+        network::trace::TraceEvent,
+        talk::{Command, CommandResult, Phase},
+    };
+
+    use super::*;
+
+    fn at(seq: u64, ms: u64, peer: PeerId, round: Option<RoundNumber>, event: Event) -> TraceEvent {
+        TraceEvent {
+            seq,
+            time: Duration::from_millis(ms),
+            peer,
+            round,
+            event,
+        }
+    }
+
+    #[test]
+    fn exports_requests_phases_and_consensus_windows() {
+        let command = Tracer::command(&Command::new(0, Action::Deposit(10)));
+        let pending = Tracer::command(&Command::new(1, Action::Get));
+        let set = vec![command.clone()];
+        let trace = Trace::new(vec![
+            at(0, 0, 0, None, Event::Instruction(command.clone())),
+            at(1, 2, 1, None, Event::Instruction(pending.clone())),
+            at(
+                2,
+                5,
+                2,
+                Some(1),
+                Event::Execute {
+                    command: command.clone(),
+                    result: Tracer::value(&CommandResult::<Action>::Success(None)),
+                },
+            ),
+            at(
+                3,
+                10,
+                2,
+                Some(1),
+                Event::Propose {
+                    nc_set: set.clone(),
+                    c_set: Vec::new(),
+                },
+            ),
+            at(4, 11, 9, None, Event::Gather(1)),
+            at(5, 12, 9, None, Event::Validate(1)),
+            at(
+                6,
+                15,
+                2,
+                Some(1),
+                Event::Decide {
+                    nc_set: set,
+                    c_set: Vec::new(),
+                },
+            ),
+            at(7, 15, 2, Some(2), Event::NewRound(2)),
+            at(
+                8,
+                20,
+                0,
+                None,
+                Event::Complete {
+                    command: *command.id(),
+                    round: 1,
+                    phase: Phase::CHK,
+                },
+            ),
+            at(9, 30, 2, Some(2), Event::Rollback(command.clone())),
+        ]);
+        let chrome = ChromeTrace::new(&trace);
+
+        let requests: Vec<&ChromeEvent> = chrome.spans("request").collect();
+        assert_eq!(requests.len(), 2);
+        assert_eq!((requests[0].tid, requests[0].ts), (0, 0.0));
+        assert_eq!(requests[0].dur, Some(20_000.0));
+        assert_eq!(requests[0].name, "Deposit");
+        // The request of client #1 never completes
+        assert_eq!((requests[1].tid, requests[1].dur), (1, Some(28_000.0)));
+
+        let ack: Vec<(&str, f64, Option<f64>)> = chrome
+            .spans("ACK")
+            .map(|span| (span.name.as_str(), span.ts, span.dur))
+            .collect();
+        assert_eq!(
+            ack,
+            vec![
+                ("ACK 1", 5_000.0, Some(5_000.0)),
+                ("ACK 2", 15_000.0, Some(15_000.0))
+            ]
+        );
+        let chk: Vec<&ChromeEvent> = chrome.spans("CHK").collect();
+        assert_eq!((chk[0].ts, chk[0].dur), (10_000.0, Some(5_000.0)));
+
+        let consensus: Vec<&ChromeEvent> = chrome.spans("consensus").collect();
+        assert_eq!(consensus.len(), 1);
+        assert_eq!((consensus[0].tid, consensus[0].dur), (9, Some(1_000.0)));
+
+        let instants = chrome.trace_events.iter().filter(|e| e.ph == "i").count();
+        assert_eq!(instants, 2);
+        let names: Vec<&Value> = chrome
+            .trace_events
+            .iter()
+            .filter(|e| e.name == "thread_name")
+            .map(|e| &e.args["name"])
+            .collect();
+        assert_eq!(
+            names,
+            vec!["client #0", "client #1", "replica #2", "coordinator #9"]
+        );
+
+        let json = serde_json::to_value(&chrome).unwrap();
+        assert!(json["traceEvents"].is_array());
+        assert_eq!(json["displayTimeUnit"], "ms");
+        assert!(json["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|event| (event["ph"] == "X") == event["dur"].is_number()));
+    }
+}
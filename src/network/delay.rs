@@ -0,0 +1,209 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+use rand::{seq::SliceRandom, Rng};
+use rand_distr::{Distribution, Exp, LogNormal, Poisson, Uniform};
+use serde::{Deserialize, Serialize};
+
+/// Upper bound of any sampled delay, in ms
+pub const MAX_TRANSMISSION_DELAY: u64 = 1000;
+
+/// 99th percentile of the standard normal distribution
+const NORMAL_P99: f64 = 2.326;
+
+/// Distribution of the delay of a single message, in milliseconds
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DelayDistribution {
+    Constant(u64),
+    /// Poisson distribution of the given mean
+    Poisson(f64),
+    /// Uniform distribution on `[low, high]`
+    Uniform(u64, u64),
+    /// Exponential distribution of the given mean
+    Exponential(f64),
+    /// Log-normal distribution, where `mu` and `sigma` parametrize the underlying normal distribution
+    LogNormal {
+        mu: f64,
+        sigma: f64,
+    },
+    /// Delays drawn uniformly from a recorded trace
+    Empirical(Vec<u64>),
+}
+
+impl DelayDistribution {
+    /// Reads an empirical distribution from a trace file containing one delay (in ms) per line.
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn from_trace<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut delays = Vec::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let delay = line.parse::<u64>().map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid delay {:?}: {}", line, e),
+                )
+            })?;
+            delays.push(delay);
+        }
+        if delays.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "Empty delay trace"));
+        }
+        Ok(DelayDistribution::Empirical(delays))
+    }
+
+    /// Mean of the distribution, in ms
+    pub fn mean(&self) -> f64 {
+        match self {
+            DelayDistribution::Constant(delay) => *delay as f64,
+            DelayDistribution::Poisson(mean) | DelayDistribution::Exponential(mean) => *mean,
+            DelayDistribution::Uniform(low, high) => (*low + *high) as f64 / 2.0,
+            DelayDistribution::LogNormal { mu, sigma } => (mu + sigma * sigma / 2.0).exp(),
+            DelayDistribution::Empirical(delays) => {
+                delays.iter().sum::<u64>() as f64 / delays.len().max(1) as f64
+            }
+        }
+    }
+
+    /// Delay that 99% of the samples do not exceed, in ms, bounded by `MAX_TRANSMISSION_DELAY`.
+    /// The percentile of the Poisson distribution follows its normal approximation.
+    pub fn p99(&self) -> u64 {
+        let p99 = match self {
+            DelayDistribution::Constant(delay) => *delay as f64,
+            DelayDistribution::Poisson(mean) if *mean > 0.0 => mean + NORMAL_P99 * mean.sqrt(),
+            DelayDistribution::Uniform(low, high) => {
+                let (low, high) = (*low.min(high) as f64, *high.max(low) as f64);
+                low + 0.99 * (high - low)
+            }
+            DelayDistribution::Exponential(mean) if *mean > 0.0 => -mean * 0.01f64.ln(),
+            DelayDistribution::LogNormal { mu, sigma } if *sigma >= 0.0 => {
+                (mu + NORMAL_P99 * sigma).exp()
+            }
+            DelayDistribution::Empirical(delays) if !delays.is_empty() => {
+                let mut delays = delays.clone();
+                delays.sort_unstable();
+                let index = (delays.len() as f64 * 0.99).ceil() as usize - 1;
+                delays[index] as f64
+            }
+            // Invalid parameters always sample a zero delay
+            _ => 0.0,
+        };
+        (p99.ceil() as u64).min(MAX_TRANSMISSION_DELAY)
+    }
+
+    /// Samples a delay, bounded by `MAX_TRANSMISSION_DELAY`.
+    /// Invalid parameters (e.g. a negative mean) fall back to a zero delay.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        let delay = match self {
+            DelayDistribution::Constant(delay) => *delay,
+            DelayDistribution::Poisson(mean) => Poisson::new(*mean)
+                .map(|distribution| distribution.sample(rng) as u64)
+                .unwrap_or(0),
+            DelayDistribution::Uniform(low, high) => {
+                Uniform::new_inclusive(*low.min(high), *high.max(low)).sample(rng)
+            }
+            DelayDistribution::Exponential(mean) => Exp::new(1.0 / mean)
+                .map(|distribution| distribution.sample(rng) as u64)
+                .unwrap_or(0),
+            DelayDistribution::LogNormal { mu, sigma } => LogNormal::new(*mu, *sigma)
+                .map(|distribution| distribution.sample(rng) as u64)
+                .unwrap_or(0),
+            DelayDistribution::Empirical(delays) => delays.choose(rng).cloned().unwrap_or(0),
+        };
+        delay.min(MAX_TRANSMISSION_DELAY)
+    }
+}
+
+impl Default for DelayDistribution {
+    fn default() -> Self {
+        DelayDistribution::Constant(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn samples_are_bounded() {
+        let rng = &mut StdRng::seed_from_u64(0);
+        let distributions = [
+            DelayDistribution::Constant(5000),
+            DelayDistribution::Poisson(900.0),
+            DelayDistribution::Uniform(10, 20),
+            DelayDistribution::Exponential(500.0),
+            DelayDistribution::LogNormal {
+                mu: 6.0,
+                sigma: 1.0,
+            },
+            DelayDistribution::Empirical(vec![3, 4, 2000]),
+        ];
+        for distribution in distributions.iter() {
+            for _ in 0..1000 {
+                assert!(distribution.sample(rng) <= MAX_TRANSMISSION_DELAY);
+            }
+        }
+        for _ in 0..1000 {
+            let delay = DelayDistribution::Uniform(10, 20).sample(rng);
+            assert!((10..=20).contains(&delay));
+        }
+    }
+
+    #[test]
+    fn p99_bounds_the_samples() {
+        let rng = &mut StdRng::seed_from_u64(0);
+        let distributions = [
+            DelayDistribution::Poisson(10.0),
+            DelayDistribution::Uniform(10, 20),
+            DelayDistribution::Exponential(10.0),
+            DelayDistribution::LogNormal {
+                mu: 2.0,
+                sigma: 0.5,
+            },
+            DelayDistribution::Empirical((1..=100).collect()),
+        ];
+        for distribution in distributions.iter() {
+            let p99 = distribution.p99();
+            let below = (0..10_000)
+                .filter(|_| distribution.sample(rng) <= p99)
+                .count();
+            assert!(below >= 9_800, "{:?}: {} samples below {}", distribution, below, p99);
+        }
+        assert_eq!(DelayDistribution::Constant(10).p99(), 10);
+        assert_eq!(DelayDistribution::Empirical((1..=100).collect()).p99(), 99);
+        // The percentile grows with the mean, up to the largest delay
+        assert!(DelayDistribution::Poisson(10.0).p99() < DelayDistribution::Poisson(100.0).p99());
+        assert_eq!(DelayDistribution::Poisson(5000.0).p99(), MAX_TRANSMISSION_DELAY);
+    }
+
+    #[test]
+    fn invalid_parameters_fall_back_to_zero() {
+        let rng = &mut StdRng::seed_from_u64(0);
+        assert_eq!(DelayDistribution::Poisson(-1.0).sample(rng), 0);
+        assert_eq!(DelayDistribution::Exponential(-1.0).sample(rng), 0);
+        assert_eq!(DelayDistribution::Empirical(Vec::new()).sample(rng), 0);
+        assert_eq!(DelayDistribution::Poisson(-1.0).p99(), 0);
+        assert_eq!(DelayDistribution::Empirical(Vec::new()).p99(), 0);
+    }
+
+    #[test]
+    fn reads_empirical_trace() {
+        let path = std::env::temp_dir().join("genericbft_delay_trace.txt");
+        fs::write(&path, "# ping 10.0.0.2\n12\n\n15\n 40 \n").unwrap();
+        assert_eq!(
+            DelayDistribution::from_trace(&path).unwrap(),
+            DelayDistribution::Empirical(vec![12, 15, 40])
+        );
+
+        fs::write(&path, "12\nlate\n").unwrap();
+        assert!(DelayDistribution::from_trace(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rand::Rng;
+use tokio::{
+    sync::Notify,
+    time::{sleep_until, Instant},
+};
+
+use crate::peer::peer::PeerId;
+
+/// What happens to a message put on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fate {
+    Lost,
+    /// The message is delivered the given number of times
+    Delivered(usize),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Faults {
+    drop_probability: f64,
+    duplicate_probability: f64,
+    /// Group of every partitioned peer. Peers outside of any group are not affected.
+    partition: HashMap<PeerId, usize>,
+    /// End of the partition, if it is not healed manually
+    partition_end: Option<Instant>,
+    crashed: HashSet<PeerId>,
+    /// Additional latency of the slowed down links, in ms
+    slow_links: HashMap<(PeerId, PeerId), u64>,
+}
+
+impl Faults {
+    fn is_partitioned(&mut self, from: PeerId, to: PeerId) -> bool {
+        if let Some(end) = self.partition_end {
+            if Instant::now() >= end {
+                self.partition.clear();
+                self.partition_end = None;
+            }
+        }
+        match (self.partition.get(&from), self.partition.get(&to)) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
+        }
+    }
+}
+
+/// Fault-injection layer of the network, shared by the `Communicator` of every peer.
+/// Messages between two sides of a partition are held until the partition heals,
+/// as a reliable transport would retransmit them. Lost messages are never delivered.
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjector {
+    faults: Arc<Mutex<Faults>>,
+    healed: Arc<Notify>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a fault-injection layer with the same faults as this one, independent from it
+    pub fn fork(&self) -> Self {
+        FaultInjector {
+            faults: Arc::new(Mutex::new(self.faults.lock().unwrap().clone())),
+            healed: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Each message is lost with the given probability
+    pub fn set_drop_probability(&self, probability: f64) {
+        assert!((0.0..=1.0).contains(&probability));
+        self.faults.lock().unwrap().drop_probability = probability;
+    }
+
+    /// Each delivered message is delivered twice with the given probability
+    pub fn set_duplicate_probability(&self, probability: f64) {
+        assert!((0.0..=1.0).contains(&probability));
+        self.faults.lock().unwrap().duplicate_probability = probability;
+    }
+
+    /// Partitions the given groups of peers from each other, for `window` if given, or until
+    /// `heal` is called. Replaces the current partition.
+    pub fn partition(&self, groups: Vec<Vec<PeerId>>, window: Option<Duration>) {
+        let mut faults = self.faults.lock().unwrap();
+        faults.partition = groups
+            .into_iter()
+            .enumerate()
+            .flat_map(|(group, peers)| peers.into_iter().map(move |peer| (peer, group)))
+            .collect();
+        faults.partition_end = window.map(|window| Instant::now() + window);
+        drop(faults);
+        // Held messages wait for the new end of the partition
+        self.healed.notify_waiters();
+    }
+
+    /// Heals the partition, releasing the held messages
+    pub fn heal(&self) {
+        let mut faults = self.faults.lock().unwrap();
+        faults.partition.clear();
+        faults.partition_end = None;
+        drop(faults);
+        self.healed.notify_waiters();
+    }
+
+    pub fn is_partitioned(&self, from: PeerId, to: PeerId) -> bool {
+        self.faults.lock().unwrap().is_partitioned(from, to)
+    }
+
+    /// Crashes the peer: every message it sends or receives is lost until it restarts.
+    /// The peer keeps its state, as if it recovered it from stable storage when restarting.
+    pub fn crash(&self, peer: PeerId) {
+        self.faults.lock().unwrap().crashed.insert(peer);
+    }
+
+    pub fn restart(&self, peer: PeerId) {
+        self.faults.lock().unwrap().crashed.remove(&peer);
+    }
+
+    pub fn is_crashed(&self, peer: PeerId) -> bool {
+        self.faults.lock().unwrap().crashed.contains(&peer)
+    }
+
+    /// Adds `delay` ms to every message between `a` and `b`, in both directions
+    pub fn slow_link(&self, a: PeerId, b: PeerId, delay: u64) {
+        let mut faults = self.faults.lock().unwrap();
+        faults.slow_links.insert((a, b), delay);
+        faults.slow_links.insert((b, a), delay);
+    }
+
+    pub fn restore_link(&self, a: PeerId, b: PeerId) {
+        let mut faults = self.faults.lock().unwrap();
+        faults.slow_links.remove(&(a, b));
+        faults.slow_links.remove(&(b, a));
+    }
+
+    /// Additional latency of the link from `from` to `to`, in ms
+    pub fn extra_delay(&self, from: PeerId, to: PeerId) -> u64 {
+        let faults = self.faults.lock().unwrap();
+        faults.slow_links.get(&(from, to)).cloned().unwrap_or(0)
+    }
+
+    /// Draws the fate of a message
+    pub fn fate<R: Rng + ?Sized>(&self, rng: &mut R) -> Fate {
+        let faults = self.faults.lock().unwrap();
+        if faults.drop_probability > 0.0 && rng.gen_bool(faults.drop_probability) {
+            Fate::Lost
+        } else if faults.duplicate_probability > 0.0 && rng.gen_bool(faults.duplicate_probability) {
+            Fate::Delivered(2)
+        } else {
+            Fate::Delivered(1)
+        }
+    }
+
+    /// Waits until `from` and `to` are on the same side of the partition
+    pub async fn wait_until_connected(&self, from: PeerId, to: PeerId) {
+        loop {
+            // Created before checking the partition, so that a concurrent heal is not missed
+            let healed = self.healed.notified();
+            let end = {
+                let mut faults = self.faults.lock().unwrap();
+                if !faults.is_partitioned(from, to) {
+                    return;
+                }
+                faults.partition_end
+            };
+            match end {
+                Some(end) => {
+                    tokio::select! {
+                        _ = healed => {}
+                        _ = sleep_until(end) => {}
+                    }
+                }
+                None => healed.await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+    use tokio::time::timeout;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn partitions_hold_messages_until_healed() {
+        tokio::time::pause();
+        let faults = FaultInjector::new();
+        faults.partition(vec![vec![0, 1], vec![2]], None);
+        assert!(faults.is_partitioned(0, 2));
+        assert!(faults.is_partitioned(2, 1));
+        assert!(!faults.is_partitioned(0, 1));
+        assert!(!faults.is_partitioned(0, 3));
+
+        let mut held = tokio::spawn({
+            let faults = faults.clone();
+            async move { faults.wait_until_connected(0, 2).await }
+        });
+        assert!(timeout(Duration::from_secs(100), &mut held).await.is_err());
+
+        faults.heal();
+        held.await.unwrap();
+        assert!(!faults.is_partitioned(0, 2));
+    }
+
+    #[tokio::test]
+    async fn partitions_end_after_their_window() {
+        tokio::time::pause();
+        let faults = FaultInjector::new();
+        faults.partition(vec![vec![0], vec![1]], Some(Duration::from_secs(10)));
+        let start = Instant::now();
+        faults.wait_until_connected(1, 0).await;
+        assert!(start.elapsed() >= Duration::from_secs(10));
+        assert!(!faults.is_partitioned(0, 1));
+    }
+
+    #[test]
+    fn crashes_and_slow_links() {
+        let faults = FaultInjector::new();
+        faults.crash(3);
+        assert!(faults.is_crashed(3));
+        faults.restart(3);
+        assert!(!faults.is_crashed(3));
+
+        faults.slow_link(0, 1, 200);
+        assert_eq!(faults.extra_delay(1, 0), 200);
+        assert_eq!(faults.extra_delay(0, 2), 0);
+        faults.restore_link(1, 0);
+        assert_eq!(faults.extra_delay(0, 1), 0);
+    }
+
+    #[test]
+    fn fate_follows_probabilities() {
+        let faults = FaultInjector::new();
+        let rng = &mut StdRng::seed_from_u64(0);
+        assert_eq!(faults.fate(rng), Fate::Delivered(1));
+
+        faults.set_drop_probability(1.0);
+        assert_eq!(faults.fate(rng), Fate::Lost);
+
+        faults.set_drop_probability(0.0);
+        faults.set_duplicate_probability(1.0);
+        assert_eq!(faults.fate(rng), Fate::Delivered(2));
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    ops::Range,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::{
+    application,
+    banking::{action::Action, banking::Money},
+    peer::peer::PeerId,
+    talk::{Command, CommandId, CommandResult},
+};
+
+/// Command of a client, from its invocation to its result
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Operation<O: application::Operation = Action> {
+    pub command: Command<O>,
+    /// Time the network instructed the client to issue the command
+    pub invoke: Duration,
+    /// Time and value of the result, `None` while the command is pending
+    pub response: Option<(Duration, CommandResult<O>)>,
+}
+
+/// Invocations and results of the commands of the clients, in the order of invocation
+#[derive(Clone, Debug)]
+pub struct History<O: application::Operation = Action> {
+    start: Instant,
+    operations: Vec<Operation<O>>,
+    index: HashMap<CommandId, usize>,
+}
+
+impl<O: application::Operation> History<O> {
+    pub fn new() -> Self {
+        History {
+            start: Instant::now(),
+            operations: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn invoke(&mut self, command: &Command<O>) {
+        self.index.insert(*command.id(), self.operations.len());
+        self.operations.push(Operation {
+            command: command.clone(),
+            invoke: self.start.elapsed(),
+            response: None,
+        });
+    }
+
+    /// Records the result of the command. Results of unknown or completed commands are ignored.
+    pub fn complete(&mut self, id: &CommandId, result: CommandResult<O>) {
+        let time = self.start.elapsed();
+        if let Some(index) = self.index.get(id) {
+            self.operations[*index]
+                .response
+                .get_or_insert((time, result));
+        }
+    }
+
+    pub fn operations(&self) -> &Vec<Operation<O>> {
+        &self.operations
+    }
+}
+
+impl History {
+    /// Checks that the history of every given client is linearizable with respect to
+    /// `Account`. Each command only affects the account of its issuer, so the histories of the
+    /// clients are checked independently.
+    /// Returns the counterexample of the first client whose history is not linearizable.
+    pub fn check(&self, clients: Range<PeerId>) -> Result<(), Counterexample> {
+        for client in clients {
+            let operations: Vec<Operation> = self
+                .operations
+                .iter()
+                .filter(|operation| *operation.command.issuer() == client)
+                .cloned()
+                .collect();
+            Linearizability::check(client, operations)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sequential specification of the account of a client in `Banking`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Account {
+    /// `None` until the client registers
+    pub balance: Option<Money>,
+}
+
+impl Account {
+    /// Applies the action and returns its expected result
+    pub fn apply(&mut self, action: &Action) -> CommandResult {
+        let failure = |reason: &str| CommandResult::Failure(String::from(reason));
+        match (action, self.balance) {
+            (Action::Register, None) => {
+                self.balance = Some(0);
+                CommandResult::Success(None)
+            }
+            (Action::Register, Some(_)) => failure("already registered"),
+            (_, None) => failure("not registered"),
+            (Action::Get, Some(balance)) => CommandResult::Success(Some(balance)),
+            (Action::Deposit(amount), Some(balance)) => {
+                self.balance = Some(balance + amount);
+                CommandResult::Success(None)
+            }
+            (Action::Withdraw(amount), Some(balance)) if balance >= *amount => {
+                self.balance = Some(balance - amount);
+                CommandResult::Success(None)
+            }
+            (Action::Withdraw(_), Some(_)) => failure("unsufficient balance"),
+        }
+    }
+}
+
+/// Shortest prefix of the history of a client that is not linearizable
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Counterexample {
+    pub client: PeerId,
+    /// Operations invoked in the prefix. Those that complete after the prefix are pending.
+    pub operations: Vec<Operation>,
+    /// Operation whose result ends the prefix, and that no linearization explains
+    pub culprit: CommandId,
+}
+
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the history of client #{} is not linearizable, because of {}:",
+            self.client, self.culprit
+        )?;
+        for operation in self.operations.iter() {
+            write!(f, "\n\t[{:?}, ", operation.invoke)?;
+            match &operation.response {
+                Some((time, result)) => write!(
+                    f,
+                    "{:?}] {} -> {}",
+                    time,
+                    operation.command.action(),
+                    result.to_string().trim_end()
+                )?,
+                None => write!(f, "pending] {}", operation.command.action())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Wing and Gong's search for a linearization of a history, memoizing the explored
+/// (linearized operations, account) configurations
+struct Linearizability<'a> {
+    operations: &'a [Operation],
+    linearized: Vec<bool>,
+    explored: HashSet<(Vec<bool>, Account)>,
+}
+
+impl<'a> Linearizability<'a> {
+    /// Checks the history of the client, and returns its shortest prefix that is not linearizable
+    fn check(client: PeerId, operations: Vec<Operation>) -> Result<(), Counterexample> {
+        if Self::linearizable(&operations) {
+            return Ok(());
+        }
+
+        // Linearizability is prefix-closed: searches the shortest prefix, cut after a response
+        let mut responses: Vec<Duration> = operations
+            .iter()
+            .filter_map(|operation| operation.response.as_ref().map(|(time, _)| *time))
+            .collect();
+        responses.sort();
+        let (mut low, mut high) = (0, responses.len() - 1);
+        while low < high {
+            let middle = (low + high) / 2;
+            if Self::linearizable(&Self::prefix(&operations, responses[middle])) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        let cut = responses[low];
+        let prefix = Self::prefix(&operations, cut);
+        let culprit = prefix
+            .iter()
+            .find(|operation| matches!(&operation.response, Some((time, _)) if *time == cut))
+            .map(|operation| *operation.command.id())
+            .unwrap();
+        Err(Counterexample {
+            client,
+            operations: prefix,
+            culprit,
+        })
+    }
+
+    /// Operations invoked until `cut`, where the results after `cut` are not known yet
+    fn prefix(operations: &[Operation], cut: Duration) -> Vec<Operation> {
+        operations
+            .iter()
+            .filter(|operation| operation.invoke <= cut)
+            .map(|operation| Operation {
+                response: operation.response.clone().filter(|(time, _)| *time <= cut),
+                ..operation.clone()
+            })
+            .collect()
+    }
+
+    fn linearizable(operations: &[Operation]) -> bool {
+        let mut search = Linearizability {
+            operations,
+            linearized: vec![false; operations.len()],
+            explored: HashSet::new(),
+        };
+        search.search(Account::default())
+    }
+
+    fn search(&mut self, account: Account) -> bool {
+        let remaining: Vec<usize> = (0..self.operations.len())
+            .filter(|i| !self.linearized[*i])
+            .collect();
+        // Pending operations may not have taken effect
+        if remaining
+            .iter()
+            .all(|i| self.operations[*i].response.is_none())
+        {
+            return true;
+        }
+        if !self.explored.insert((self.linearized.clone(), account)) {
+            return false;
+        }
+
+        // The next operation cannot follow an operation that is not linearized yet
+        let first_response = remaining
+            .iter()
+            .filter_map(|i| self.operations[*i].response.as_ref().map(|(time, _)| *time))
+            .min();
+        for candidate in remaining.iter() {
+            let operation = &self.operations[*candidate];
+            if matches!(first_response, Some(response) if response < operation.invoke) {
+                continue;
+            }
+            let mut next = account;
+            let result = next.apply(operation.command.action());
+            let consistent = match &operation.response {
+                Some((_, expected)) => *expected == result,
+                None => true,
+            };
+            if consistent {
+                self.linearized[*candidate] = true;
+                if self.search(next) {
+                    return true;
+                }
+                self.linearized[*candidate] = false;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operation(action: Action, invoke: u64, response: Option<(u64, CommandResult)>) -> Operation {
+        Operation {
+            command: Command::new(0, action),
+            invoke: Duration::from_millis(invoke),
+            response: response.map(|(time, result)| (Duration::from_millis(time), result)),
+        }
+    }
+
+    fn success(data: Option<Money>) -> CommandResult {
+        CommandResult::Success(data)
+    }
+
+    fn failure() -> CommandResult {
+        CommandResult::Failure(String::from("failure"))
+    }
+
+    #[test]
+    fn concurrent_operations_can_be_reordered() {
+        let operations = vec![
+            operation(Action::Register, 0, Some((10, success(None)))),
+            // The get overlaps the deposits, and sees one of them
+            operation(Action::Deposit(5), 20, Some((40, success(None)))),
+            operation(Action::Deposit(7), 20, Some((40, success(None)))),
+            operation(Action::Get, 25, Some((30, success(Some(7))))),
+            operation(Action::Withdraw(20), 50, Some((60, failure()))),
+            operation(Action::Withdraw(12), 70, None),
+        ];
+        assert_eq!(Linearizability::check(0, operations), Ok(()));
+
+        // A get racing with the registration may fail
+        let operations = vec![
+            operation(Action::Register, 0, Some((10, success(None)))),
+            operation(Action::Get, 0, Some((5, failure()))),
+        ];
+        assert_eq!(Linearizability::check(0, operations), Ok(()));
+    }
+
+    #[test]
+    fn reports_the_shortest_prefix_that_is_not_linearizable() {
+        let operations = vec![
+            operation(Action::Register, 0, Some((10, success(None)))),
+            operation(Action::Deposit(5), 20, Some((30, success(None)))),
+            operation(Action::Get, 25, Some((28, success(Some(5))))),
+            // The deposit completed before: the balance cannot be 0 anymore
+            operation(Action::Get, 40, Some((50, success(Some(0))))),
+            operation(Action::Withdraw(1), 60, Some((70, success(None)))),
+            operation(Action::Get, 80, Some((90, success(Some(0))))),
+        ];
+        let culprit = *operations[3].command.id();
+        let counterexample = Linearizability::check(0, operations).unwrap_err();
+        assert_eq!(counterexample.culprit, culprit);
+        assert_eq!(counterexample.operations.len(), 4);
+        assert!(counterexample.to_string().contains("Get balance"));
+    }
+
+    #[tokio::test]
+    async fn histories_are_checked_per_client() {
+        let mut history = History::new();
+        let register = Command::new(1, Action::Register);
+        let get = Command::new(1, Action::Get);
+        let other = Command::new(2, Action::Get);
+        history.invoke(&register);
+        history.invoke(&other);
+        history.complete(register.id(), success(None));
+        history.complete(register.id(), failure());
+        history.invoke(&get);
+        history.complete(get.id(), success(Some(3)));
+
+        assert_eq!(
+            history.operations()[0].response.as_ref().unwrap().1,
+            success(None)
+        );
+        assert_eq!(history.check(0..1), Ok(()));
+        let counterexample = history.check(0..3).unwrap_err();
+        assert_eq!(counterexample.client, 1);
+        assert_eq!(counterexample.culprit, *get.id());
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    peer::peer::PeerId,
+    talk::{Phase, RoundNumber},
+};
+
+/// Command completed during an execution of the network
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Completion<O = Action> {
+    pub client: PeerId,
+    pub action: O,
+    /// Round of the receipt of the command
+    pub round: RoundNumber,
+    /// `Phase::ACK` if the command completed on the fast path, `Phase::CHK` otherwise
+    pub phase: Phase,
+    /// Time between the execution instruction and the result of the command
+    pub latency: Duration,
+}
+
+/// Percentiles of a set of latencies, computed with the nearest-rank method
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub count: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    pub fn new(latencies: impl IntoIterator<Item = Duration>) -> Self {
+        let mut latencies: Vec<Duration> = latencies.into_iter().collect();
+        latencies.sort();
+        LatencySummary {
+            count: latencies.len(),
+            p50: Self::percentile(&latencies, 50),
+            p90: Self::percentile(&latencies, 90),
+            p99: Self::percentile(&latencies, 99),
+            max: latencies.last().cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Smallest latency greater or equal to `percentile`% of the sorted latencies
+    fn percentile(sorted: &[Duration], percentile: usize) -> Duration {
+        if sorted.is_empty() {
+            return Duration::default();
+        }
+        let rank = (percentile * sorted.len() + 99) / 100;
+        sorted[rank.max(1) - 1]
+    }
+}
+
+impl Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} commands, p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+            self.count, self.p50, self.p90, self.p99, self.max
+        )
+    }
+}
+
+/// Number of latencies per bucket, where bucket `i` counts the latencies
+/// under `2^i` ms that are not counted by the previous buckets
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    buckets: Vec<usize>,
+}
+
+impl LatencyHistogram {
+    pub fn new(latencies: impl IntoIterator<Item = Duration>) -> Self {
+        let mut buckets = Vec::new();
+        for latency in latencies {
+            let bucket = (0..)
+                .find(|bucket| latency < Self::upper_bound(*bucket))
+                .unwrap();
+            if buckets.len() <= bucket {
+                buckets.resize(bucket + 1, 0);
+            }
+            buckets[bucket] += 1;
+        }
+        LatencyHistogram { buckets }
+    }
+
+    pub fn buckets(&self) -> &Vec<usize> {
+        &self.buckets
+    }
+
+    /// Exclusive upper bound of the latencies counted by `bucket`
+    pub fn upper_bound(bucket: usize) -> Duration {
+        Duration::from_millis(1 << bucket)
+    }
+}
+
+impl Display for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.buckets.iter().sum::<usize>().max(1);
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            let bar = "#".repeat((count * 50 + total - 1) / total);
+            writeln!(
+                f,
+                "< {:>7}ms: {:>5} {}",
+                Self::upper_bound(bucket).as_millis(),
+                count,
+                bar
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Latencies of the completed commands, split by action and by phase
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencyReport {
+    pub overall: LatencySummary,
+    pub histogram: LatencyHistogram,
+    /// Indexed by `Operation::kind`
+    pub by_action: BTreeMap<String, LatencySummary>,
+    pub by_phase: BTreeMap<Phase, LatencySummary>,
+}
+
+impl LatencyReport {
+    pub fn new<'a, O: Operation>(completions: impl IntoIterator<Item = &'a Completion<O>>) -> Self {
+        let completions: Vec<&Completion<O>> = completions.into_iter().collect();
+        let latencies = || completions.iter().map(|completion| completion.latency);
+
+        let mut by_action: BTreeMap<String, Vec<Duration>> = BTreeMap::new();
+        let mut by_phase: BTreeMap<Phase, Vec<Duration>> = BTreeMap::new();
+        for completion in completions.iter() {
+            by_action
+                .entry(String::from(completion.action.kind()))
+                .or_default()
+                .push(completion.latency);
+            by_phase
+                .entry(completion.phase.clone())
+                .or_default()
+                .push(completion.latency);
+        }
+
+        LatencyReport {
+            overall: LatencySummary::new(latencies()),
+            histogram: LatencyHistogram::new(latencies()),
+            by_action: by_action
+                .into_iter()
+                .map(|(action, latencies)| (action, LatencySummary::new(latencies)))
+                .collect(),
+            by_phase: by_phase
+                .into_iter()
+                .map(|(phase, latencies)| (phase, LatencySummary::new(latencies)))
+                .collect(),
+        }
+    }
+}
+
+impl Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "all: {}", self.overall)?;
+        for (action, summary) in self.by_action.iter() {
+            writeln!(f, "{}: {}", action, summary)?;
+        }
+        for (phase, summary) in self.by_phase.iter() {
+            writeln!(f, "{:?}: {}", phase, summary)?;
+        }
+        write!(f, "{}", self.histogram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let summary = LatencySummary::new((1..=100).rev().map(Duration::from_millis));
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p90, Duration::from_millis(90));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+        assert_eq!(summary.max, Duration::from_millis(100));
+
+        let single = LatencySummary::new(vec![Duration::from_millis(7)]);
+        assert_eq!(single.p50, Duration::from_millis(7));
+        assert_eq!(single.p99, Duration::from_millis(7));
+
+        assert_eq!(LatencySummary::new(Vec::new()), LatencySummary::default());
+    }
+
+    #[test]
+    fn histogram_buckets_are_powers_of_two() {
+        let histogram = LatencyHistogram::new(
+            [0, 1, 3, 4, 7, 100]
+                .iter()
+                .cloned()
+                .map(Duration::from_millis),
+        );
+        assert_eq!(histogram.buckets(), &vec![1, 1, 1, 2, 0, 0, 0, 1]);
+        assert_eq!(LatencyHistogram::upper_bound(7), Duration::from_millis(128));
+    }
+
+    #[test]
+    fn report_splits_by_action_and_phase() {
+        let completion = |action, phase, latency| Completion {
+            client: 0,
+            action,
+            round: 0,
+            phase,
+            latency: Duration::from_millis(latency),
+        };
+        let completions = vec![
+            completion(Action::Deposit(10), Phase::ACK, 10),
+            completion(Action::Deposit(20), Phase::ACK, 20),
+            completion(Action::Withdraw(5), Phase::CHK, 500),
+        ];
+        let report = LatencyReport::new(completions.iter());
+        assert_eq!(report.overall.count, 3);
+        assert_eq!(report.by_action["Deposit"].max, Duration::from_millis(20));
+        assert_eq!(report.by_action["Withdraw"].count, 1);
+        assert!(!report.by_action.contains_key("Get"));
+        assert_eq!(report.by_phase[&Phase::ACK].count, 2);
+        assert_eq!(report.by_phase[&Phase::CHK].p50, Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::peer::peer::PeerId;
+
+/// Counters of a replica
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplicaMetrics {
+    /// Distinct commands received from the clients or the other replicas
+    pub commands_received: u64,
+    /// Commands dropped because their issuer did not sign them, see `CommandRejection`
+    pub commands_rejected: u64,
+    /// Commands executed and acknowledged in `Phase::ACK`
+    pub fast_path_deliveries: u64,
+    /// Rounds in which the replica proposed to the consensus
+    pub chk_rounds: u64,
+    /// Speculative executions undone after a decision
+    pub rollbacks: u64,
+    /// Time spent waiting for the decisions of the consensus
+    pub consensus_wait: Duration,
+    /// `ReplicaBroadcast` sent to the other replicas
+    pub broadcasts: u64,
+    /// Serialized size of the sent `ReplicaBroadcast`, in bytes
+    pub broadcast_bytes: u64,
+}
+
+/// Counters of the coordinator of the `Oracle` and `Simulated` consensus
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoordinatorMetrics {
+    pub rounds_validated: u64,
+}
+
+/// Metrics of every peer of the network at a given time
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub replicas: BTreeMap<PeerId, ReplicaMetrics>,
+    pub coordinator: CoordinatorMetrics,
+}
+
+/// Registry of the metrics of the network, shared by the replicas and the coordinator
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    snapshot: Arc<Mutex<MetricsSnapshot>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the metrics of the replica `id`
+    pub fn replica<F: FnOnce(&mut ReplicaMetrics)>(&self, id: PeerId, update: F) {
+        update(
+            self.snapshot
+                .lock()
+                .unwrap()
+                .replicas
+                .entry(id)
+                .or_default(),
+        );
+    }
+
+    pub fn coordinator<F: FnOnce(&mut CoordinatorMetrics)>(&self, update: F) {
+        update(&mut self.snapshot.lock().unwrap().coordinator);
+    }
+
+    /// Returns the current value of every metric
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
+}
+
+impl Display for ReplicaMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} commands received, {} rejected, {} fast-path deliveries, {} CHK rounds, \
+             {} rollbacks, {:?} waiting for the consensus, {} broadcasts ({} bytes)",
+            self.commands_received,
+            self.commands_rejected,
+            self.fast_path_deliveries,
+            self.chk_rounds,
+            self.rollbacks,
+            self.consensus_wait,
+            self.broadcasts,
+            self.broadcast_bytes
+        )
+    }
+}
+
+impl Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, metrics) in self.replicas.iter() {
+            writeln!(f, "replica #{}: {}", id, metrics)?;
+        }
+        write!(
+            f,
+            "coordinator: {} rounds validated",
+            self.coordinator.rounds_validated
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_the_registry() {
+        let metrics = Metrics::new();
+        let replica = metrics.clone();
+        replica.replica(3, |metrics| metrics.rollbacks += 2);
+        replica.replica(3, |metrics| {
+            metrics.consensus_wait += Duration::from_millis(5)
+        });
+        metrics.coordinator(|metrics| metrics.rounds_validated += 1);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.replicas[&3].rollbacks, 2);
+        assert_eq!(
+            snapshot.replicas[&3].consensus_wait,
+            Duration::from_millis(5)
+        );
+        assert!(!snapshot.replicas.contains_key(&0));
+        assert_eq!(snapshot.coordinator.rounds_validated, 1);
+    }
+}
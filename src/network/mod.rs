@@ -1,6 +1,28 @@
+pub mod chrome_trace;
+pub mod delay;
+pub mod fault_injector;
+pub mod history;
+pub mod latency;
+pub mod metrics;
+pub mod model_checker;
+pub mod nemesis;
 pub mod network;
 pub mod network_info;
 pub mod network_peer;
+pub mod safety;
+pub mod topology;
+pub mod trace;
 
+pub use chrome_trace::ChromeTrace;
+pub use delay::DelayDistribution;
+pub use fault_injector::FaultInjector;
+pub use history::{Counterexample, History};
+pub use latency::{Completion, LatencyHistogram, LatencyReport, LatencySummary};
+pub use metrics::{Metrics, MetricsSnapshot};
+pub use model_checker::{FailingSchedule, ModelChecker, Schedule};
+pub use nemesis::{Nemesis, NemesisEvent};
 pub use network_info::NetworkInfo;
 pub use network_peer::NetworkPeer;
+pub use safety::{Decisions, Divergence};
+pub use topology::Topology;
+pub use trace::{Event, Trace, TraceEvent, Tracer};
@@ -0,0 +1,732 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashSet},
+    fmt::{self, Display},
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use talk::{
+    crypto::{Identity, KeyChain},
+    unicast::test::UnicastSystem,
+};
+
+use crate::{
+    crypto::{
+        identity_table::{IdentityTable, IdentityTableBuilder},
+        statements::IssuedCommand,
+    },
+    database::replica_database::Set,
+    error::{ConsensusError, ScheduleError},
+    network::{safety::Divergence, trace::Event, NetworkInfo},
+    peer::{
+        consensus::Consensus,
+        coordinator::{Coordinator, ProposalData, ProposalSignedData},
+        handler::{Communicator, FaultyReplicaStrategy, ReplicaHandler},
+        peer::PeerId,
+    },
+    talk::{
+        Command, CommandId, CommandResult, FeedbackChannel, FeedbackReceiver, Message, Phase,
+        RoundNumber,
+    },
+    types::*,
+};
+
+/// Largest number of states explored by default
+const DEFAULT_MAX_STATES: usize = 100_000;
+
+/// Transition of the model: a message reaches a replica, or a replica gets the decision it
+/// waits for. Messages are numbered in the order they are sent.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Step {
+    Deliver {
+        message: usize,
+        from: PeerId,
+        to: PeerId,
+    },
+    Decide {
+        replica: PeerId,
+        round: RoundNumber,
+    },
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Deliver { message, from, to } => {
+                write!(f, "deliver message {} from #{} to #{}", message, from, to)
+            }
+            Step::Decide { replica, round } => {
+                write!(
+                    f,
+                    "replica #{} gets the decision of round {}",
+                    replica, round
+                )
+            }
+        }
+    }
+}
+
+/// Commands issued by the clients and steps taken by the model, to replay a run with
+/// `ModelChecker::replay` on a network of the same size
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub commands: Vec<Command>,
+    pub steps: Vec<Step>,
+}
+
+impl Schedule {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        serde_json::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.flush()
+    }
+}
+
+/// Invariant broken by a state of the model
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Violation {
+    /// Agreement: two correct replicas disagree on a round
+    Divergence(Divergence),
+    /// Validity: a correct replica delivers a command that no client issued, or delivers a
+    /// command a second time
+    Validity {
+        replica: PeerId,
+        round: RoundNumber,
+        command: CommandId,
+    },
+    /// A client can gather quorums of acknowledgements for two different results of a command
+    Results {
+        command: CommandId,
+        results: (CommandResult, CommandResult),
+    },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Divergence(divergence) => write!(f, "{}", divergence),
+            Violation::Validity {
+                replica,
+                round,
+                command,
+            } => write!(
+                f,
+                "replica #{} delivers {} in round {}, which was not issued or already delivered",
+                replica, command, round
+            ),
+            Violation::Results { command, results } => write!(
+                f,
+                "the client gathers quorums for both {} and {} as the result of {}",
+                results.0.to_string().trim_end(),
+                results.1.to_string().trim_end(),
+                command
+            ),
+        }
+    }
+}
+
+/// Schedule that leads the model to a state breaking an invariant
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailingSchedule {
+    pub schedule: Schedule,
+    pub violation: Violation,
+}
+
+impl Display for FailingSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, after:", self.violation)?;
+        for (position, step) in self.schedule.steps.iter().enumerate() {
+            write!(f, "\n\t{:>4}. {}", position, step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Statistics of an exploration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Exploration {
+    /// Distinct states reached
+    pub states: usize,
+    /// States where no step is enabled
+    pub final_states: usize,
+    /// Length of the longest schedule explored
+    pub depth: usize,
+    /// False if the exploration stopped at the maximum number of states
+    pub complete: bool,
+}
+
+/// Explores every interleaving of the message deliveries and decisions among the replicas of
+/// a small network, driving the actual `ReplicaHandler`s, and checks agreement and validity in
+/// every state reached.
+///
+/// The checker owns the network: the fault-injection layer drops every message, and the checker
+/// delivers those the tracer recorded whenever the schedule says so. The clients issue their
+/// commands at the start and only collect the acknowledgements. Crashed faulty replicas take no
+/// step, the other faulty replicas follow their strategy. The consensus decides as the
+/// `Coordinator` once `n_ack` replicas proposed. A replica waiting for a decision handles no
+/// message, as it is blocked in `wait_for_decision`.
+///
+/// A state is only explored once: states are compared by a digest of the replicas, the messages
+/// in flight, the consensus, the acknowledgements and the decisions.
+/// Every run replays its schedule from the start, so the checker must run on a
+/// `current_thread` runtime whose clock is paused, e.g. in
+/// `#[tokio::test(start_paused = true)]`.
+pub struct ModelChecker {
+    network_info: NetworkInfo,
+    commands: Vec<Command>,
+    keys: Vec<Identity>,
+    keychains: Vec<Arc<KeyChain>>,
+    senders: Vec<UnicastSender<Message>>,
+    identity_table: IdentityTable,
+    max_states: usize,
+}
+
+impl ModelChecker {
+    pub async fn setup(network_info: NetworkInfo, commands: Vec<Command>) -> Self {
+        let mut network_info = network_info;
+        network_info.set_trace(true);
+        let keychains: Vec<KeyChain> = (0..network_info.size())
+            .map(|_| KeyChain::random())
+            .collect();
+        let UnicastSystem { keys, senders, .. } =
+            UnicastSystem::<Message>::setup_with_keychains(keychains.clone()).await;
+        let mut identity_table = IdentityTableBuilder::new(network_info.clone());
+        for keychain in keychains {
+            identity_table.add_peer(keychain);
+        }
+        let keychains = keys
+            .iter()
+            .map(|key| identity_table.keychain(key).unwrap())
+            .collect();
+        let identity_table = identity_table.build();
+        ModelChecker {
+            network_info,
+            commands,
+            keys,
+            keychains,
+            senders,
+            identity_table,
+            max_states: DEFAULT_MAX_STATES,
+        }
+    }
+
+    pub fn set_max_states(&mut self, max_states: usize) {
+        self.max_states = max_states;
+    }
+
+    /// Explores the states of the model depth first.
+    /// Returns the first schedule that breaks an invariant, if any.
+    pub async fn check(&self) -> Result<Exploration, FailingSchedule> {
+        self.explore().await
+    }
+
+    /// Takes the steps of the schedule. Returns the invariant broken by the state they lead to,
+    /// if any.
+    pub async fn replay(&self, steps: &[Step]) -> Result<Option<Violation>, ScheduleError> {
+        self.run(steps).await.map(|run| run.violation())
+    }
+
+    async fn explore(&self) -> Result<Exploration, FailingSchedule> {
+        let mut exploration = Exploration {
+            complete: true,
+            ..Exploration::default()
+        };
+        let mut visited: HashSet<u64> = HashSet::new();
+        let mut schedules: Vec<Vec<Step>> = vec![Vec::new()];
+        // The run of the last explored state goes on with its first enabled step, the other
+        // ones replay their schedule from the start
+        let mut last: Option<(Vec<Step>, Run)> = None;
+        while let Some(steps) = schedules.pop() {
+            let run = match last.take() {
+                Some((previous, mut run))
+                    if steps.len() == previous.len() + 1 && steps.starts_with(&previous) =>
+                {
+                    run.take(self, &steps[previous.len()]).await;
+                    run
+                }
+                _ => self.run(&steps).await.expect("Explored steps are enabled"),
+            };
+            if let Some(violation) = run.violation() {
+                return Err(FailingSchedule {
+                    schedule: Schedule {
+                        commands: self.commands.clone(),
+                        steps,
+                    },
+                    violation,
+                });
+            }
+            if !visited.insert(run.digest()) {
+                continue;
+            }
+            if exploration.states == self.max_states {
+                exploration.complete = false;
+                break;
+            }
+            exploration.states += 1;
+            exploration.depth = exploration.depth.max(steps.len());
+
+            let enabled = run.enabled();
+            if enabled.is_empty() {
+                exploration.final_states += 1;
+            }
+            for step in enabled.into_iter().rev() {
+                let mut next = steps.clone();
+                next.push(step);
+                schedules.push(next);
+            }
+            last = Some((steps, run));
+        }
+        Ok(exploration)
+    }
+
+    /// Sets up a fresh model, and takes the steps
+    async fn run(&self, steps: &[Step]) -> Result<Run, ScheduleError> {
+        let mut run = Run::new(self);
+        for (position, step) in steps.iter().enumerate() {
+            if !run.take(self, step).await {
+                return Err(ScheduleError::Disabled(position));
+            }
+        }
+        Ok(run)
+    }
+}
+
+/// Message in flight
+#[derive(Clone, Debug)]
+struct Envelope {
+    from: PeerId,
+    to: PeerId,
+    message: Message,
+}
+
+/// Proposals and decisions of the consensus of a run
+#[derive(Default)]
+struct ConsensusState {
+    /// Proposals of the rounds that are not decided yet
+    proposals: BTreeMap<RoundNumber, BTreeMap<PeerId, (Set, Set)>>,
+    decisions: BTreeMap<RoundNumber, (Set, Set)>,
+}
+
+/// Consensus of a replica of the model: decides as the `Coordinator`, without delay
+struct ModelConsensus {
+    replica: PeerId,
+    coordinator: Arc<Mutex<Coordinator>>,
+    state: Arc<Mutex<ConsensusState>>,
+}
+
+#[async_trait::async_trait]
+impl Consensus for ModelConsensus {
+    async fn propose(&mut self, proposal: ProposalSignedData) -> Result<(), ConsensusError> {
+        let (_, k, nc_set, c_set) = &proposal;
+        let mut state = self.state.lock().unwrap();
+        if !state.decisions.contains_key(k) {
+            state
+                .proposals
+                .entry(*k)
+                .or_default()
+                .insert(self.replica, (nc_set.clone(), c_set.clone()));
+        }
+        let k = *k;
+        if let Some(decision) = self.coordinator.lock().unwrap().propose(proposal) {
+            state.proposals.remove(&k);
+            state.decisions.insert(k, decision);
+        }
+        Ok(())
+    }
+
+    /// Only returns once the round is decided: the replica times out meanwhile
+    async fn decide(&mut self, round: RoundNumber) -> Result<ProposalData, ConsensusError> {
+        let decision = self
+            .state
+            .lock()
+            .unwrap()
+            .decisions
+            .range(round..)
+            .next()
+            .map(|(k, (nc_set, c_set))| (*k, nc_set.clone(), c_set.clone()));
+        match decision {
+            Some(decision) => Ok(decision),
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// State of the model along a schedule
+struct Run {
+    network_info: NetworkInfo,
+    replicas: BTreeMap<PeerId, ReplicaHandler>,
+    consensus: Arc<Mutex<ConsensusState>>,
+    /// Messages in flight, by number
+    in_flight: BTreeMap<usize, Envelope>,
+    sent: usize,
+    /// Commands issued by the clients
+    issued: HashSet<CommandId>,
+    /// Events of the trace already collected
+    cursor: usize,
+    /// Replicas that acknowledged each (command, round, result, phase)
+    acknowledgements: BTreeMap<(CommandId, RoundNumber, CommandResult, Phase), BTreeSet<PeerId>>,
+    _feedback_outlet: FeedbackReceiver,
+}
+
+impl Run {
+    fn new(checker: &ModelChecker) -> Self {
+        let network_info = checker.network_info.fork();
+        network_info.faults().set_drop_probability(1.0);
+        let (feedback_inlet, feedback_outlet) = FeedbackChannel::channel();
+        let coordinator = Arc::new(Mutex::new(Coordinator::new(network_info.clone())));
+        let consensus = Arc::new(Mutex::new(ConsensusState::default()));
+
+        let (_, _, replica_range, faulty_replica_range) = network_info.compute_ranges();
+        let mut replicas = BTreeMap::new();
+        for id in replica_range.chain(faulty_replica_range.clone()) {
+            let strategy = faulty_replica_range
+                .contains(&id)
+                .then(|| network_info.faulty_replica_strategy(&id));
+            if strategy == Some(FaultyReplicaStrategy::Crash) {
+                continue;
+            }
+            let communicator = Communicator::new(
+                id,
+                checker.keys[id].clone(),
+                checker.keychains[id].clone(),
+                checker.senders[id].clone(),
+                feedback_inlet.clone(),
+                network_info.clone(),
+                checker.identity_table.clone(),
+            );
+            let mut replica = ReplicaHandler::new(
+                communicator,
+                Box::new(ModelConsensus {
+                    replica: id,
+                    coordinator: coordinator.clone(),
+                    state: consensus.clone(),
+                }),
+            );
+            if let Some(strategy) = strategy {
+                replica.corrupt(strategy);
+            }
+            replicas.insert(id, replica);
+        }
+
+        let mut run = Run {
+            network_info,
+            replicas,
+            consensus,
+            in_flight: BTreeMap::new(),
+            sent: 0,
+            issued: checker
+                .commands
+                .iter()
+                .map(|command| *command.id())
+                .collect(),
+            cursor: 0,
+            acknowledgements: BTreeMap::new(),
+            _feedback_outlet: feedback_outlet,
+        };
+        // The clients issue every command to every replica at the start
+        for command in checker.commands.iter() {
+            let issuer = *command.issuer();
+            let signature = checker.keychains[issuer]
+                .sign(&IssuedCommand { command })
+                .ok();
+            for to in run.replicas.keys().cloned().collect::<Vec<PeerId>>() {
+                run.send(issuer, to, Message::Command(command.clone(), signature));
+            }
+        }
+        run
+    }
+
+    fn send(&mut self, from: PeerId, to: PeerId, message: Message) {
+        self.in_flight
+            .insert(self.sent, Envelope { from, to, message });
+        self.sent += 1;
+    }
+
+    /// Takes the step if it is enabled. Returns false otherwise.
+    async fn take(&mut self, checker: &ModelChecker, step: &Step) -> bool {
+        if !self.enabled().contains(step) {
+            return false;
+        }
+        match step {
+            Step::Deliver { message, .. } => {
+                let envelope = self.in_flight.remove(message).unwrap();
+                let replica = self.replicas.get_mut(&envelope.to).unwrap();
+                let from = checker.keys[envelope.from].clone();
+                replica.receive(from, envelope.message).await;
+            }
+            Step::Decide { replica, .. } => self.replicas.get_mut(replica).unwrap().process().await,
+        }
+        self.collect();
+        self.discard_stale();
+        true
+    }
+
+    /// Collects the messages the replicas sent, from the trace
+    fn collect(&mut self) {
+        let events = self.network_info.tracer().events_since(self.cursor);
+        self.cursor += events.len();
+        for event in events.iter() {
+            let (to, message) = match &event.event {
+                Event::Send {
+                    to: Some(to),
+                    message,
+                    ..
+                } => (*to, message),
+                _ => continue,
+            };
+            let message: Message = match serde_json::from_value(message.clone()) {
+                Ok(message) => message,
+                Err(_) => continue,
+            };
+            match message {
+                Message::CommandAcknowledgement(command, round, result, phase, _) => {
+                    self.acknowledgements
+                        .entry((*command.id(), round, result, phase))
+                        .or_default()
+                        .insert(event.peer);
+                }
+                // Messages to crashed replicas are lost
+                message if self.replicas.contains_key(&to) => self.send(event.peer, to, message),
+                _ => {}
+            }
+        }
+    }
+
+    /// Drops the messages whose delivery would not change their replica: the commands it
+    /// received, and the broadcasts of past rounds or of commands it received in the current one.
+    /// It stays so, as the round and the received commands of a replica only grow.
+    fn discard_stale(&mut self) {
+        let replicas = &self.replicas;
+        self.in_flight.retain(|_, envelope| {
+            let database = replicas[&envelope.to].database();
+            match &envelope.message {
+                Message::Command(command, _) => !database.received().contains(command),
+                Message::ReplicaBroadcast(round, set, _) => {
+                    round > database.round()
+                        || (round == database.round()
+                            && !set
+                                .keys()
+                                .all(|command| database.received().contains(command)))
+                }
+                _ => true,
+            }
+        });
+    }
+
+    /// Steps enabled in the current state, in a deterministic order
+    fn enabled(&self) -> Vec<Step> {
+        let consensus = self.consensus.lock().unwrap();
+        let mut steps = Vec::new();
+        for (id, replica) in self.replicas.iter() {
+            match replica.awaiting_decision() {
+                Some(round) => {
+                    if consensus.decisions.range(round..).next().is_some() {
+                        steps.push(Step::Decide {
+                            replica: *id,
+                            round,
+                        });
+                    }
+                }
+                None => steps.extend(
+                    self.in_flight
+                        .iter()
+                        .filter(|(_, envelope)| envelope.to == *id)
+                        .map(|(message, envelope)| Step::Deliver {
+                            message: *message,
+                            from: envelope.from,
+                            to: envelope.to,
+                        }),
+                ),
+            }
+        }
+        steps
+    }
+
+    /// Returns the first invariant the state breaks, if any
+    fn violation(&self) -> Option<Violation> {
+        let (_, _, replica_range, _) = self.network_info.compute_ranges();
+        let decisions = self.network_info.decisions();
+        if let Err(divergence) = decisions.check(replica_range.clone()) {
+            return Some(Violation::Divergence(divergence));
+        }
+
+        for (replica, rounds) in decisions.snapshot() {
+            if !replica_range.contains(&replica) {
+                continue;
+            }
+            let mut delivered: HashSet<CommandId> = HashSet::new();
+            for (round, decision) in rounds {
+                for command in decision.nc_set.iter().chain(decision.c_set.iter()) {
+                    if !self.issued.contains(command) || !delivered.insert(*command) {
+                        return Some(Violation::Validity {
+                            replica,
+                            round,
+                            command: *command,
+                        });
+                    }
+                }
+            }
+        }
+
+        // A client completes a command with a quorum of identical acknowledgements
+        let mut completed: BTreeMap<CommandId, &CommandResult> = BTreeMap::new();
+        for ((command, _, result, phase), replicas) in self.acknowledgements.iter() {
+            let quorum = match phase {
+                Phase::ACK => self.network_info.n_ack(),
+                Phase::CHK => self.network_info.f() + 1,
+            };
+            if replicas.len() < quorum {
+                continue;
+            }
+            match completed.insert(*command, result) {
+                Some(other) if other != result => {
+                    return Some(Violation::Results {
+                        command: *command,
+                        results: (other.clone(), result.clone()),
+                    })
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Digest of the state of the model, up to the numbers of the messages and to a permutation
+    /// of the correct replicas: they behave the same regardless of their identifier, and of the
+    /// replica that sent them a message. The faulty replicas keep their identifier, as their
+    /// strategy may depend on it.
+    fn digest(&self) -> u64 {
+        let (_, _, replica_range, _) = self.network_info.compute_ranges();
+        let consensus = self.consensus.lock().unwrap();
+        let decisions = self.network_info.decisions().snapshot();
+        let mut replicas: Vec<(Option<PeerId>, u64)> = self
+            .replicas
+            .iter()
+            .map(|(id, replica)| {
+                let mut state = DefaultHasher::new();
+                replica.digest(&mut state);
+                let mut incoming: Vec<Vec<u8>> = self
+                    .in_flight
+                    .values()
+                    .filter(|envelope| envelope.to == *id)
+                    .map(|envelope| bincode::serialize(&envelope.message).unwrap_or_default())
+                    .collect();
+                incoming.sort();
+                incoming.hash(&mut state);
+                for (round, proposals) in consensus.proposals.iter() {
+                    (round, proposals.get(id)).hash(&mut state);
+                }
+                bincode::serialize(&decisions.get(id))
+                    .unwrap_or_default()
+                    .hash(&mut state);
+                for (acknowledgement, replicas) in self.acknowledgements.iter() {
+                    if replicas.contains(id) {
+                        acknowledgement.hash(&mut state);
+                    }
+                }
+                let identity = (!replica_range.contains(id)).then_some(*id);
+                (identity, state.finish())
+            })
+            .collect();
+        replicas.sort();
+
+        let mut state = DefaultHasher::new();
+        replicas.hash(&mut state);
+        consensus.decisions.hash(&mut state);
+        state.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::banking::action::Action;
+
+    use super::*;
+
+    fn commands() -> Vec<Command> {
+        vec![
+            Command::new(0, Action::Register),
+            Command::new(0, Action::Deposit(5)),
+        ]
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn explores_every_interleaving_of_a_small_network() {
+        let network_info = NetworkInfo::with_default_report_folder(1, 3, 0, 0, 0, 3);
+        let checker = ModelChecker::setup(network_info, commands()).await;
+        let exploration = checker.check().await.unwrap();
+        assert!(exploration.complete);
+        assert!(exploration.final_states > 0);
+        assert!(exploration.states > exploration.depth);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn explores_every_interleaving_of_two_clients() {
+        let network_info = NetworkInfo::with_default_report_folder(2, 3, 0, 0, 0, 3);
+        let commands = vec![
+            Command::new(0, Action::Register),
+            Command::new(1, Action::Register),
+        ];
+        let checker = ModelChecker::setup(network_info, commands).await;
+        let exploration = checker.check().await.unwrap();
+        assert!(exploration.complete);
+        assert!(exploration.final_states > 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn byzantine_replica_cannot_break_the_invariants() {
+        // The smallest network with a faulty replica that a scenario file accepts
+        let mut network_info = NetworkInfo::with_default_report_folder(1, 5, 0, 1, 0, 5);
+        network_info.set_faulty_replica_strategy(6, FaultyReplicaStrategy::WrongResults);
+        let commands = vec![Command::new(0, Action::Register)];
+        let checker = ModelChecker::setup(network_info, commands).await;
+        let exploration = checker.check().await.unwrap();
+        assert!(exploration.complete);
+        assert!(exploration.final_states > 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_a_replayable_failing_schedule() {
+        // Two quorums of 2 out of 3 replicas do not intersect in a correct replica that
+        // acknowledged both: a lagging replica completes a result that the others rolled back
+        let network_info = NetworkInfo::with_default_report_folder(1, 3, 0, 0, 0, 2);
+        let checker = ModelChecker::setup(network_info, commands()).await;
+        let failing = checker.check().await.unwrap_err();
+        assert!(matches!(failing.violation, Violation::Results { .. }));
+        assert!(failing.to_string().contains("deliver message"));
+
+        let path = std::env::temp_dir().join("genericbft_failing_schedule.json");
+        failing.schedule.write(&path).unwrap();
+        let schedule = Schedule::from_file(&path).unwrap();
+        assert_eq!(schedule, failing.schedule);
+        assert_eq!(
+            checker.replay(&schedule.steps).await,
+            Ok(Some(failing.violation))
+        );
+        assert_eq!(
+            checker
+                .replay(&schedule.steps[..1])
+                .await
+                .map(|v| v.is_some()),
+            Ok(false)
+        );
+
+        let disabled = Step::Decide {
+            replica: 1,
+            round: 1,
+        };
+        assert_eq!(
+            checker.replay(&[disabled]).await,
+            Err(ScheduleError::Disabled(0))
+        );
+    }
+}
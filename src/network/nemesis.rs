@@ -0,0 +1,130 @@
+use std::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{network::fault_injector::FaultInjector, peer::peer::PeerId};
+
+/// Fault injected by the nemesis
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NemesisEvent {
+    Crash(PeerId),
+    Restart(PeerId),
+    /// Partitions the groups of peers from each other, until the next `Heal` or `Partition`
+    Partition(Vec<Vec<PeerId>>),
+    Heal,
+    /// Adds the given latency, in ms, to the link between two peers
+    SlowLink(PeerId, PeerId, u64),
+    RestoreLink(PeerId, PeerId),
+    DropMessages(f64),
+    DuplicateMessages(f64),
+}
+
+impl NemesisEvent {
+    pub fn apply(&self, faults: &FaultInjector) {
+        match self {
+            NemesisEvent::Crash(peer) => faults.crash(*peer),
+            NemesisEvent::Restart(peer) => faults.restart(*peer),
+            NemesisEvent::Partition(groups) => faults.partition(groups.clone(), None),
+            NemesisEvent::Heal => faults.heal(),
+            NemesisEvent::SlowLink(a, b, delay) => faults.slow_link(*a, *b, *delay),
+            NemesisEvent::RestoreLink(a, b) => faults.restore_link(*a, *b),
+            NemesisEvent::DropMessages(probability) => faults.set_drop_probability(*probability),
+            NemesisEvent::DuplicateMessages(probability) => {
+                faults.set_duplicate_probability(*probability)
+            }
+        }
+    }
+}
+
+impl Display for NemesisEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NemesisEvent::Crash(peer) => write!(f, "crash #{}", peer),
+            NemesisEvent::Restart(peer) => write!(f, "restart #{}", peer),
+            NemesisEvent::Partition(groups) => write!(f, "partition {:?}", groups),
+            NemesisEvent::Heal => write!(f, "heal the partition"),
+            NemesisEvent::SlowLink(a, b, delay) => {
+                write!(f, "slow down the link #{} <-> #{} by {}ms", a, b, delay)
+            }
+            NemesisEvent::RestoreLink(a, b) => write!(f, "restore the link #{} <-> #{}", a, b),
+            NemesisEvent::DropMessages(probability) => {
+                write!(f, "drop messages with probability {}", probability)
+            }
+            NemesisEvent::DuplicateMessages(probability) => {
+                write!(f, "duplicate messages with probability {}", probability)
+            }
+        }
+    }
+}
+
+/// Timeline of the faults injected while the clients execute their commands.
+/// The time of an event is relative to the start of the execution.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Nemesis {
+    timeline: Vec<(Duration, NemesisEvent)>,
+}
+
+impl Nemesis {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules the event at the given time. Events scheduled at the same time are applied
+    /// in the order they were scheduled.
+    pub fn at(mut self, time: Duration, event: NemesisEvent) -> Self {
+        let index = self.timeline.partition_point(|(t, _)| *t <= time);
+        self.timeline.insert(index, (time, event));
+        self
+    }
+
+    pub fn timeline(&self) -> &Vec<(Duration, NemesisEvent)> {
+        &self.timeline
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timeline.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeline_is_ordered() {
+        let nemesis = Nemesis::new()
+            .at(Duration::from_secs(5), NemesisEvent::Restart(3))
+            .at(Duration::from_secs(1), NemesisEvent::Crash(3))
+            .at(Duration::from_secs(5), NemesisEvent::Heal);
+        let events: Vec<&NemesisEvent> = nemesis.timeline().iter().map(|(_, e)| e).collect();
+        assert_eq!(
+            events,
+            vec![
+                &NemesisEvent::Crash(3),
+                &NemesisEvent::Restart(3),
+                &NemesisEvent::Heal
+            ]
+        );
+    }
+
+    #[test]
+    fn events_are_applied_to_the_faults() {
+        let faults = FaultInjector::new();
+        NemesisEvent::Crash(1).apply(&faults);
+        NemesisEvent::Partition(vec![vec![0], vec![2]]).apply(&faults);
+        NemesisEvent::SlowLink(0, 1, 50).apply(&faults);
+        assert!(faults.is_crashed(1));
+        assert!(faults.is_partitioned(0, 2));
+        assert_eq!(faults.extra_delay(1, 0), 50);
+
+        NemesisEvent::Restart(1).apply(&faults);
+        NemesisEvent::Heal.apply(&faults);
+        NemesisEvent::RestoreLink(0, 1).apply(&faults);
+        assert!(!faults.is_crashed(1));
+        assert!(!faults.is_partitioned(0, 2));
+        assert_eq!(faults.extra_delay(1, 0), 0);
+    }
+}
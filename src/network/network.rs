@@ -1,72 +1,101 @@
 use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTimeError};
 
 use doomstack::Top;
 use futures::future::join_all;
 
 use talk::time::{timeout, Timeout};
-use talk::{crypto::Identity, sync::fuse::Fuse, unicast::test::UnicastSystem};
-use tokio::sync::mpsc::error::SendError;
+use talk::{
+    crypto::{Identity, KeyChain},
+    sync::fuse::Fuse,
+    unicast::test::UnicastSystem,
+};
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::SendError;
 use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
 
-use super::{NetworkInfo, NetworkPeer};
+use super::{
+    ChromeTrace, Completion, Counterexample, Divergence, History, LatencyReport, MetricsSnapshot,
+    Nemesis, NemesisEvent, NetworkInfo, NetworkPeer, Trace,
+};
 
+use crate::application::Application;
 use crate::banking::action::Action;
-use crate::banking::banking::Money;
-use crate::peer::coordinator::Coordinator;
-use crate::talk::Command;
+use crate::banking::banking::{Banking, Money};
+use crate::peer::consensus::ConsensusSystem;
+use crate::talk::{Command, CommandId};
 use crate::{
     crypto::identity_table::{IdentityTable, IdentityTableBuilder},
-    peer::{handler::HandlerBuilder, peer::PeerId, runner::Runner, Peer},
+    peer::{
+        handler::{Adversarial, HandlerBuilder},
+        peer::PeerId,
+        runner::Runner,
+        Peer,
+    },
     talk::{Feedback, FeedbackChannel, FeedbackReceiver, FeedbackSender, Instruction, Message},
     types::*,
 };
 
-type MessagePeer = Peer<Message>;
 const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
-// A network of `Peer<Message>`.
-pub struct Network {
+// A network of `Peer<Message>`, whose replicas replicate the application `A`.
+pub struct Network<A: Application = Banking> {
     network_info: NetworkInfo,
-    peers_inlets: Vec<InstructionSender>,
-    pending_execution: HashMap<PeerId, VecDeque<Command>>,
+    peers_inlets: Vec<InstructionSender<A::Action>>,
+    pending_execution: HashMap<PeerId, VecDeque<Command<A::Action>>>,
     pending_nbr: usize,
-    feedback_outlet: FeedbackReceiver,
+    feedback_outlet: FeedbackReceiver<A::Action>,
     identity_table: IdentityTable,
+    nemesis: Nemesis,
+    applied_events: Arc<Mutex<Vec<(Duration, NemesisEvent)>>>,
+    /// Commands sent to their client, with the time they were sent at
+    in_flight: HashMap<CommandId, (A::Action, Instant)>,
+    completions: Vec<Completion<A::Action>>,
+    /// Every command sent to a client, with its result
+    history: History<A::Action>,
     _fuse: Fuse,
 }
 
-impl Network {
+impl<A: Adversarial> Network<A> {
     pub async fn setup(network_info: NetworkInfo) -> Self {
-        let mut inlets: Vec<InstructionSender> = Vec::new();
-        let mut outlets: Vec<InstructionReceiver> = Vec::new();
+        let mut inlets: Vec<InstructionSender<A::Action>> = Vec::new();
+        let mut outlets: Vec<InstructionReceiver<A::Action>> = Vec::new();
         let (feedback_inlet, feedback_outlet) = FeedbackChannel::channel();
         let size = network_info.size();
         for _ in 0..size {
-            let (tx, rx) = mpsc::channel::<Instruction>(32);
+            let (tx, rx) = mpsc::channel::<Instruction<A::Action>>(32);
             inlets.push(tx);
             outlets.push(rx);
         }
         let inlets = inlets;
         let outlets = outlets;
 
+        // The peers sign with the key material behind their identity on the network
+        let keychains: Vec<KeyChain> = (0..size).map(|_| KeyChain::random()).collect();
         let UnicastSystem {
             keys,
             senders,
             receivers,
-        } = UnicastSystem::<Message>::setup(size).await.into();
+        } = UnicastSystem::<Message<A::Action>>::setup_with_keychains(keychains.clone())
+            .await
+            .into();
+
+        let consensus = ConsensusSystem::setup(network_info.clone()).await;
 
-        let (peers, coordinator, identity_table) = Self::compose_peers(
+        let (peers, identity_table) = Self::compose_peers(
             network_info.clone(),
+            keychains,
             keys,
             senders,
             receivers,
             outlets,
             feedback_inlet,
+            &consensus,
         );
 
-        let mut pending_execution: HashMap<PeerId, VecDeque<Command>> = HashMap::new();
+        let mut pending_execution: HashMap<PeerId, VecDeque<Command<A::Action>>> = HashMap::new();
 
         let fuse = Fuse::new();
         {
@@ -78,7 +107,7 @@ impl Network {
             }
 
             fuse.spawn(async move {
-                coordinator.run().await;
+                consensus.run().await;
             });
         }
 
@@ -89,35 +118,40 @@ impl Network {
             pending_nbr: 0,
             feedback_outlet,
             identity_table,
+            nemesis: Nemesis::new(),
+            applied_events: Arc::new(Mutex::new(Vec::new())),
+            in_flight: HashMap::new(),
+            completions: Vec::new(),
+            history: History::new(),
             _fuse: fuse,
         }
     }
 
     fn compose_peers(
         network_info: NetworkInfo,
+        keychains: Vec<KeyChain>,
         keys: Vec<Identity>,
-        senders: Vec<UnicastSender<Message>>,
-        receivers: Vec<UnicastReceiver<Message>>,
-        outlets: Vec<InstructionReceiver>,
-        feedback_inlet: FeedbackSender,
-    ) -> (Vec<MessagePeer>, Coordinator, IdentityTable) {
+        senders: Vec<UnicastSender<Message<A::Action>>>,
+        receivers: Vec<UnicastReceiver<Message<A::Action>>>,
+        outlets: Vec<InstructionReceiver<A::Action>>,
+        feedback_inlet: FeedbackSender<A::Action>,
+        consensus: &ConsensusSystem<A::Action>,
+    ) -> (Vec<Peer<A::Action>>, IdentityTable) {
         let (keys, senders, receivers) =
             (keys.into_iter(), senders.into_iter(), receivers.into_iter());
         let size = network_info.size();
         let ids = (0..size).into_iter();
 
-        let mut identity_table = IdentityTableBuilder::new(network_info.clone());
-        for key in keys.clone() {
-            identity_table.add_peer(key.clone());
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        for keychain in keychains {
+            identity_table_builder.add_peer(keychain);
         }
-        let identity_table = identity_table.build();
+        let identity_table = identity_table_builder.build();
 
         let (client_range, faulty_client_range, replica_range, faulty_replica_range) =
             network_info.compute_ranges();
 
-        let coordinator = Coordinator::new(network_info.clone());
-
-        let peers: Vec<MessagePeer> = ids
+        let peers: Vec<Peer<A::Action>> = ids
             .zip(keys)
             .zip(senders)
             .zip(receivers)
@@ -131,24 +165,28 @@ impl Network {
                     &faulty_replica_range,
                 )
                 .unwrap();
-                let handler = HandlerBuilder::handler(
+                let keychain = identity_table_builder
+                    .keychain(&key)
+                    .expect("Every peer is registered");
+                let handler = HandlerBuilder::handler::<A>(
                     peer_type,
                     id,
                     key,
+                    keychain,
                     sender,
                     feedback_inlet.clone(),
-                    &coordinator,
+                    consensus,
                     network_info.clone(),
                     identity_table.clone(),
                 );
-                Peer::<Message>::new(receiver, outlet, handler)
+                Peer::new(receiver, outlet, handler)
             })
             .collect::<Vec<_>>();
 
-        (peers, coordinator, identity_table)
+        (peers, identity_table)
     }
 
-    fn peer_inlet(&self, id: PeerId) -> Option<&InstructionSender> {
+    fn peer_inlet(&self, id: PeerId) -> Option<&InstructionSender<A::Action>> {
         self.peers_inlets.get(id)
     }
 
@@ -202,9 +240,9 @@ impl Network {
 
     async fn send_instruction(
         &self,
-        instruction: Instruction,
+        instruction: Instruction<A::Action>,
         id: PeerId,
-    ) -> Option<JoinHandle<Result<(), SendError<Instruction>>>> {
+    ) -> Option<JoinHandle<Result<(), SendError<Instruction<A::Action>>>>> {
         self.peer_inlet(id).map(|sender| {
             let sender = sender.clone();
             tokio::spawn(async move { sender.send(instruction).await })
@@ -212,8 +250,9 @@ impl Network {
     }
 
     /// Execute the given command and wait for the result
-    pub fn execute(&mut self, client: PeerId, command: Command) -> bool {
-        let status = self.pending_execution
+    pub fn execute(&mut self, client: PeerId, command: Command<A::Action>) -> bool {
+        let status = self
+            .pending_execution
             .get_mut(&client)
             .map(|pending_commands| {
                 pending_commands.push_back(command);
@@ -233,6 +272,9 @@ impl Network {
             .map(|vec| vec.pop_front())
             .flatten();
         if let Some(command) = command {
+            self.in_flight
+                .insert(*command.id(), (command.action().clone(), Instant::now()));
+            self.history.invoke(&command);
             let instruction = Instruction::Execute(command.clone());
             self.send_instruction(instruction, client)
                 .await
@@ -242,21 +284,126 @@ impl Network {
         false
     }
 
-    fn display_feedback(feedback: Feedback) {
+    fn display_feedback(feedback: Feedback<A::Action>) {
         match feedback {
             Feedback::Error(id, msg) => println!("Client #{} failed: {}", id, msg),
             Feedback::Acknowledgement(id) => println!("Client #{} request is successful", id),
-            Feedback::Result(id, res) => {
-                println!("Client #{} request: {}", id, res)
+            Feedback::Result(id, receipt) => {
+                println!("Client #{} request: {}", id, receipt.result())
             }
             Feedback::ShutdownComplete(_) => {}
         }
     }
 
-    // Assume that no client is faulty
+    /// Records the completion of the command acknowledged by `feedback`, if it is measured
+    fn complete(&mut self, feedback: &Feedback<A::Action>) {
+        if let Feedback::Result(client, receipt) = feedback {
+            self.history
+                .complete(receipt.id(), receipt.result().clone());
+            if let Some((action, start)) = self.in_flight.remove(receipt.id()) {
+                self.completions.push(Completion {
+                    client: *client,
+                    action,
+                    round: *receipt.round(),
+                    phase: receipt.phase().clone(),
+                    latency: start.elapsed(),
+                });
+            }
+        }
+    }
+
+    /// Returns the commands completed during the last `execute_all`
+    pub fn completions(&self) -> &Vec<Completion<A::Action>> {
+        &self.completions
+    }
+
+    /// Returns the latency percentiles of the commands completed during the last `execute_all`
+    pub fn latency_report(&self) -> LatencyReport {
+        LatencyReport::new(self.completions.iter())
+    }
+
+    /// Returns the current metrics of the replicas and the coordinator
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.network_info.metrics().snapshot()
+    }
+
+    /// Returns the events recorded so far, if tracing is enabled, see `NetworkInfo::set_trace`
+    pub fn trace(&self) -> Trace {
+        self.network_info.tracer().trace()
+    }
+
+    /// Path of the trace file written when the network shuts down
+    pub fn trace_path(&self) -> String {
+        format!(
+            "{}/trace_{}.jsonl",
+            self.network_info.report_folder(),
+            self.network_info.seed()
+        )
+    }
+
+    /// Path of the timeline of the trace, in the Chrome trace-event format
+    pub fn chrome_trace_path(&self) -> String {
+        format!(
+            "{}/trace_{}.chrome.json",
+            self.network_info.report_folder(),
+            self.network_info.seed()
+        )
+    }
+
+    /// Returns the commands sent to the clients since the network was set up, with their results
+    pub fn history(&self) -> &History<A::Action> {
+        &self.history
+    }
+
+    /// Checks that the correct replicas agree on the rounds they decided so far, see
+    /// `Decisions::check`, and on the state they ended in once the network shut down, see
+    /// `Decisions::check_final`
+    pub fn check_safety(&self) -> Result<(), Divergence> {
+        let (_, _, replicas, _) = self.network_info.compute_ranges();
+        let decisions = self.network_info.decisions();
+        decisions.check(replicas.clone())?;
+        decisions.check_final(replicas)
+    }
+
+    /// Sets the faults to inject during the next executions
+    pub fn set_nemesis(&mut self, nemesis: Nemesis) {
+        self.nemesis = nemesis;
+    }
+
+    /// Returns the events applied by the nemesis, with the time they were applied at
+    pub fn applied_events(&self) -> Vec<(Duration, NemesisEvent)> {
+        self.applied_events.lock().unwrap().clone()
+    }
+
+    /// Applies the events of the nemesis at their time, from now on
+    fn spawn_nemesis(&self) -> JoinHandle<()> {
+        let timeline = self.nemesis.timeline().clone();
+        let faults = self.network_info.faults().clone();
+        let applied_events = self.applied_events.clone();
+        tokio::spawn(async move {
+            let start = Instant::now();
+            for (time, event) in timeline {
+                sleep_until(start + time).await;
+                event.apply(&faults);
+                println!("[{:?}] Nemesis: {}", start.elapsed(), event);
+                applied_events
+                    .lock()
+                    .unwrap()
+                    .push((start.elapsed(), event));
+            }
+        })
+    }
+
+    // Faulty clients acknowledge their commands as soon as they are sent
+    // The events of the nemesis that are not applied by the end of the execution are discarded
+    // Only the commands sent during the execution are measured
     pub async fn execute_all(&mut self) {
+        let nemesis = self.spawn_nemesis();
+        self.in_flight.clear();
+        self.completions.clear();
         // Execute one action for every peer
-        for i in 0..self.network_info.nbr_clients() {
+        let (client_range, faulty_client_range, _, _) = self.network_info.compute_ranges();
+        for i in client_range.chain(faulty_client_range) {
             self.execute_next(i).await;
         }
 
@@ -266,42 +413,21 @@ impl Network {
             if let Some(feedback) = self.feedback_outlet.recv().await {
                 count += 1;
                 let from = feedback.from();
+                self.complete(&feedback);
                 Self::display_feedback(feedback);
                 self.execute_next(from).await;
             }
         }
+        nemesis.abort();
     }
     //async fn execute_multiple(&mut self, )
 
-    /* Banking operations */
-
-    pub fn deposit(&mut self, client: PeerId, amount: Money) -> bool {
-        self.execute(client, Command::new(client, Action::Deposit(amount)))
-    }
-
-    pub fn register(&mut self, client: PeerId) -> bool {
-        self.execute(client, Command::new(client, Action::Register))
-    }
-
-    pub fn get_balance(&mut self, client: PeerId) -> bool {
-        self.execute(client, Command::new(client, Action::Get))
-    }
-
-    pub fn withdraw(&mut self, client: PeerId, amount: Money) -> bool {
-        self.execute(client, Command::new(client, Action::Withdraw(amount)))
+    /// Issues `action` from `client` with an identifier drawn from the seeded random number generator
+    pub fn execute_action(&mut self, client: PeerId, action: A::Action) -> bool {
+        let id = self.network_info.generate_command_id();
+        self.execute(client, Command::with_id(id, client, action))
     }
 
-    pub fn register_all(&mut self) -> Vec<bool> {
-        let mut feedbacks: Vec<bool> = Vec::new();
-        for i in 0..self.network_info().nbr_clients() {
-            let res = self.register(i);
-            feedbacks.push(res);
-        }
-
-        feedbacks
-    }
-    /* _______________________ */
-
     pub fn network_info(&self) -> &NetworkInfo {
         &self.network_info
     }
@@ -310,15 +436,16 @@ impl Network {
         &self.identity_table
     }
 
-    async fn receive_feedback(&mut self) -> Option<Feedback> {
+    async fn receive_feedback(&mut self) -> Option<Feedback<A::Action>> {
         self.feedback_outlet.recv().await
     }
 
     /// Blocking function to shutdown the network.
     pub async fn shutdown(&mut self) -> Result<Duration, SystemTimeError> {
         println!("Start shutdown");
-        let mut handles: Vec<JoinHandle<Result<Result<(), SendError<Instruction>>, Top<Timeout>>>> =
-            Vec::new();
+        let mut handles: Vec<
+            JoinHandle<Result<Result<(), SendError<Instruction<A::Action>>>, Top<Timeout>>>,
+        > = Vec::new();
         for peer in self.peers_inlets.clone() {
             let future = async move { peer.send(Instruction::Shutdown).await };
             let future = timeout(SHUTDOWN_TIMEOUT.clone(), future);
@@ -328,6 +455,15 @@ impl Network {
         }
         tokio::join!(join_all(handles));
         self.wait_until_shutdown().await;
+        if self.network_info.trace() {
+            let trace = self.trace();
+            let written = std::fs::create_dir_all(self.network_info.report_folder())
+                .and_then(|_| trace.write(self.trace_path()))
+                .and_then(|_| ChromeTrace::new(&trace).write(self.chrome_trace_path()));
+            if let Err(e) = written {
+                println!("Couldn't write the trace to {}: {}", self.trace_path(), e);
+            }
+        }
         self.network_info().elapsed()
     }
     /// Wait until every peers are shut downed.
@@ -356,7 +492,43 @@ impl Network {
     }
 }
 
-impl Drop for Network {
+impl Network {
+    /* Banking operations */
+
+    pub fn deposit(&mut self, client: PeerId, amount: Money) -> bool {
+        self.execute_action(client, Action::Deposit(amount))
+    }
+
+    pub fn register(&mut self, client: PeerId) -> bool {
+        self.execute_action(client, Action::Register)
+    }
+
+    pub fn get_balance(&mut self, client: PeerId) -> bool {
+        self.execute_action(client, Action::Get)
+    }
+
+    pub fn withdraw(&mut self, client: PeerId, amount: Money) -> bool {
+        self.execute_action(client, Action::Withdraw(amount))
+    }
+
+    pub fn register_all(&mut self) -> Vec<bool> {
+        let mut feedbacks: Vec<bool> = Vec::new();
+        for i in 0..self.network_info().nbr_clients() {
+            let res = self.register(i);
+            feedbacks.push(res);
+        }
+
+        feedbacks
+    }
+
+    /// Checks that the history of every correct client is linearizable, see `History::check`
+    pub fn check_linearizability(&self) -> Result<(), Counterexample> {
+        let (clients, _, _, _) = self.network_info.compute_ranges();
+        self.history.check(clients)
+    }
+}
+
+impl<A: Application> Drop for Network<A> {
     fn drop(&mut self) {
         println!(
             "[{:#?}] Network: shutdown",
@@ -368,14 +540,22 @@ impl Drop for Network {
 #[cfg(test)]
 mod tests {
 
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
+
+    use crate::peer::{
+        consensus::ConsensusMode,
+        handler::{FaultyClientStrategy, FaultyReplicaStrategy},
+    };
 
     use super::*;
+    use crate::network::{DelayDistribution, Event, Nemesis, NemesisEvent, Topology, Tracer};
+    use crate::talk::CommandResult;
+    use crate::tests::util::Utils;
 
     #[tokio::test]
     async fn building_network_works() {
         let network_info = NetworkInfo::with_default_report_folder(5, 5, 0, 0, 100, 3);
-        let mut network = Network::setup(network_info).await;
+        let mut network: Network = Network::setup(network_info).await;
         for i in 0..10 {
             network.send_instruction(Instruction::Testing, i).await;
             network.send_instruction(Instruction::Shutdown, i).await;
@@ -389,7 +569,7 @@ mod tests {
     // #[tokio::test]
     // async fn sending_instruction_with_type_reaches_every_peers() {
     //     let network_info = NetworkInfo::with_default_report_folder(2, 2, 2, 2, 10, 0);
-    //     let mut network = Network::setup(network_info).await;
+    //     let mut network: Network = Network::setup(network_info).await;
     //     let types: [NetworkPeer; 4] = [
     //         NetworkPeer::Client,
     //         NetworkPeer::FaultyClient,
@@ -416,7 +596,7 @@ mod tests {
     async fn end_to_end_test1() -> Duration {
         let network_info =
             NetworkInfo::default_parameters(3, 11, 0, 2, 100, 0.1, String::from("resources/test1"));
-        let mut network = Network::setup(network_info).await;
+        let mut network: Network = Network::setup(network_info).await;
 
         network.register_all();
         for i in 0..3 {
@@ -435,7 +615,7 @@ mod tests {
     async fn end_to_end_test2() -> Duration {
         let network_info =
             NetworkInfo::default_parameters(3, 11, 0, 2, 100, 0.1, String::from("resources/test2"));
-        let mut network = Network::setup(network_info).await;
+        let mut network: Network = Network::setup(network_info).await;
         network.register_all();
         for i in 0..3 {
             for _ in 0..10 {
@@ -451,6 +631,374 @@ mod tests {
             .expect("Failed to deliver the time elapsed")
     }
 
+    #[tokio::test]
+    async fn end_to_end_test_with_every_consensus() {
+        for mode in [
+            ConsensusMode::Oracle,
+            ConsensusMode::Simulated,
+            ConsensusMode::Pbft,
+        ] {
+            let mut network_info = Utils::network_info(2, 0, 0.1);
+            network_info.set_consensus_mode(mode);
+            let mut network: Network = Network::setup(network_info).await;
+            Utils::deposit_and_withdraw(&mut network, 3);
+            Utils::execute_and_shutdown(&mut network, Duration::from_secs(60), mode).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn end_to_end_test_with_byzantine_replicas() {
+        for strategy in [
+            FaultyReplicaStrategy::EquivocateAcknowledgements,
+            FaultyReplicaStrategy::TruncateBroadcasts,
+            FaultyReplicaStrategy::ForgeCommands,
+            FaultyReplicaStrategy::WrongResults,
+            FaultyReplicaStrategy::GarbageProposals,
+            FaultyReplicaStrategy::Delay(100),
+        ] {
+            let mut network_info = Utils::network_info(2, 0, 0.1);
+            network_info.set_faulty_replicas_strategy(strategy.clone());
+            let mut network: Network = Network::setup(network_info).await;
+            Utils::deposit_and_withdraw(&mut network, 3);
+            Utils::execute_and_shutdown(&mut network, Duration::from_secs(60), &strategy).await;
+            // The faulty replica cannot make the correct ones diverge
+            assert_eq!(network.check_safety(), Ok(()), "{:?}", strategy);
+            if strategy == FaultyReplicaStrategy::ForgeCommands {
+                // The correct replicas drop the forged commands
+                let (_, _, replica_range, _) = network.network_info().compute_ranges();
+                let metrics = network.metrics();
+                for id in replica_range {
+                    assert!(metrics.replicas[&id].commands_rejected > 0);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn end_to_end_test_with_byzantine_consensus() {
+        let strategy = FaultyReplicaStrategy::EquivocateConsensus;
+        let mut network_info = Utils::network_info(2, 0, 0.1);
+        network_info.set_consensus_mode(ConsensusMode::Pbft);
+        network_info.set_faulty_replicas_strategy(strategy.clone());
+        let mut network: Network = Network::setup(network_info).await;
+        Utils::deposit_and_withdraw(&mut network, 3);
+        Utils::execute_and_shutdown(&mut network, Duration::from_secs(60), strategy).await;
+        assert_eq!(network.check_safety(), Ok(()));
+    }
+
+    /// Runs the same workload on the 2 honest clients, alongside a faulty client following
+    /// `strategy` if any, and returns the actions of the honest clients with their results
+    async fn honest_results(
+        strategy: Option<FaultyClientStrategy>,
+    ) -> Vec<(PeerId, Action, Option<CommandResult>)> {
+        let faulty_clients = strategy.iter().count();
+        let mut network_info = Utils::network_info(2, faulty_clients, 0.1);
+        if let Some(strategy) = strategy.clone() {
+            network_info.set_faulty_clients_strategy(strategy);
+        }
+        let mut network: Network = Network::setup(network_info).await;
+        network.register_all();
+        for i in 2..2 + faulty_clients {
+            network.register(i);
+        }
+        for i in 0..2 + faulty_clients {
+            for _ in 0..3 {
+                network.deposit(i, 2);
+                network.withdraw(i, 1);
+            }
+            network.get_balance(i);
+        }
+
+        Utils::execute_and_shutdown(&mut network, Duration::from_secs(60), &strategy).await;
+        assert_eq!(network.check_safety(), Ok(()), "{:?}", strategy);
+        assert_eq!(network.check_linearizability(), Ok(()), "{:?}", strategy);
+
+        // The clients are interleaved differently across runs, but not the commands of a client
+        let mut results: Vec<(PeerId, Action, Option<CommandResult>)> = network
+            .history()
+            .operations()
+            .iter()
+            .filter(|operation| *operation.command.issuer() < 2)
+            .map(|operation| {
+                (
+                    *operation.command.issuer(),
+                    operation.command.action().clone(),
+                    operation.response.clone().map(|(_, result)| result),
+                )
+            })
+            .collect();
+        results.sort_by_key(|(client, _, _)| *client);
+        results
+    }
+
+    #[tokio::test]
+    async fn end_to_end_test_with_adversarial_clients() {
+        let baseline = honest_results(None).await;
+        assert!(baseline.iter().all(|(_, _, result)| result.is_some()));
+        for client in 0..2 {
+            let balance = (client, Action::Get, Some(CommandResult::Success(Some(3))));
+            assert!(baseline.contains(&balance));
+        }
+        for strategy in [
+            FaultyClientStrategy::Idle,
+            FaultyClientStrategy::Flood(5),
+            FaultyClientStrategy::DuplicateIds,
+            FaultyClientStrategy::Replay,
+            FaultyClientStrategy::Partial(2),
+        ] {
+            // The honest clients get the same results as without the faulty client
+            assert_eq!(
+                honest_results(Some(strategy.clone())).await,
+                baseline,
+                "{:?} changed the results of the honest clients",
+                strategy
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn end_to_end_test_with_virtual_time() {
+        let folder = std::env::temp_dir().join("genericbft_virtual_time");
+        let mut network_info = NetworkInfo::default_parameters(
+            2,
+            6,
+            0,
+            1,
+            1000,
+            5.0,
+            folder.to_string_lossy().into_owned(),
+        );
+        network_info.set_seed(2022);
+        network_info.set_trace(true);
+        let mut network: Network = Network::setup(network_info).await;
+        Utils::deposit_and_withdraw(&mut network, 1);
+
+        let start = Instant::now();
+        let virtual_start = tokio::time::Instant::now();
+        Utils::execute_and_shutdown(&mut network, Duration::from_secs(600), &folder).await;
+
+        // Every message takes a second to be delivered: the run only completes this fast
+        // because the clock is paused.
+        assert!(virtual_start.elapsed() >= Duration::from_secs(3));
+        assert!(start.elapsed() < virtual_start.elapsed());
+
+        // Each command goes to the replicas and back
+        let completions = network.completions();
+        assert_eq!(completions.len(), 6);
+        assert!(completions
+            .iter()
+            .all(|completion| completion.latency >= Duration::from_secs(1)));
+        let report = network.latency_report();
+        assert_eq!(report.overall.count, 6);
+        for action in ["Register", "Deposit", "Withdraw"] {
+            assert_eq!(report.by_action[action].count, 2);
+        }
+        assert_eq!(
+            report
+                .by_phase
+                .values()
+                .map(|summary| summary.count)
+                .sum::<usize>(),
+            6
+        );
+
+        let metrics = network.metrics();
+        // The faulty replica crashes, so only the correct ones are measured
+        let (_, _, replicas, faulty_replicas) = network.network_info().compute_ranges();
+        for replica in replicas {
+            let replica = &metrics.replicas[&replica];
+            assert!(replica.commands_received >= 6);
+            assert!(replica.broadcasts > 0 && replica.broadcast_bytes > 0);
+        }
+        for replica in faulty_replicas {
+            assert!(!metrics.replicas.contains_key(&replica));
+        }
+
+        // The trace holds the history of every command, and can be loaded back
+        let trace = network.trace();
+        for operation in network.history().operations() {
+            let command = &operation.command;
+            let history = trace.command(command.id());
+            let issuer = *command.issuer();
+            let traced = Tracer::command(command);
+            let instructed = Event::Instruction(traced.clone());
+            assert!(history
+                .iter()
+                .any(|e| e.peer == issuer && e.event == instructed));
+            assert!(history.iter().any(|e| matches!(
+                &e.event,
+                Event::Execute { command: executed, .. } if *executed == traced
+            )));
+        }
+        assert!(trace
+            .events()
+            .windows(2)
+            .all(|events| events[0].seq < events[1].seq && events[0].time <= events[1].time));
+        assert_eq!(Trace::from_file(network.trace_path()).unwrap(), trace);
+
+        // Every request appears on the timeline of its client
+        let chrome: ChromeTrace =
+            serde_json::from_reader(std::fs::File::open(network.chrome_trace_path()).unwrap())
+                .unwrap();
+        assert_eq!(
+            chrome.spans("request").count(),
+            network.history().operations().len()
+        );
+        assert!(chrome
+            .spans("request")
+            .all(|span| span.args.get("completed").is_none() && span.tid < 2));
+        assert!(chrome.spans("ACK").count() > 0);
+
+        assert!(!network.network_info().decisions().snapshot().is_empty());
+        assert_eq!(network.check_safety(), Ok(()));
+
+        let operations = network.history().operations();
+        assert_eq!(operations.len(), 6);
+        assert!(operations
+            .iter()
+            .all(|operation| operation.response.is_some()));
+        assert_eq!(network.check_linearizability(), Ok(()));
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn end_to_end_test_with_the_same_seed() {
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let mut network_info = Utils::network_info(2, 0, 0.1);
+            network_info.set_seed(2022);
+            network_info.set_transmission_delay_distribution(DelayDistribution::Poisson(10.0));
+            let mut network: Network = Network::setup(network_info).await;
+            Utils::deposit_and_withdraw(&mut network, 3);
+            Utils::execute_and_shutdown(&mut network, Duration::from_secs(600), "Seed 2022").await;
+            runs.push((
+                network.network_info().decisions().snapshot(),
+                network.metrics(),
+            ));
+        }
+
+        // In virtual time, a run only depends on its seed
+        assert!(!runs[0].0.is_empty());
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn end_to_end_test_with_every_delay_distribution() {
+        for distribution in [
+            DelayDistribution::Constant(20),
+            DelayDistribution::Poisson(20.0),
+            DelayDistribution::Uniform(1, 200),
+            DelayDistribution::Exponential(50.0),
+            DelayDistribution::LogNormal {
+                mu: 3.0,
+                sigma: 1.0,
+            },
+            DelayDistribution::Empirical(vec![1, 5, 400]),
+        ] {
+            let mut network_info = Utils::network_info(2, 0, 0.5);
+            network_info.set_transmission_delay_distribution(distribution.clone());
+            let mut network: Network = Network::setup(network_info).await;
+            Utils::deposit_and_withdraw(&mut network, 3);
+            Utils::execute_and_shutdown(&mut network, Duration::from_secs(600), &distribution)
+                .await;
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn end_to_end_test_with_geo_distributed_replicas() {
+        let mut network_info =
+            NetworkInfo::default_parameters(2, 6, 0, 1, 0, 0.5, String::from("resources/test3"));
+        // Both clients and the first two replicas are in region 0, the others are far away
+        network_info
+            .set_topology(Topology::Regions {
+                regions: vec![0, 0, 0, 0, 1, 1, 2, 2, 2],
+                rtts: vec![vec![2, 80, 150], vec![80, 2, 100], vec![150, 100, 2]],
+            })
+            .unwrap();
+        let mut network: Network = Network::setup(network_info).await;
+        network.register_all();
+        for i in 0..2 {
+            network.deposit(i, 1);
+            network.withdraw(i, 1);
+        }
+
+        let start = tokio::time::Instant::now();
+        timeout(Duration::from_secs(600), network.execute_all())
+            .await
+            .expect("Clients did not complete with geo-distributed replicas");
+        // A client needs acknowledgements from replicas of the distant regions
+        assert!(start.elapsed() >= Duration::from_millis(80));
+        network
+            .shutdown()
+            .await
+            .expect("Failed to deliver the time elapsed");
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn end_to_end_test_with_partitioned_minority() {
+        for mode in [ConsensusMode::Oracle, ConsensusMode::Pbft] {
+            let mut network_info = Utils::network_info(2, 0, 0.5);
+            network_info.set_consensus_mode(mode);
+            // The faulty replica stays up, but none of its results counts towards the fast path
+            let (_, _, replica_range, faulty_replica_range) = network_info.compute_ranges();
+            for id in faulty_replica_range.clone() {
+                network_info.set_faulty_replica_strategy(id, FaultyReplicaStrategy::WrongResults);
+            }
+            let mut network: Network = Network::setup(network_info).await;
+            // The replica #2 is cut off from the clients and the other replicas for two minutes
+            let others = (0..2)
+                .chain(replica_range.skip(1))
+                .chain(faulty_replica_range);
+            network.network_info().faults().partition(
+                vec![vec![2], others.collect()],
+                Some(Duration::from_secs(120)),
+            );
+
+            Utils::deposit_and_withdraw(&mut network, 3);
+
+            let start = tokio::time::Instant::now();
+            Utils::execute_and_shutdown(&mut network, Duration::from_secs(600), mode).await;
+            // Without the acknowledgements of the replica #2, the clients fall back to the CHK path
+            assert!(start.elapsed() < Duration::from_secs(60));
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn end_to_end_test_with_nemesis() {
+        // One honest replica can be left out of the acknowledgements
+        let network_info = NetworkInfo::new(
+            2,
+            6,
+            0,
+            1,
+            10,
+            0.5,
+            String::from("resources/test3"),
+            5,
+            false,
+        );
+        let mut network: Network = Network::setup(network_info).await;
+        let nemesis = Nemesis::new()
+            .at(Duration::from_millis(0), NemesisEvent::SlowLink(0, 4, 300))
+            .at(Duration::from_millis(100), NemesisEvent::Crash(3))
+            .at(
+                Duration::from_millis(200),
+                NemesisEvent::Partition(vec![vec![2], vec![0, 1, 3, 4, 5, 6, 7, 8]]),
+            )
+            .at(Duration::from_secs(5), NemesisEvent::Heal)
+            .at(Duration::from_secs(6), NemesisEvent::Restart(3));
+        network.set_nemesis(nemesis.clone());
+        Utils::deposit_and_withdraw(&mut network, 5);
+        Utils::execute_and_shutdown(&mut network, Duration::from_secs(600), &nemesis).await;
+
+        // The events are applied in order, until the end of the execution
+        let applied = network.applied_events();
+        assert!(applied.len() >= 3);
+        for ((time, event), (scheduled, expected)) in applied.iter().zip(nemesis.timeline()) {
+            assert_eq!(event, expected);
+            assert!(time >= scheduled);
+        }
+    }
+
     #[tokio::test]
     async fn end_to_end_test() {
         let t1 = end_to_end_test1().await;
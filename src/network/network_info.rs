@@ -1,14 +1,30 @@
 use std::{
+    collections::HashMap,
     ops::Range,
+    sync::{Arc, Mutex},
     time::{Duration, SystemTime, SystemTimeError},
 };
 
-use rand::thread_rng;
-use rand_distr::{Distribution, Poisson};
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use uuid::{Builder, Variant, Version};
+
+use crate::{
+    error::TopologyError,
+    network::{
+        delay::DelayDistribution, fault_injector::FaultInjector, metrics::Metrics,
+        safety::Decisions, topology::Topology, trace::Tracer,
+    },
+    peer::{
+        consensus::ConsensusMode,
+        handler::{FaultyClientStrategy, FaultyReplicaStrategy},
+        peer::PeerId,
+    },
+    talk::CommandId,
+};
 
 pub const DEFAULT_CONSENSUS_DURATION: f64 = 10.0;
 pub const DEFAULT_REPORT_FOLDER: &str = "resources";
-pub const MAX_TRANSMISSION_DELAY: u64 = 1000;
+pub use crate::network::delay::MAX_TRANSMISSION_DELAY;
 #[derive(Clone, Debug)]
 pub struct NetworkInfo {
     nbr_clients: usize,
@@ -18,11 +34,24 @@ pub struct NetworkInfo {
     transmission_delay: u64,
     /// in ms
     consensus_duration: f64, // in s
-    transmission_delay_distribution: Poisson<f64>, // In milliseconds
+    transmission_delay_distribution: DelayDistribution,
+    topology: Topology,
+    faults: FaultInjector,
+    metrics: Metrics,
+    tracer: Tracer,
+    decisions: Decisions,
     n_ack: usize,
     report_folder: String,
     creation: SystemTime,
     write_logs: bool,
+    consensus_mode: ConsensusMode,
+    faulty_replica_strategies: HashMap<PeerId, FaultyReplicaStrategy>,
+    faulty_client_strategies: HashMap<PeerId, FaultyClientStrategy>,
+    /// Every random decision of the simulation is drawn from `rng`, seeded with `seed`.
+    /// The tasks of the network share `rng`, so that the draws are only replayed if the tasks run
+    /// in the same order: on a `current_thread` runtime, with the clock paused.
+    seed: u64,
+    rng: Arc<Mutex<StdRng>>,
 }
 
 impl NetworkInfo {
@@ -42,6 +71,7 @@ impl NetworkInfo {
             "Network does not satisfy the resilience condition"
         );
         assert!(consensus_duration >= 0.0);
+        let seed = thread_rng().gen();
         Self {
             nbr_clients,
             nbr_replicas,
@@ -49,12 +79,21 @@ impl NetworkInfo {
             nbr_faulty_replicas,
             transmission_delay,
             consensus_duration,
-            transmission_delay_distribution: Poisson::new(transmission_delay as f64)
-                .unwrap_or(Poisson::new(1.0).unwrap()),
+            transmission_delay_distribution: Self::default_delay_distribution(transmission_delay),
+            topology: Topology::Uniform,
+            faults: FaultInjector::new(),
+            metrics: Metrics::new(),
+            tracer: Tracer::new(false),
+            decisions: Decisions::new(),
             n_ack,
             report_folder,
             creation: SystemTime::now(),
             write_logs,
+            consensus_mode: ConsensusMode::Oracle,
+            faulty_replica_strategies: HashMap::new(),
+            faulty_client_strategies: HashMap::new(),
+            seed,
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
         }
     }
 
@@ -66,6 +105,7 @@ impl NetworkInfo {
         transmission_delay: u64,
         n_ack: usize,
     ) -> Self {
+        let seed = thread_rng().gen();
         Self {
             nbr_clients,
             nbr_replicas,
@@ -73,12 +113,21 @@ impl NetworkInfo {
             nbr_faulty_replicas,
             transmission_delay,
             consensus_duration: DEFAULT_CONSENSUS_DURATION,
-            transmission_delay_distribution: Poisson::new(transmission_delay as f64)
-                .unwrap_or(Poisson::new(1.0).unwrap()),
+            transmission_delay_distribution: Self::default_delay_distribution(transmission_delay),
+            topology: Topology::Uniform,
+            faults: FaultInjector::new(),
+            metrics: Metrics::new(),
+            tracer: Tracer::new(false),
+            decisions: Decisions::new(),
             n_ack,
             report_folder: String::from(DEFAULT_REPORT_FOLDER),
             creation: SystemTime::now(),
             write_logs: false,
+            consensus_mode: ConsensusMode::Oracle,
+            faulty_replica_strategies: HashMap::new(),
+            faulty_client_strategies: HashMap::new(),
+            seed,
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
         }
     }
 
@@ -141,6 +190,12 @@ impl NetworkInfo {
         self.nbr_faulty_clients
     }
 
+    /// Id of the coordinator of the `Oracle` and `Simulated` consensus in the traces,
+    /// which follows the ids of the peers
+    pub fn coordinator_id(&self) -> PeerId {
+        self.size()
+    }
+
     pub fn nbr_faulty_replicas(&self) -> usize {
         self.nbr_faulty_replicas
     }
@@ -164,6 +219,92 @@ impl NetworkInfo {
         self.write_logs = value;
     }
 
+    pub fn consensus_mode(&self) -> ConsensusMode {
+        self.consensus_mode
+    }
+    pub fn set_consensus_mode(&mut self, mode: ConsensusMode) {
+        self.consensus_mode = mode;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Reseeds the random number generator shared by every clone of this `NetworkInfo`.
+    /// Running the same network twice with the same seed, on a `current_thread` runtime with the
+    /// clock paused, replays the same random decisions.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        *self.rng.lock().unwrap() = StdRng::seed_from_u64(seed);
+    }
+
+    /// Returns a copy of this `NetworkInfo` with its own random number generator, reseeded with
+    /// the same seed, its own fault-injection layer, with the same faults, and its own metrics,
+    /// trace and decisions: a network set up with the copy replays the same random decisions,
+    /// independently of this one.
+    pub fn fork(&self) -> Self {
+        let mut info = self.clone();
+        info.rng = Arc::new(Mutex::new(StdRng::seed_from_u64(self.seed)));
+        info.faults = self.faults.fork();
+        info.metrics = Metrics::new();
+        info.tracer = Tracer::new(self.trace());
+        info.decisions = Decisions::new();
+        info
+    }
+
+    /// Draws a value from the seeded random number generator of the network
+    pub fn with_rng<R, F: FnOnce(&mut StdRng) -> R>(&self, f: F) -> R {
+        f(&mut self.rng.lock().unwrap())
+    }
+
+    /// Generates a random (v4) command identifier from the seeded random number generator
+    pub fn generate_command_id(&self) -> CommandId {
+        let bytes = self.with_rng(|rng| rng.gen());
+        Builder::from_bytes(bytes)
+            .set_variant(Variant::RFC4122)
+            .set_version(Version::Random)
+            .build()
+    }
+
+    /// Returns the strategy of the faulty replica #`id`. Faulty replicas crash by default.
+    pub fn faulty_replica_strategy(&self, id: &PeerId) -> FaultyReplicaStrategy {
+        self.faulty_replica_strategies
+            .get(id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_faulty_replica_strategy(&mut self, id: PeerId, strategy: FaultyReplicaStrategy) {
+        self.faulty_replica_strategies.insert(id, strategy);
+    }
+
+    /// Returns the strategy of the faulty client #`id`. Faulty clients are idle by default.
+    pub fn faulty_client_strategy(&self, id: &PeerId) -> FaultyClientStrategy {
+        self.faulty_client_strategies
+            .get(id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_faulty_client_strategy(&mut self, id: PeerId, strategy: FaultyClientStrategy) {
+        self.faulty_client_strategies.insert(id, strategy);
+    }
+
+    /// Sets the same strategy for every faulty client
+    pub fn set_faulty_clients_strategy(&mut self, strategy: FaultyClientStrategy) {
+        let (_, faulty_client_range, _, _) = self.compute_ranges();
+        for id in faulty_client_range {
+            self.set_faulty_client_strategy(id, strategy.clone());
+        }
+    }
+
+    /// Sets the same strategy for every faulty replica
+    pub fn set_faulty_replicas_strategy(&mut self, strategy: FaultyReplicaStrategy) {
+        let (_, _, _, faulty_replica_range) = self.compute_ranges();
+        for id in faulty_replica_range {
+            self.set_faulty_replica_strategy(id, strategy.clone());
+        }
+    }
+
     pub fn compute_ranges(&self) -> (Range<usize>, Range<usize>, Range<usize>, Range<usize>) {
         let client_start: usize = 0;
         let client_end: usize = self.nbr_clients();
@@ -182,19 +323,85 @@ impl NetworkInfo {
         )
     }
 
+    /// Messages are delayed following a Poisson distribution centered on `transmission_delay`
+    fn default_delay_distribution(transmission_delay: u64) -> DelayDistribution {
+        if transmission_delay == 0 {
+            DelayDistribution::Constant(0)
+        } else {
+            DelayDistribution::Poisson(transmission_delay as f64)
+        }
+    }
+
+    /// Mean transmission delay, in ms
     pub fn transmission_delay(&self) -> u64 {
         self.transmission_delay
     }
+
+    pub fn transmission_delay_distribution(&self) -> &DelayDistribution {
+        &self.transmission_delay_distribution
+    }
+    /// Sets the distribution of the delay of every message.
+    /// `transmission_delay` is updated to the mean of the distribution.
+    pub fn set_transmission_delay_distribution(&mut self, distribution: DelayDistribution) {
+        self.transmission_delay = distribution.mean().round() as u64;
+        self.transmission_delay_distribution = distribution;
+    }
+
+    /// Samples the delay of a message, in ms, from the seeded random number generator
     pub fn sample_transmission_delay(&self) -> u64 {
-        if self.transmission_delay == 0 {
-            return 0;
-        }
-        let mut rng = thread_rng();
-        let mut delay = self.transmission_delay_distribution.sample(&mut rng) as u64;
-        while delay > MAX_TRANSMISSION_DELAY {
-            delay = self.transmission_delay_distribution.sample(&mut rng) as u64;
-        }
-        delay
+        let distribution = &self.transmission_delay_distribution;
+        self.with_rng(|rng| distribution.sample(rng))
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+    /// Returns an error if the topology does not describe every link of the network
+    pub fn set_topology(&mut self, topology: Topology) -> Result<(), TopologyError> {
+        topology.validate(self.size())?;
+        self.topology = topology;
+        Ok(())
+    }
+
+    /// Returns the fault-injection layer shared by every peer of the network
+    pub fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
+
+    /// Returns the metrics registry shared by every peer of the network
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn trace(&self) -> bool {
+        self.tracer.enabled()
+    }
+    /// If set, every peer records its events in the tracer of the network,
+    /// and the network writes them to `<report folder>/trace_<seed>.jsonl` when it shuts down
+    pub fn set_trace(&mut self, value: bool) {
+        self.tracer = Tracer::new(value);
+    }
+
+    /// Returns the tracer shared by every peer of the network
+    pub fn tracer(&self) -> &Tracer {
+        &self.tracer
+    }
+
+    /// Returns the decisions applied by the replicas of the network, see `Decisions::check`
+    pub fn decisions(&self) -> &Decisions {
+        &self.decisions
+    }
+
+    /// Samples the delay of a message sent from `from` to `to`, in ms:
+    /// the latency of the link in the topology plus a sampled transmission delay
+    pub fn sample_link_delay(&self, from: PeerId, to: PeerId) -> u64 {
+        self.topology.latency(from, to) + self.sample_transmission_delay()
+    }
+
+    /// Delay of a message sent on the slowest link, in ms: its latency in the topology plus the
+    /// 99th percentile of the transmission delay. Injected faults are ignored.
+    pub fn max_link_delay(&self) -> u64 {
+        self.topology.max_latency() + self.transmission_delay_distribution.p99()
     }
 
     pub fn consensus_transmission_delay(&self) -> f64 {
@@ -207,6 +414,8 @@ impl NetworkInfo {
 
 #[cfg(test)]
 mod tests {
+    use crate::network::fault_injector::Fate;
+
     use super::*;
 
     #[test]
@@ -232,9 +441,71 @@ mod tests {
         assert!(!faulty_replica_range.contains(&14));
     }
 
+    #[test]
+    fn same_seed_replays_the_same_decisions() {
+        let mut first = NetworkInfo::with_default_report_folder(2, 4, 0, 0, 50, 4);
+        let mut second = NetworkInfo::with_default_report_folder(2, 4, 0, 0, 50, 4);
+        first.set_seed(42);
+        second.set_seed(42);
+
+        let draw = |info: &NetworkInfo| {
+            let delays: Vec<u64> = (0..20).map(|_| info.sample_transmission_delay()).collect();
+            let ids: Vec<CommandId> = (0..20).map(|_| info.generate_command_id()).collect();
+            (delays, ids)
+        };
+        assert_eq!(draw(&first), draw(&second));
+
+        second.set_seed(43);
+        first.set_seed(42);
+        assert_ne!(draw(&first), draw(&second));
+    }
+
+    #[test]
+    fn clones_share_the_seeded_rng() {
+        let mut info = NetworkInfo::with_default_report_folder(2, 4, 0, 0, 50, 4);
+        info.set_seed(7);
+        let clone = info.clone();
+        let ids = [info.generate_command_id(), clone.generate_command_id()];
+
+        info.set_seed(7);
+        assert_eq!(ids[0], info.generate_command_id());
+        assert_eq!(ids[1], info.generate_command_id());
+        assert_eq!(ids[0].get_version(), Some(Version::Random));
+    }
+
+    #[test]
+    fn forks_keep_the_faults_independently() {
+        let mut info = NetworkInfo::with_default_report_folder(2, 4, 0, 0, 50, 4);
+        info.set_seed(7);
+        info.faults().set_drop_probability(1.0);
+        info.faults().slow_link(0, 2, 100);
+        let fork = info.fork();
+        assert_eq!(fork.faults().fate(&mut StdRng::seed_from_u64(0)), Fate::Lost);
+        assert_eq!(fork.faults().extra_delay(2, 0), 100);
+        assert_eq!(fork.generate_command_id(), info.generate_command_id());
+
+        fork.faults().set_drop_probability(0.0);
+        assert_eq!(info.faults().fate(&mut StdRng::seed_from_u64(0)), Fate::Lost);
+    }
+
+    #[test]
+    fn link_delay_adds_topology_latency() {
+        let mut info = NetworkInfo::with_default_report_folder(1, 2, 0, 0, 0, 2);
+        info.set_topology(Topology::Regions {
+            regions: vec![0, 0, 1],
+            rtts: vec![vec![0, 100], vec![100, 0]],
+        })
+        .unwrap();
+        assert_eq!(info.sample_link_delay(0, 1), 0);
+        assert_eq!(info.sample_link_delay(0, 2), 50);
+
+        info.set_transmission_delay_distribution(DelayDistribution::Constant(5));
+        assert_eq!(info.sample_link_delay(2, 1), 55);
+    }
+
     #[test]
     fn poisson() {
-        let distr = Poisson::new(2.0).unwrap();
+        let distr = DelayDistribution::Poisson(2.0);
         for _ in 0..10 {
             let delay = distr.sample(&mut thread_rng());
             println!("{:?}", delay);
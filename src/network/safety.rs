@@ -0,0 +1,425 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    peer::peer::PeerId,
+    talk::{CommandId, RoundNumber},
+};
+
+/// What a replica delivered when it applied the decision of a round
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    /// Commands of the NCSet delivered in the round, in the order of delivery
+    pub nc_set: Vec<CommandId>,
+    /// Commands of the CSet delivered in the round, in the order of execution
+    pub c_set: Vec<CommandId>,
+    /// Serialized state of the application once the round is delivered
+    pub state: Value,
+}
+
+impl Decision {
+    fn delivered(&self) -> BTreeSet<&CommandId> {
+        self.nc_set.iter().chain(self.c_set.iter()).collect()
+    }
+}
+
+/// What a replica delivered and executed when the network shut down
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FinalState {
+    /// Round the replica was running
+    pub round: RoundNumber,
+    /// Commands delivered by the decisions of the previous rounds
+    pub delivered: BTreeSet<CommandId>,
+    /// Commands executed on the fast path and not delivered yet
+    pub pending: BTreeSet<CommandId>,
+    /// Serialized state of the application
+    pub state: Value,
+}
+
+/// Decisions applied by every replica of the network, indexed by replica and round, and the
+/// state every replica ended in
+#[derive(Clone, Debug, Default)]
+pub struct Decisions {
+    decisions: Arc<Mutex<BTreeMap<PeerId, BTreeMap<RoundNumber, Decision>>>>,
+    finals: Arc<Mutex<BTreeMap<PeerId, FinalState>>>,
+}
+
+impl Decisions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, replica: PeerId, round: RoundNumber, decision: Decision) {
+        self.decisions
+            .lock()
+            .unwrap()
+            .entry(replica)
+            .or_default()
+            .insert(round, decision);
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<PeerId, BTreeMap<RoundNumber, Decision>> {
+        self.decisions.lock().unwrap().clone()
+    }
+
+    pub fn record_final(&self, replica: PeerId, state: FinalState) {
+        self.finals.lock().unwrap().insert(replica, state);
+    }
+
+    pub fn finals(&self) -> BTreeMap<PeerId, FinalState> {
+        self.finals.lock().unwrap().clone()
+    }
+
+    /// Checks that the given correct replicas agree on every round they both decided:
+    /// they deliver the same commands, execute the CSet in the same order and end up with the
+    /// same application state. Replicas may decide a different number of rounds, e.g. if they lag behind.
+    /// Returns the divergence of the earliest round, if any.
+    pub fn check(&self, replicas: Range<PeerId>) -> Result<(), Divergence> {
+        let decisions = self.snapshot();
+        let mut rounds: BTreeMap<RoundNumber, Vec<(PeerId, &Decision)>> = BTreeMap::new();
+        for (replica, decided) in decisions.iter() {
+            if replicas.contains(replica) {
+                for (round, decision) in decided.iter() {
+                    rounds.entry(*round).or_default().push((*replica, decision));
+                }
+            }
+        }
+
+        for (round, decisions) in rounds {
+            let (reference, expected) = decisions[0];
+            for (replica, decision) in decisions.into_iter().skip(1) {
+                if let Some(kind) = Self::compare(expected, decision) {
+                    return Err(Divergence {
+                        round,
+                        replicas: (reference, replica),
+                        kind,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the given correct replicas agree on the state they ended in: replicas running
+    /// the same round delivered the same commands and, if they also executed the same commands on
+    /// the fast path, whose order does not matter, they have the same application state.
+    /// The divergence is reported in the round the replicas were running.
+    pub fn check_final(&self, replicas: Range<PeerId>) -> Result<(), Divergence> {
+        let finals = self.finals();
+        let mut rounds: BTreeMap<RoundNumber, Vec<(PeerId, &FinalState)>> = BTreeMap::new();
+        for (replica, state) in finals.iter() {
+            if replicas.contains(replica) {
+                rounds.entry(state.round).or_default().push((*replica, state));
+            }
+        }
+
+        for (round, states) in rounds {
+            let (reference, expected) = states[0];
+            for (replica, state) in states.iter().skip(1) {
+                let delivered = &expected.delivered;
+                if let Some(command) = delivered.symmetric_difference(&state.delivered).next() {
+                    return Err(Divergence {
+                        round,
+                        replicas: (reference, *replica),
+                        kind: DivergenceKind::Delivered(*command),
+                    });
+                }
+            }
+            // Each replica is compared with the first one that executed the same commands
+            for (index, (replica, state)) in states.iter().enumerate() {
+                let reference = states[..index]
+                    .iter()
+                    .find(|(_, other)| other.pending == state.pending);
+                if let Some((reference, expected)) = reference {
+                    if let Some(kind) =
+                        Self::difference(&expected.state, &state.state, String::new())
+                    {
+                        return Err(Divergence {
+                            round,
+                            replicas: (*reference, *replica),
+                            kind,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the first difference between the decisions of a round
+    fn compare(expected: &Decision, actual: &Decision) -> Option<DivergenceKind> {
+        let (delivered, other) = (expected.delivered(), actual.delivered());
+        if let Some(command) = delivered.symmetric_difference(&other).next() {
+            return Some(DivergenceKind::Delivered(**command));
+        }
+        let order = expected.c_set.iter().zip(actual.c_set.iter());
+        if let Some((position, (command, _))) = order.enumerate().find(|(_, (a, b))| a != b) {
+            return Some(DivergenceKind::Order {
+                position,
+                command: *command,
+            });
+        }
+        Self::difference(&expected.state, &actual.state, String::new())
+    }
+
+    /// Returns the first entry of the states that differs, e.g. the balance of a client.
+    /// Objects and arrays are compared entry by entry, and their entries are named by their path.
+    fn difference(expected: &Value, actual: &Value, entry: String) -> Option<DivergenceKind> {
+        let path = |key: &dyn Display| match entry.as_str() {
+            "" => key.to_string(),
+            _ => format!("{}.{}", entry, key),
+        };
+        match (expected, actual) {
+            (Value::Object(expected), Value::Object(actual)) => {
+                let keys: BTreeSet<&String> = expected.keys().chain(actual.keys()).collect();
+                keys.into_iter()
+                    .find_map(|key| match (expected.get(key), actual.get(key)) {
+                        (Some(x), Some(y)) => Self::difference(x, y, path(key)),
+                        (x, y) => Some(DivergenceKind::State {
+                            entry: path(key),
+                            values: (x.cloned(), y.cloned()),
+                        }),
+                    })
+            }
+            (Value::Array(x), Value::Array(y)) if x.len() == y.len() => x
+                .iter()
+                .zip(y.iter())
+                .enumerate()
+                .find_map(|(index, (x, y))| Self::difference(x, y, path(&index))),
+            (x, y) if x == y => None,
+            (x, y) => Some(DivergenceKind::State {
+                entry,
+                values: (Some(x.clone()), Some(y.clone())),
+            }),
+        }
+    }
+}
+
+/// How two correct replicas disagree on a round
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DivergenceKind {
+    /// Only one of the replicas delivers the command in the round
+    Delivered(CommandId),
+    /// The replicas execute the CSet in a different order, from the command at `position`
+    Order { position: usize, command: CommandId },
+    /// An entry of the state of the application differs once the round is delivered, e.g. the
+    /// balance of a client. `None` if only one of the replicas has the entry.
+    State {
+        entry: String,
+        values: (Option<Value>, Option<Value>),
+    },
+}
+
+/// Safety violation: two correct replicas disagree on a round
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    pub round: RoundNumber,
+    pub replicas: (PeerId, PeerId),
+    pub kind: DivergenceKind,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (a, b) = self.replicas;
+        write!(
+            f,
+            "replicas #{} and #{} diverge in round {}: ",
+            a, b, self.round
+        )?;
+        match &self.kind {
+            DivergenceKind::Delivered(command) => {
+                write!(f, "command {} is delivered by only one of them", command)
+            }
+            DivergenceKind::Order { position, command } => write!(
+                f,
+                "the CSet is executed in a different order from command {} (position {})",
+                command, position
+            ),
+            DivergenceKind::State { entry, values } => {
+                let value = |value: &Option<Value>| match value {
+                    Some(value) => value.to_string(),
+                    None => String::from("missing"),
+                };
+                write!(
+                    f,
+                    "the entry `{}` of the state is {} and {}",
+                    entry,
+                    value(&values.0),
+                    value(&values.1)
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{application::Application, banking::banking::Banking, network::Tracer};
+
+    use super::*;
+
+    fn decision(nc_set: &[CommandId], c_set: &[CommandId], balance: u64) -> Decision {
+        let mut banking = Banking::default();
+        banking.restore(vec![(0, balance)].into_iter().collect());
+        Decision {
+            nc_set: nc_set.to_vec(),
+            c_set: c_set.to_vec(),
+            state: Tracer::value(&banking.snapshot()),
+        }
+    }
+
+    #[test]
+    fn agreeing_replicas_pass_the_check() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let decisions = Decisions::new();
+        for replica in 2..5 {
+            decisions.record(replica, 1, decision(&[a], &[b, c], 10));
+        }
+        // A lagging replica is only compared on the rounds it decided
+        for replica in 2..4 {
+            decisions.record(replica, 2, decision(&[], &[c], 5));
+        }
+        // Faulty replicas are not checked
+        decisions.record(5, 1, decision(&[], &[], 0));
+        assert_eq!(decisions.check(2..5), Ok(()));
+        assert!(decisions.check(2..6).is_err());
+        assert_eq!(Decisions::new().check(2..5), Ok(()));
+    }
+
+    #[test]
+    fn reports_the_first_divergent_round() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let decisions = Decisions::new();
+        decisions.record(2, 1, decision(&[a], &[b, c], 10));
+        decisions.record(2, 2, decision(&[], &[], 10));
+        decisions.record(3, 1, decision(&[a], &[b, c], 10));
+        decisions.record(3, 2, decision(&[], &[], 7));
+        decisions.record(4, 1, decision(&[a], &[c, b], 10));
+        decisions.record(4, 2, decision(&[c], &[], 10));
+
+        let divergence = decisions.check(2..5).unwrap_err();
+        assert_eq!(divergence.round, 1);
+        assert_eq!(divergence.replicas, (2, 4));
+        assert_eq!(
+            divergence.kind,
+            DivergenceKind::Order {
+                position: 0,
+                command: b
+            }
+        );
+
+        let divergence = decisions.check(2..4).unwrap_err();
+        assert_eq!(
+            (divergence.round, divergence.kind),
+            (
+                2,
+                DivergenceKind::State {
+                    entry: String::from("0"),
+                    values: (Some(Value::from(10)), Some(Value::from(7)))
+                }
+            )
+        );
+
+        let decisions = Decisions::new();
+        decisions.record(2, 1, decision(&[a], &[b, c], 10));
+        decisions.record(5, 1, decision(&[], &[b, c], 10));
+        assert_eq!(
+            decisions.check(2..6).unwrap_err().kind,
+            DivergenceKind::Delivered(a)
+        );
+    }
+
+    fn final_state(round: RoundNumber, pending: &[CommandId], balance: u64) -> FinalState {
+        FinalState {
+            round,
+            delivered: BTreeSet::new(),
+            pending: pending.iter().cloned().collect(),
+            state: decision(&[], &[], balance).state,
+        }
+    }
+
+    #[test]
+    fn replicas_diverging_on_the_fast_path_fail_the_final_check() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let decisions = Decisions::new();
+        // No round goes through the consensus: only the final states can be compared
+        decisions.record_final(2, final_state(1, &[a, b], 10));
+        decisions.record_final(3, final_state(1, &[a, b], 10));
+        decisions.record_final(4, final_state(1, &[a, b], 7));
+        // A replica that did not execute every command is not compared
+        decisions.record_final(5, final_state(1, &[a], 0));
+        assert_eq!(decisions.check(2..6), Ok(()));
+        assert_eq!(decisions.check_final(2..4), Ok(()));
+        assert_eq!(
+            decisions.check_final(2..6),
+            Err(Divergence {
+                round: 1,
+                replicas: (2, 4),
+                kind: DivergenceKind::State {
+                    entry: String::from("0"),
+                    values: (Some(Value::from(10)), Some(Value::from(7)))
+                }
+            })
+        );
+        assert_eq!(decisions.check_final(3..4), Ok(()));
+        assert_eq!(decisions.check_final(5..6), Ok(()));
+
+        // Replicas running the same round delivered the same commands
+        let decisions = Decisions::new();
+        decisions.record_final(2, final_state(2, &[], 10));
+        let mut ahead = final_state(2, &[], 10);
+        ahead.delivered.insert(a);
+        decisions.record_final(3, ahead);
+        decisions.record_final(4, final_state(1, &[b], 0));
+        assert_eq!(
+            decisions.check_final(2..5).unwrap_err().kind,
+            DivergenceKind::Delivered(a)
+        );
+    }
+
+    #[test]
+    fn reports_the_first_differing_entry_of_the_state() {
+        let state = |value: Value| Decision {
+            state: value,
+            ..Decision::default()
+        };
+        let decisions = Decisions::new();
+        decisions.record(
+            2,
+            1,
+            state(serde_json::json!({ "a": [1, 2], "b": { "c": 3 } })),
+        );
+        decisions.record(3, 1, state(serde_json::json!({ "a": [1, 4], "b": {} })));
+        let divergence = decisions.check(2..4).unwrap_err();
+        assert_eq!(
+            divergence.kind,
+            DivergenceKind::State {
+                entry: String::from("a.1"),
+                values: (Some(Value::from(2)), Some(Value::from(4)))
+            }
+        );
+        assert!(divergence
+            .to_string()
+            .contains("`a.1` of the state is 2 and 4"));
+
+        let decisions = Decisions::new();
+        decisions.record(2, 1, state(serde_json::json!({ "b": { "c": 3 } })));
+        decisions.record(3, 1, state(serde_json::json!({ "b": {} })));
+        assert_eq!(
+            decisions.check(2..4).unwrap_err().kind,
+            DivergenceKind::State {
+                entry: String::from("b.c"),
+                values: (Some(Value::from(3)), None)
+            }
+        );
+    }
+}
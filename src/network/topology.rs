@@ -0,0 +1,174 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::TopologyError, peer::peer::PeerId};
+
+/// Latency of the links between the peers of the network.
+/// The latency of a link is added to the transmission delay sampled for each message,
+/// which then models the jitter of the link.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Topology {
+    /// Every link has the same, zero, latency
+    #[default]
+    Uniform,
+    /// `matrix[from][to]` is the one-way latency from peer `from` to peer `to`, in ms
+    Matrix(Vec<Vec<u64>>),
+    /// Peer `id` is located in region `regions[id]`, and `rtts[a][b]` is the round-trip time
+    /// between the regions `a` and `b`, in ms
+    Regions {
+        regions: Vec<usize>,
+        rtts: Vec<Vec<u64>>,
+    },
+}
+
+impl Topology {
+    /// Checks that the topology describes every link of a network of `size` peers
+    pub fn validate(&self, size: usize) -> Result<(), TopologyError> {
+        match self {
+            Topology::Uniform => Ok(()),
+            Topology::Matrix(matrix) => {
+                if matrix.len() != size || matrix.iter().any(|row| row.len() != size) {
+                    return Err(TopologyError::MatrixSize { size });
+                }
+                Ok(())
+            }
+            Topology::Regions { regions, rtts } => {
+                if regions.len() != size {
+                    return Err(TopologyError::RegionCount {
+                        regions: regions.len(),
+                        size,
+                    });
+                }
+                if rtts.iter().any(|row| row.len() != rtts.len()) {
+                    return Err(TopologyError::RttsSize);
+                }
+                match regions.iter().position(|region| *region >= rtts.len()) {
+                    Some(peer) => Err(TopologyError::UnknownRegion {
+                        peer,
+                        region: regions[peer],
+                    }),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// One-way latency of the link from `from` to `to`, in ms.
+    /// Links that the topology does not describe have no latency.
+    pub fn latency(&self, from: PeerId, to: PeerId) -> u64 {
+        match self {
+            Topology::Uniform => 0,
+            Topology::Matrix(matrix) => Self::lookup(matrix, from, to),
+            Topology::Regions { regions, rtts } => match (regions.get(from), regions.get(to)) {
+                (Some(a), Some(b)) => Self::lookup(rtts, *a, *b) / 2,
+                _ => 0,
+            },
+        }
+    }
+
+    /// Largest one-way latency of a link of the topology, in ms
+    pub fn max_latency(&self) -> u64 {
+        let max = |matrix: &[Vec<u64>]| matrix.iter().flatten().cloned().max().unwrap_or(0);
+        match self {
+            Topology::Uniform => 0,
+            Topology::Matrix(matrix) => max(matrix),
+            Topology::Regions { rtts, .. } => max(rtts) / 2,
+        }
+    }
+
+    fn lookup(matrix: &[Vec<u64>], row: usize, column: usize) -> u64 {
+        matrix
+            .get(row)
+            .and_then(|row| row.get(column))
+            .cloned()
+            .unwrap_or(0)
+    }
+}
+
+impl Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topology::Uniform => write!(f, "uniform"),
+            Topology::Matrix(matrix) => {
+                write!(f, "latency matrix (ms, one-way)")?;
+                for (from, row) in matrix.iter().enumerate() {
+                    write!(f, "\n\t\t\t {}: {:?}", from, row)?;
+                }
+                Ok(())
+            }
+            Topology::Regions { regions, rtts } => {
+                write!(f, "regions {:?} with RTTs (ms)", regions)?;
+                for (region, row) in rtts.iter().enumerate() {
+                    write!(f, "\n\t\t\t {}: {:?}", region, row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_of_links() {
+        let matrix = Topology::Matrix(vec![vec![0, 10], vec![20, 0]]);
+        assert_eq!(matrix.latency(0, 1), 10);
+        assert_eq!(matrix.latency(1, 0), 20);
+        assert_eq!(matrix.latency(0, 5), 0);
+
+        let regions = Topology::Regions {
+            regions: vec![0, 0, 1, 2],
+            rtts: vec![vec![2, 80, 150], vec![80, 2, 100], vec![150, 100, 2]],
+        };
+        assert_eq!(regions.latency(0, 1), 1);
+        assert_eq!(regions.latency(0, 2), 40);
+        assert_eq!(regions.latency(3, 1), 75);
+        assert_eq!(regions.latency(3, 4), 0);
+
+        assert_eq!(Topology::Uniform.latency(0, 1), 0);
+
+        assert_eq!(matrix.max_latency(), 20);
+        assert_eq!(regions.max_latency(), 75);
+        assert_eq!(Topology::Uniform.max_latency(), 0);
+    }
+
+    #[test]
+    fn topologies_must_fit_the_network() {
+        let matrix = Topology::Matrix(vec![vec![0, 10], vec![20, 0]]);
+        assert_eq!(matrix.validate(2), Ok(()));
+        assert_eq!(
+            matrix.validate(3),
+            Err(TopologyError::MatrixSize { size: 3 })
+        );
+        let ragged = Topology::Matrix(vec![vec![0, 10], vec![20]]);
+        assert_eq!(
+            ragged.validate(2),
+            Err(TopologyError::MatrixSize { size: 2 })
+        );
+
+        let rtts = vec![vec![2, 80], vec![80, 2]];
+        let regions =
+            |regions: Vec<usize>, rtts: Vec<Vec<u64>>| Topology::Regions { regions, rtts };
+        assert_eq!(regions(vec![0, 1, 1], rtts.clone()).validate(3), Ok(()));
+        assert_eq!(
+            regions(vec![0, 1], rtts.clone()).validate(3),
+            Err(TopologyError::RegionCount {
+                regions: 2,
+                size: 3
+            })
+        );
+        assert_eq!(
+            regions(vec![0, 2, 1], rtts).validate(3),
+            Err(TopologyError::UnknownRegion { peer: 1, region: 2 })
+        );
+        assert_eq!(
+            regions(vec![0, 0], vec![vec![2, 80]]).validate(2),
+            Err(TopologyError::RttsSize)
+        );
+
+        assert_eq!(Topology::Uniform.validate(4), Ok(()));
+    }
+}
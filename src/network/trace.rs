@@ -0,0 +1,369 @@
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+
+use crate::{
+    application::Operation,
+    database::replica_database::Set,
+    peer::peer::PeerId,
+    talk::{Command, CommandId, Phase, RoundNumber},
+};
+
+/// Command of the trace, whose operation is serialized so that traces do not depend on the
+/// replicated application
+pub type TracedCommand = Command<Value>;
+
+/// What happened to a peer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// The peer sends a message, which the network carries for `delay` ms
+    Send {
+        to: Option<PeerId>,
+        delay: u64,
+        message: Value,
+    },
+    /// A copy of a message reaches the peer
+    Receive {
+        from: PeerId,
+        message: Value,
+    },
+    /// The network loses a message sent by the peer
+    Lost {
+        to: Option<PeerId>,
+        message: Value,
+    },
+    /// The network instructs the client to issue the command
+    Instruction(TracedCommand),
+    /// The client gathers a quorum of acknowledgements for the command
+    Complete {
+        command: CommandId,
+        round: RoundNumber,
+        phase: Phase,
+    },
+    Shutdown,
+    /// The replica executes the command, speculatively or after a decision
+    Execute {
+        command: TracedCommand,
+        result: Value,
+    },
+    /// The replica undoes the speculative execution of the command
+    Rollback(TracedCommand),
+    /// The replica proposes its (NCSet, CSet) to the consensus, starting `Phase::CHK`
+    Propose {
+        nc_set: Vec<TracedCommand>,
+        c_set: Vec<TracedCommand>,
+    },
+    /// The replica applies the decision of the consensus
+    Decide {
+        nc_set: Vec<TracedCommand>,
+        c_set: Vec<TracedCommand>,
+    },
+    /// The replica enters the round
+    NewRound(RoundNumber),
+    /// The coordinator receives a proposal for the round
+    Gather(RoundNumber),
+    /// The coordinator validates the round
+    Validate(RoundNumber),
+}
+
+/// Event of the trace of a run
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    /// Logical time: an event happened before the events with a greater `seq`
+    pub seq: u64,
+    /// Physical time, since the first event of the trace
+    pub time: Duration,
+    pub peer: PeerId,
+    /// Round of the replica when the event happened, `None` for the other peers
+    pub round: Option<RoundNumber>,
+    pub event: Event,
+}
+
+#[derive(Debug, Default)]
+struct Recorder {
+    start: Option<Instant>,
+    events: Vec<TraceEvent>,
+}
+
+/// Records the events of every peer of the network, if enabled
+#[derive(Clone, Debug, Default)]
+pub struct Tracer {
+    enabled: bool,
+    recorder: Arc<Mutex<Recorder>>,
+}
+
+impl Tracer {
+    pub fn new(enabled: bool) -> Self {
+        Tracer {
+            enabled,
+            recorder: Arc::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Records the event of `peer`. The event is only built if the tracer is enabled.
+    pub fn record<F: FnOnce() -> Event>(&self, peer: PeerId, round: Option<RoundNumber>, event: F) {
+        if !self.enabled {
+            return;
+        }
+        let event = event();
+        let mut recorder = self.recorder.lock().unwrap();
+        let now = Instant::now();
+        let time = now - *recorder.start.get_or_insert(now);
+        let seq = recorder.events.len() as u64;
+        recorder.events.push(TraceEvent {
+            seq,
+            time,
+            peer,
+            round,
+            event,
+        });
+    }
+
+    /// Serializes a message, a result or a state of the trace
+    pub fn value<T: Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap_or(Value::Null)
+    }
+
+    pub fn command<O: Operation>(command: &Command<O>) -> TracedCommand {
+        command.map(Self::value)
+    }
+
+    /// Commands of the set, in order
+    pub fn set<O: Operation>(set: &Set<O>) -> Vec<TracedCommand> {
+        set.iter().map(Self::command).collect()
+    }
+
+    /// Name of the operation of a traced command, regardless of its arguments: the variant of
+    /// an operation serialized as an enum, e.g. `Deposit` for `{"Deposit": 10}`
+    pub fn kind(command: &TracedCommand) -> &str {
+        match command.action() {
+            Value::String(variant) => variant,
+            Value::Object(fields) if fields.len() == 1 => fields.keys().next().unwrap(),
+            _ => "Command",
+        }
+    }
+
+    /// Returns the events recorded so far
+    pub fn trace(&self) -> Trace {
+        Trace::new(self.recorder.lock().unwrap().events.clone())
+    }
+
+    /// Returns the events recorded after the first `seq` ones
+    pub fn events_since(&self, seq: usize) -> Vec<TraceEvent> {
+        let recorder = self.recorder.lock().unwrap();
+        recorder
+            .events
+            .get(seq..)
+            .map(<[_]>::to_vec)
+            .unwrap_or_default()
+    }
+}
+
+/// Events of a run, in the order they happened
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    pub fn new(events: Vec<TraceEvent>) -> Self {
+        Trace { events }
+    }
+
+    /// Loads a trace written by `write`: one JSON event per line
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut events = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = serde_json::from_str(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            events.push(event);
+        }
+        Ok(Trace::new(events))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        for event in self.events.iter() {
+            serde_json::to_writer(&mut file, event)?;
+            writeln!(file)?;
+        }
+        file.flush()
+    }
+
+    pub fn events(&self) -> &Vec<TraceEvent> {
+        &self.events
+    }
+
+    /// Events that happened to `peer`
+    pub fn peer(&self, peer: PeerId) -> Vec<&TraceEvent> {
+        self.events.iter().filter(|e| e.peer == peer).collect()
+    }
+
+    /// Events that involve the command: its instruction, executions, rollbacks,
+    /// the decisions that contain it and the messages that carry it
+    pub fn command(&self, id: &CommandId) -> Vec<&TraceEvent> {
+        self.events
+            .iter()
+            .filter(|e| Self::involves(&e.event, id))
+            .collect()
+    }
+
+    fn involves(event: &Event, id: &CommandId) -> bool {
+        let contains = |set: &Vec<TracedCommand>| set.iter().any(|command| command.id() == id);
+        match event {
+            Event::Send { message, .. }
+            | Event::Receive { message, .. }
+            | Event::Lost { message, .. } => Self::carries(message, &id.to_string()),
+            Event::Instruction(command)
+            | Event::Execute { command, .. }
+            | Event::Rollback(command) => command.id() == id,
+            Event::Complete { command, .. } => command == id,
+            Event::Propose { nc_set, c_set } | Event::Decide { nc_set, c_set } => {
+                contains(nc_set) || contains(c_set)
+            }
+            Event::Shutdown | Event::NewRound(_) | Event::Gather(_) | Event::Validate(_) => false,
+        }
+    }
+
+    /// Whether the serialized message carries the command `id`, whatever the application
+    fn carries(message: &Value, id: &str) -> bool {
+        match message {
+            Value::Object(fields) => {
+                fields.get("id").and_then(Value::as_str) == Some(id)
+                    || fields.values().any(|field| Self::carries(field, id))
+            }
+            Value::Array(values) => values.iter().any(|value| Self::carries(value, id)),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids = |set: &Vec<TracedCommand>| {
+            set.iter()
+                .map(|command| command.id().to_string())
+                .collect::<Vec<_>>()
+        };
+        match self {
+            Event::Send { to, delay, message } => match to {
+                Some(to) => write!(f, "sends to #{} in {}ms: {}", to, delay, message),
+                None => write!(f, "sends in {}ms: {}", delay, message),
+            },
+            Event::Receive { from, message } => write!(f, "receives from #{}: {}", from, message),
+            Event::Lost { to, message } => match to {
+                Some(to) => write!(f, "loses a message to #{}: {}", to, message),
+                None => write!(f, "loses a message: {}", message),
+            },
+            Event::Instruction(command) => write!(f, "is instructed to issue {:?}", command),
+            Event::Complete {
+                command,
+                round,
+                phase,
+            } => write!(f, "completes {} in round {} ({:?})", command, round, phase),
+            Event::Shutdown => write!(f, "shuts down"),
+            Event::Execute { command, result } => {
+                write!(f, "executes {:?}: {:?}", command, result)
+            }
+            Event::Rollback(command) => write!(f, "rollbacks {:?}", command),
+            Event::Propose { nc_set, c_set } => write!(
+                f,
+                "proposes NC-set {:?} and C-set {:?}",
+                ids(nc_set),
+                ids(c_set)
+            ),
+            Event::Decide { nc_set, c_set } => write!(
+                f,
+                "decides NC-set {:?} and C-set {:?}",
+                ids(nc_set),
+                ids(c_set)
+            ),
+            Event::NewRound(round) => write!(f, "enters round {}", round),
+            Event::Gather(round) => write!(f, "gathers a proposal for round {}", round),
+            Event::Validate(round) => write!(f, "validates round {}", round),
+        }
+    }
+}
+
+impl Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>6} [{:?}] #{}", self.seq, self.time, self.peer)?;
+        if let Some(round) = self.round {
+            write!(f, " (round {})", round)?;
+        }
+        write!(f, " {}", self.event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        banking::action::Action,
+        talk::{CommandResult, Message},
+    };
+
+    use super::*;
+
+    #[test]
+    fn disabled_tracer_records_nothing() {
+        let tracer = Tracer::new(false);
+        tracer.record(0, None, || panic!("The event is built"));
+        assert!(tracer.trace().events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn traces_are_written_and_loaded() {
+        let tracer = Tracer::new(true);
+        let clone = tracer.clone();
+        let command = Command::new(0, Action::Deposit(10));
+        let other = Command::new(0, Action::Get);
+
+        tracer.record(0, None, || Event::Instruction(Tracer::command(&command)));
+        clone.record(0, None, || Event::Send {
+            to: Some(2),
+            delay: 5,
+            message: Tracer::value(&Message::Command(command.clone(), None)),
+        });
+        clone.record(2, Some(0), || Event::Execute {
+            command: Tracer::command(&other),
+            result: Tracer::value(&CommandResult::<Action>::Success(Some(0))),
+        });
+        clone.record(2, Some(0), || Event::Rollback(Tracer::command(&command)));
+        tracer.record(2, Some(0), || Event::Decide {
+            nc_set: vec![Tracer::command(&other)],
+            c_set: Vec::new(),
+        });
+        tracer.record(2, Some(1), || Event::NewRound(1));
+
+        let trace = tracer.trace();
+        let seqs: Vec<u64> = trace.events().iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(trace.peer(2).len(), 4);
+        let history: Vec<u64> = trace.command(command.id()).iter().map(|e| e.seq).collect();
+        assert_eq!(history, vec![0, 1, 3]);
+        assert_eq!(trace.command(other.id()).len(), 2);
+
+        let path = std::env::temp_dir().join(format!("trace_{}.jsonl", command.id()));
+        trace.write(&path).unwrap();
+        let loaded = Trace::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, trace);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    error::ConsensusError,
+    peer::coordinator::{Coordinator, ProposalData, ProposalSignedData},
+    talk::RoundNumber,
+    types::*,
+};
+
+use super::{receive_decision, Consensus, PbftReplica, PendingDecisions};
+
+/// Consensus reached by a dedicated task, namely the oracle `Coordinator` or the `PbftReplica`
+/// of the replica. Proposals, decisions and consensus messages go through channels.
+pub struct ChannelConsensus<O: Operation = Action> {
+    proposal_inlet: MPSCSender<ProposalSignedData<O>>,
+    proposal_outlet: BroadcastReceiver<ProposalData<O>>,
+    message_inlet: Option<ConsensusInlet<O>>,
+    pending: PendingDecisions<O>,
+}
+
+impl<O: Operation> ChannelConsensus<O> {
+    pub fn new(
+        proposal_inlet: MPSCSender<ProposalSignedData<O>>,
+        proposal_outlet: BroadcastReceiver<ProposalData<O>>,
+    ) -> Self {
+        ChannelConsensus {
+            proposal_inlet,
+            proposal_outlet,
+            message_inlet: None,
+            pending: PendingDecisions::new(),
+        }
+    }
+
+    pub fn oracle(coordinator: &Coordinator<O>) -> Self {
+        Self::new(coordinator.proposer(), coordinator.subscribe())
+    }
+
+    pub fn pbft(replica: &PbftReplica<O>) -> Self {
+        ChannelConsensus {
+            message_inlet: Some(replica.inlet()),
+            ..Self::new(replica.proposer(), replica.subscribe())
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<O: Operation> Consensus<O> for ChannelConsensus<O> {
+    async fn propose(&mut self, proposal: ProposalSignedData<O>) -> Result<(), ConsensusError> {
+        self.proposal_inlet
+            .send(proposal)
+            .await
+            .map_err(|_| ConsensusError::Closed)
+    }
+
+    async fn decide(&mut self, round: RoundNumber) -> Result<ProposalData<O>, ConsensusError> {
+        receive_decision(&mut self.proposal_outlet, &mut self.pending, round).await
+    }
+
+    fn inlet(&self) -> Option<ConsensusInlet<O>> {
+        self.message_inlet.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::{broadcast, mpsc};
+
+    use crate::database::replica_database::Set;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn decisions_of_later_rounds_are_kept() {
+        let (proposal_inlet, _proposal_outlet) = mpsc::channel(10);
+        let (broadcaster, decisions) = broadcast::channel(10);
+        let mut consensus: ChannelConsensus = ChannelConsensus::new(proposal_inlet, decisions);
+
+        for k in [1, 3, 2] {
+            broadcaster.send((k, Set::new(), Set::new())).unwrap();
+        }
+
+        // The decision of round 1 is skipped, the one of round 3 is kept for later
+        let (k, _, _) = consensus.decide(2).await.unwrap();
+        assert_eq!(k, 2);
+        let (k, _, _) = consensus.decide(3).await.unwrap();
+        assert_eq!(k, 3);
+
+        drop(broadcaster);
+        assert!(matches!(
+            consensus.decide(4).await,
+            Err(ConsensusError::Closed)
+        ));
+    }
+}
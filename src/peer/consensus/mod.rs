@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    application::Operation, banking::action::Action, error::ConsensusError, network::NetworkInfo,
+    talk::RoundNumber, types::*,
+};
+
+use super::{
+    coordinator::{Coordinator, ProposalData, ProposalSignedData},
+    handler::Communicator,
+    runner::Runner,
+};
+
+pub mod channel;
+pub mod pbft;
+pub mod simulated;
+
+pub use channel::ChannelConsensus;
+pub use pbft::PbftReplica;
+pub use simulated::SimulatedConsensus;
+
+const BUFFER_SIZE: usize = 100;
+
+/// Consensus used by a `ReplicaHandler` during the CHK phase.
+/// A replica proposes its (k, NCSet, CSet), then waits for the decision of the round k.
+#[async_trait::async_trait]
+pub trait Consensus<O: Operation = Action>: Sync + Send {
+    async fn propose(&mut self, proposal: ProposalSignedData<O>) -> Result<(), ConsensusError>;
+
+    /// Waits for the decision of the given round. Decisions of previous rounds are skipped,
+    /// while those of later rounds are kept until they are waited for.
+    async fn decide(&mut self, round: RoundNumber) -> Result<ProposalData<O>, ConsensusError>;
+
+    /// Inlet of the consensus messages the replica receives from the network, if the
+    /// consensus exchanges messages between the replicas
+    fn inlet(&self) -> Option<ConsensusInlet<O>> {
+        None
+    }
+}
+
+/// Decisions received before the replica waited for them, by round
+pub(crate) type PendingDecisions<O> = BTreeMap<RoundNumber, ProposalData<O>>;
+
+/// Returns the decision of `round`, from `pending` or from `decisions`.
+/// The decisions of later rounds received meanwhile are added to `pending`.
+async fn receive_decision<O: Operation>(
+    decisions: &mut BroadcastReceiver<ProposalData<O>>,
+    pending: &mut PendingDecisions<O>,
+    round: RoundNumber,
+) -> Result<ProposalData<O>, ConsensusError> {
+    // The previous rounds are over
+    *pending = pending.split_off(&round);
+    if let Some(decision) = pending.remove(&round) {
+        return Ok(decision);
+    }
+    loop {
+        match decisions.recv().await {
+            Ok(decision) => {
+                let k = decision.0;
+                if k == round {
+                    return Ok(decision);
+                }
+                if k > round {
+                    pending.insert(k, decision);
+                }
+            }
+            Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => return Err(ConsensusError::Closed),
+        }
+    }
+}
+
+/// Defines how the replicas agree on the (k, NCSet, CSet) of a round during the CHK phase.
+/// `Oracle` relies on the trusted in-process `Coordinator`, `Simulated` decides with a fixed latency
+/// (the consensus duration) without any task, while `Pbft` runs a message-based Byzantine consensus
+/// among the replicas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsensusMode {
+    Oracle,
+    Simulated,
+    Pbft,
+}
+
+/// The consensus instances of a `Network`.
+pub enum ConsensusSystem<O: Operation = Action> {
+    Oracle(Coordinator<O>),
+    Simulated(
+        Arc<Mutex<Coordinator<O>>>,
+        BroadcastSender<ProposalData<O>>,
+        Duration,
+    ),
+    /// Consensus instances of the replicas, created along with their handlers
+    Pbft(Mutex<Vec<PbftReplica<O>>>),
+}
+
+impl<O: Operation> ConsensusSystem<O> {
+    pub async fn setup(network_info: NetworkInfo) -> Self {
+        match network_info.consensus_mode() {
+            ConsensusMode::Oracle => ConsensusSystem::Oracle(Coordinator::new(network_info)),
+            ConsensusMode::Simulated => {
+                let latency = Duration::from_secs_f64(network_info.consensus_duration());
+                let (broadcaster, _) = broadcast::channel(BUFFER_SIZE);
+                ConsensusSystem::Simulated(
+                    Arc::new(Mutex::new(Coordinator::new(network_info))),
+                    broadcaster,
+                    latency,
+                )
+            }
+            ConsensusMode::Pbft => ConsensusSystem::Pbft(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns the consensus to inject in the replica of the given `Communicator`.
+    /// With `Pbft`, the consensus instance of the replica sends its messages through a copy of
+    /// the `Communicator`.
+    pub fn consensus(&self, communicator: &Communicator<O>) -> Box<dyn Consensus<O>> {
+        match self {
+            ConsensusSystem::Oracle(coordinator) => Box::new(ChannelConsensus::oracle(coordinator)),
+            ConsensusSystem::Simulated(coordinator, broadcaster, latency) => Box::new(
+                SimulatedConsensus::new(coordinator.clone(), broadcaster.clone(), *latency),
+            ),
+            ConsensusSystem::Pbft(replicas) => {
+                let replica = PbftReplica::new(communicator.clone());
+                let consensus = ChannelConsensus::pbft(&replica);
+                replicas
+                    .lock()
+                    .expect("The consensus system is poisoned")
+                    .push(replica);
+                Box::new(consensus)
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<O: Operation> Runner for ConsensusSystem<O> {
+    async fn run(self) {
+        match self {
+            ConsensusSystem::Oracle(coordinator) => coordinator.run().await,
+            ConsensusSystem::Simulated(..) => {}
+            ConsensusSystem::Pbft(replicas) => {
+                let replicas = replicas
+                    .into_inner()
+                    .expect("The consensus system is poisoned");
+                join_all(replicas.into_iter().map(|replica| replica.run())).await;
+            }
+        }
+    }
+}
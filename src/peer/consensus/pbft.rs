@@ -0,0 +1,1106 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use talk::crypto::{primitives::sign::Signature, Identity, Statement};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    crypto::statements::{PbftPrepare, PbftProposal, PbftViewChange},
+    database::replica_database::Set,
+    network::NetworkInfo,
+    peer::{
+        coordinator::{Coordinator, ProposalData, ProposalSignedData},
+        handler::{Communicator, FaultyReplicaStrategy},
+        peer::PeerId,
+        runner::Runner,
+    },
+    talk::{Message, RoundNumber, ViewNumber},
+    types::*,
+};
+
+const BUFFER_SIZE: usize = 100;
+
+/// Message delays of a view change followed by a view: the view changes reach the new leader,
+/// which broadcasts its pre-prepare, after which the replicas exchange prepares and then commits
+const VIEW_MESSAGE_DELAYS: u32 = 4;
+
+/// Lower bound of the timeout, which then stays positive on links without delay
+const MIN_VIEW_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// (NCSet, CSet) proposed or decided for a round
+type Value<O> = (Set<O>, Set<O>);
+
+/// Signed prepares of a quorum of replicas for the same value in the same view.
+/// Some correct replica may have committed the value, so the next views must re-propose it.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(bound = "")]
+pub struct PreparedCertificate<O: Operation = Action> {
+    pub view: ViewNumber,
+    pub nc_set: Set<O>,
+    pub c_set: Set<O>,
+    pub prepares: Vec<(Identity, Signature)>,
+}
+
+/// Sent by a replica moving to the view `view` of the round `round`, with the value it prepared
+/// last, if any
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(bound = "")]
+pub struct ViewChange<O: Operation = Action> {
+    pub round: RoundNumber,
+    pub view: ViewNumber,
+    pub prepared: Option<PreparedCertificate<O>>,
+}
+
+/// View change and signature of a replica, as relayed by the leader of the new view
+pub type SignedViewChange<O = Action> = (Identity, ViewChange<O>, Signature);
+
+/// Proposal and signature of a replica, as relayed by the leader that aggregates it
+pub type SignedProposal<O = Action> = (ProposalSignedData<O>, Signature);
+
+/// State of the consensus instance of a round
+struct RoundState<O: Operation> {
+    view: ViewNumber,
+    proposals: Vec<SignedProposal<O>>, // In the order of reception
+    accepted: Option<Value<O>>, // Value pre-prepared in the current view
+    prepared: Option<PreparedCertificate<O>>,
+    prepares: HashMap<(ViewNumber, Value<O>), HashMap<Identity, Signature>>,
+    commits: HashMap<(ViewNumber, Value<O>), HashSet<Identity>>,
+    commit_sent: HashSet<ViewNumber>,
+    view_changes: HashMap<ViewNumber, HashMap<Identity, (ViewChange<O>, Signature)>>,
+    decisions: HashMap<Value<O>, HashSet<Identity>>, // Replicas that told this one they decided
+    pre_prepared: HashSet<ViewNumber>, // Views in which this replica already acted as the leader
+    deadline: Option<Instant>,
+}
+
+impl<O: Operation> RoundState<O> {
+    fn new() -> Self {
+        RoundState {
+            view: 0,
+            proposals: Vec::new(),
+            accepted: None,
+            prepared: None,
+            prepares: HashMap::new(),
+            commits: HashMap::new(),
+            commit_sent: HashSet::new(),
+            view_changes: HashMap::new(),
+            decisions: HashMap::new(),
+            pre_prepared: HashSet::new(),
+            deadline: None,
+        }
+    }
+
+    /// Aggregates the first `n_ack` proposals received, which the pre-prepare then carries
+    fn aggregate(&self, n_ack: usize) -> Option<(Value<O>, Vec<SignedProposal<O>>)> {
+        if self.proposals.len() < n_ack {
+            return None;
+        }
+        let proposals = self.proposals[..n_ack].to_vec();
+        Some((PbftReplica::<O>::aggregate(&proposals, n_ack), proposals))
+    }
+}
+
+/// Consensus instance of a single replica, following a PBFT-style protocol.
+///
+/// For each round k, every replica broadcasts its signed (NCSet, CSet) proposal. The leader of the
+/// view v (replica (k + v) mod n) aggregates the first `n_ack` proposals the same way the
+/// `Coordinator` does, and pre-prepares the result along with these proposals: every replica
+/// recomputes the value from them before preparing it. Replicas then exchange signed prepare and
+/// commit messages, and decide once they received a quorum of n - f commits. If no decision is
+/// reached before the view timeout, replicas send a signed view change, carrying over the
+/// certificate of the value they prepared. The leader of the next view pre-prepares with the view
+/// changes of a quorum (NEW-VIEW), from which every replica re-derives the value to propose: a
+/// value committed in a previous view is prepared by a correct replica of any quorum, and thus
+/// kept. If none is prepared, the leader aggregates the proposals again.
+///
+/// A replica that decided a round answers the proposals and view changes of this round with its
+/// decision, so that the replicas left behind, e.g. by an equivocating leader, decide as well:
+/// f + 1 matching decisions include the one of a correct replica.
+///
+/// The messages go through the `Communicator` of the replica, and are received by its `Peer`,
+/// so that the delays and faults of the network apply to them. The decisions are exposed to the
+/// replica through the same channels as the `Coordinator`.
+/// The instance of a faulty replica follows its `FaultyReplicaStrategy`.
+pub struct PbftReplica<O: Operation = Action> {
+    communicator: Communicator<O>,
+    message_inlet: ConsensusInlet<O>,
+    message_outlet: ConsensusOutlet<O>,
+    proposal_inlet: MPSCSender<ProposalSignedData<O>>,
+    proposal_outlet: MPSCReceiver<ProposalSignedData<O>>,
+    broadcaster: BroadcastSender<ProposalData<O>>,
+    rounds: HashMap<RoundNumber, RoundState<O>>,
+    decided: HashMap<RoundNumber, Value<O>>,
+    outbox: VecDeque<(Option<Identity>, Message<O>)>, // Messages to send, to every replica if no recipient is set
+    strategy: Option<FaultyReplicaStrategy>,          // Only set for faulty replicas
+}
+
+impl<O: Operation> PbftReplica<O> {
+    pub fn new(communicator: Communicator<O>) -> Self {
+        let (message_inlet, message_outlet) = mpsc::channel(BUFFER_SIZE);
+        let (proposal_inlet, proposal_outlet) = mpsc::channel(BUFFER_SIZE);
+        let (broadcaster, _) = broadcast::channel(BUFFER_SIZE);
+        let id = *communicator.id();
+        let strategy = communicator
+            .identity_table()
+            .is_faulty(&id)
+            .then(|| communicator.network_info().faulty_replica_strategy(&id));
+        PbftReplica {
+            communicator,
+            message_inlet,
+            message_outlet,
+            proposal_inlet,
+            proposal_outlet,
+            broadcaster,
+            rounds: HashMap::new(),
+            decided: HashMap::new(),
+            outbox: VecDeque::new(),
+            strategy,
+        }
+    }
+
+    pub fn id(&self) -> &PeerId {
+        self.communicator.id()
+    }
+
+    /// Inlet of the consensus messages received by the replica
+    pub fn inlet(&self) -> ConsensusInlet<O> {
+        self.message_inlet.clone()
+    }
+
+    pub fn proposer(&self) -> MPSCSender<ProposalSignedData<O>> {
+        self.proposal_inlet.clone()
+    }
+
+    pub fn subscribe(&self) -> BroadcastReceiver<ProposalData<O>> {
+        self.broadcaster.subscribe()
+    }
+
+    fn replicas(&self) -> &Vec<Identity> {
+        self.communicator.identity_table().replicas()
+    }
+
+    /// Number of matching messages needed to prepare, commit or change view
+    fn quorum(&self) -> usize {
+        let network_info = self.communicator.network_info();
+        self.replicas().len() - network_info.f()
+    }
+
+    fn leader(&self, round: RoundNumber, view: ViewNumber) -> &Identity {
+        let replicas = self.replicas();
+        &replicas[(round + view) % replicas.len()]
+    }
+
+    fn is_leader(&self, round: RoundNumber, view: ViewNumber) -> bool {
+        self.leader(round, view).eq(self.communicator.key())
+    }
+
+    /// Time a replica waits for the decision of a round before moving to the next view, which
+    /// then waits one more timeout than the previous one
+    fn view_timeout(network_info: &NetworkInfo) -> Duration {
+        let link_delay = Duration::from_millis(network_info.max_link_delay());
+        (link_delay * VIEW_MESSAGE_DELAYS).max(MIN_VIEW_TIMEOUT)
+    }
+
+    fn round_mut(&mut self, round: RoundNumber) -> &mut RoundState<O> {
+        self.rounds.entry(round).or_insert_with(RoundState::new)
+    }
+
+    fn arm_timer(&mut self, round: RoundNumber) {
+        let deadline = Instant::now() + Self::view_timeout(self.communicator.network_info());
+        let state = self.round_mut(round);
+        if state.deadline.is_none() {
+            state.deadline = Some(deadline);
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.rounds
+            .values()
+            .filter_map(|state| state.deadline)
+            .min()
+    }
+
+    /// Queues a message for every replica, including this one
+    fn broadcast(&mut self, message: Message<O>) {
+        self.outbox.push_back((None, message));
+    }
+
+    /// Queues a message for the given replica
+    fn send(&mut self, to: Identity, message: Message<O>) {
+        self.outbox.push_back((Some(to), message));
+    }
+
+    fn is_crashed(&self) -> bool {
+        self.strategy == Some(FaultyReplicaStrategy::Crash)
+    }
+
+    /// Answers a message of a decided round with the decision
+    fn send_decision(&mut self, to: Identity, round: RoundNumber) {
+        if let Some((nc_set, c_set)) = self.decided.get(&round).cloned() {
+            self.send(to, Message::ConsensusDecision(round, nc_set, c_set));
+        }
+    }
+
+    /// Returns true if `signer` is a replica, and signed the statement
+    fn verify<S: Statement>(
+        &self,
+        signer: &Identity,
+        signature: &Signature,
+        statement: &S,
+    ) -> bool {
+        let identity_table = self.communicator.identity_table();
+        identity_table.is_replica(signer)
+            && identity_table
+                .keycard(signer)
+                .map(|keycard| signature.verify(keycard, statement).is_ok())
+                .unwrap_or(false)
+    }
+
+    /// Returns true if a quorum of distinct replicas signed the prepares of the certificate
+    fn verify_prepared(&self, round: RoundNumber, certificate: &PreparedCertificate<O>) -> bool {
+        let statement = PbftPrepare {
+            round: &round,
+            view: &certificate.view,
+            nc_set: &certificate.nc_set,
+            c_set: &certificate.c_set,
+        };
+        let signers = certificate
+            .prepares
+            .iter()
+            .filter(|(signer, signature)| self.verify(signer, signature, &statement))
+            .map(|(signer, _)| signer)
+            .collect::<HashSet<_>>();
+        signers.len() >= self.quorum()
+    }
+
+    /// Returns true if the replica `signer` signed the view change, and the value it carries was
+    /// prepared in a previous view
+    fn verify_view_change(
+        &self,
+        signer: &Identity,
+        view_change: &ViewChange<O>,
+        signature: &Signature,
+    ) -> bool {
+        self.verify(signer, signature, &PbftViewChange { view_change })
+            && view_change
+                .prepared
+                .as_ref()
+                .map(|certificate| {
+                    certificate.view < view_change.view
+                        && self.verify_prepared(view_change.round, certificate)
+                })
+                .unwrap_or(true)
+    }
+
+    /// Returns true if the view changes are valid, for the given view, and come from a quorum
+    fn verify_new_view(
+        &self,
+        round: RoundNumber,
+        view: ViewNumber,
+        view_changes: &[SignedViewChange<O>],
+    ) -> bool {
+        let valid = view_changes.iter().all(|(signer, view_change, signature)| {
+            view_change.round == round
+                && view_change.view == view
+                && self.verify_view_change(signer, view_change, signature)
+        });
+        let signers = view_changes
+            .iter()
+            .map(|(signer, _, _)| signer)
+            .collect::<HashSet<_>>();
+        valid && signers.len() >= self.quorum()
+    }
+
+    /// Returns true if `n_ack` distinct replicas signed the proposals for the round, and the value
+    /// is their aggregate
+    fn verify_proposals(
+        &self,
+        round: RoundNumber,
+        value: &Value<O>,
+        proposals: &[SignedProposal<O>],
+    ) -> bool {
+        let n_ack = self.communicator.network_info().n_ack();
+        let valid = proposals
+            .iter()
+            .all(|((signer, k, nc_set, c_set), signature)| {
+                let statement = PbftProposal {
+                    round: k,
+                    nc_set,
+                    c_set,
+                };
+                *k == round && self.verify(signer, signature, &statement)
+            });
+        let signers = proposals
+            .iter()
+            .map(|((signer, _, _, _), _)| signer)
+            .collect::<HashSet<_>>();
+        valid
+            && proposals.len() == n_ack
+            && signers.len() == n_ack
+            && Self::aggregate(proposals, n_ack).eq(value)
+    }
+
+    fn aggregate(proposals: &[SignedProposal<O>], n_ack: usize) -> Value<O> {
+        let values = proposals
+            .iter()
+            .map(|((_, _, nc_set, c_set), _)| (nc_set.clone(), c_set.clone()))
+            .collect::<Vec<_>>();
+        Coordinator::aggregate(values, n_ack)
+    }
+
+    /// Value the leader of a new view must pre-prepare: the one prepared in the most recent view,
+    /// if any. Otherwise, no value can have been committed, and the leader is free to choose.
+    fn new_view_value<'a, I: Iterator<Item = &'a ViewChange<O>>>(
+        view_changes: I,
+    ) -> Option<Value<O>> {
+        view_changes
+            .filter_map(|view_change| view_change.prepared.as_ref())
+            .max_by_key(|certificate| certificate.view)
+            .map(|certificate| (certificate.nc_set.clone(), certificate.c_set.clone()))
+    }
+
+    fn handle_local_proposal(&mut self, round: RoundNumber, nc_set: Set<O>, c_set: Set<O>) {
+        if self.decided.contains_key(&round) || self.is_crashed() {
+            return;
+        }
+        let signature = self.communicator.sign(&PbftProposal {
+            round: &round,
+            nc_set: &nc_set,
+            c_set: &c_set,
+        });
+        if let Some(signature) = signature {
+            self.broadcast(Message::ConsensusProposal(round, nc_set, c_set, signature));
+        }
+    }
+
+    fn handle_message(&mut self, from: Identity, message: Message<O>) {
+        if !self.replicas().contains(&from) || self.is_crashed() {
+            return;
+        }
+        match message {
+            Message::ConsensusProposal(k, nc_set, c_set, signature) => {
+                self.handle_proposal(from, k, (nc_set, c_set), signature)
+            }
+            Message::ConsensusPrePrepare(k, v, nc_set, c_set, proposals, view_changes) => {
+                self.handle_pre_prepare(from, k, v, (nc_set, c_set), proposals, view_changes)
+            }
+            Message::ConsensusPrepare(k, v, nc_set, c_set, signature) => {
+                self.handle_prepare(from, k, v, (nc_set, c_set), signature)
+            }
+            Message::ConsensusCommit(k, v, nc_set, c_set) => {
+                self.handle_commit(from, k, v, (nc_set, c_set))
+            }
+            Message::ConsensusViewChange(view_change, signature) => {
+                self.handle_view_change(from, view_change, signature)
+            }
+            Message::ConsensusDecision(k, nc_set, c_set) => {
+                self.handle_decision(from, k, (nc_set, c_set))
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_proposal(
+        &mut self,
+        from: Identity,
+        round: RoundNumber,
+        (nc_set, c_set): Value<O>,
+        signature: Signature,
+    ) {
+        if self.decided.contains_key(&round) {
+            self.send_decision(from, round);
+            return;
+        }
+        let statement = PbftProposal {
+            round: &round,
+            nc_set: &nc_set,
+            c_set: &c_set,
+        };
+        if !self.verify(&from, &signature, &statement) {
+            return;
+        }
+        let n_ack = self.communicator.network_info().n_ack();
+        let state = self.round_mut(round);
+        // Only the first proposal of each replica is taken into account
+        if state
+            .proposals
+            .iter()
+            .any(|((proposer, _, _, _), _)| proposer.eq(&from))
+        {
+            return;
+        }
+        state
+            .proposals
+            .push(((from, round, nc_set, c_set), signature));
+        if state.proposals.len() >= n_ack {
+            self.arm_timer(round);
+            self.try_pre_prepare(round, 0);
+        }
+    }
+
+    /// Pre-prepares the aggregated proposals if this replica leads the first view of the round
+    fn try_pre_prepare(&mut self, round: RoundNumber, view: ViewNumber) {
+        if !self.is_leader(round, view) {
+            return;
+        }
+        let n_ack = self.communicator.network_info().n_ack();
+        let state = self.round_mut(round);
+        if state.view != view || state.pre_prepared.contains(&view) {
+            return;
+        }
+        let ((nc_set, c_set), proposals) = match state.aggregate(n_ack) {
+            Some(aggregate) => aggregate,
+            None => return,
+        };
+        state.pre_prepared.insert(view);
+        self.broadcast(Message::ConsensusPrePrepare(
+            round,
+            view,
+            nc_set,
+            c_set,
+            proposals,
+            Vec::new(),
+        ));
+    }
+
+    /// Prepares the value pre-prepared by the leader of the view. After the first view, the
+    /// value must be the one re-derived from the view changes of a quorum. Otherwise, it must be
+    /// the aggregate of the `n_ack` signed proposals carried by the pre-prepare.
+    fn handle_pre_prepare(
+        &mut self,
+        from: Identity,
+        round: RoundNumber,
+        view: ViewNumber,
+        value: Value<O>,
+        proposals: Vec<SignedProposal<O>>,
+        view_changes: Vec<SignedViewChange<O>>,
+    ) {
+        if self.decided.contains_key(&round) || !self.leader(round, view).eq(&from) {
+            return;
+        }
+        let prepared = if view > 0 {
+            if !self.verify_new_view(round, view, &view_changes) {
+                return;
+            }
+            Self::new_view_value(view_changes.iter().map(|(_, vc, _)| vc))
+        } else {
+            None
+        };
+        let valid = match prepared {
+            Some(prepared) => prepared == value,
+            None => self.verify_proposals(round, &value, &proposals),
+        };
+        if !valid {
+            return;
+        }
+        let state = self.round_mut(round);
+        if view < state.view || (view == state.view && state.accepted.is_some()) {
+            return;
+        }
+        state.view = view;
+        state.accepted = Some(value.clone());
+        self.arm_timer(round);
+        let (nc_set, c_set) = value;
+        let signature = self.communicator.sign(&PbftPrepare {
+            round: &round,
+            view: &view,
+            nc_set: &nc_set,
+            c_set: &c_set,
+        });
+        if let Some(signature) = signature {
+            self.broadcast(Message::ConsensusPrepare(
+                round, view, nc_set, c_set, signature,
+            ));
+        }
+        self.check_progress(round);
+    }
+
+    fn handle_prepare(
+        &mut self,
+        from: Identity,
+        round: RoundNumber,
+        view: ViewNumber,
+        value: Value<O>,
+        signature: Signature,
+    ) {
+        if self.decided.contains_key(&round) {
+            return;
+        }
+        let (nc_set, c_set) = &value;
+        let statement = PbftPrepare {
+            round: &round,
+            view: &view,
+            nc_set,
+            c_set,
+        };
+        if !self.verify(&from, &signature, &statement) {
+            return;
+        }
+        self.round_mut(round)
+            .prepares
+            .entry((view, value))
+            .or_default()
+            .insert(from, signature);
+        self.check_progress(round);
+    }
+
+    fn handle_commit(
+        &mut self,
+        from: Identity,
+        round: RoundNumber,
+        view: ViewNumber,
+        value: Value<O>,
+    ) {
+        if self.decided.contains_key(&round) {
+            return;
+        }
+        self.round_mut(round)
+            .commits
+            .entry((view, value))
+            .or_default()
+            .insert(from);
+        self.check_progress(round);
+    }
+
+    /// Sends the commit once the accepted value is prepared, and decides once it is committed
+    fn check_progress(&mut self, round: RoundNumber) {
+        let quorum = self.quorum();
+        let state = match self.rounds.get_mut(&round) {
+            Some(state) => state,
+            None => return,
+        };
+        let view = state.view;
+        let value = match state.accepted.clone() {
+            Some(value) => value,
+            None => return,
+        };
+        let key = (view, value);
+
+        let prepares = state.prepares.get(&key).cloned().unwrap_or_default();
+        if prepares.len() >= quorum && !state.commit_sent.contains(&view) {
+            state.commit_sent.insert(view);
+            let (_, (nc_set, c_set)) = key.clone();
+            state.prepared = Some(PreparedCertificate {
+                view,
+                nc_set: nc_set.clone(),
+                c_set: c_set.clone(),
+                prepares: prepares.into_iter().collect(),
+            });
+            self.broadcast(Message::ConsensusCommit(round, view, nc_set, c_set));
+        }
+
+        let state = self.rounds.get(&round).unwrap();
+        let nbr_commits = state.commits.get(&key).map(|set| set.len()).unwrap_or(0);
+        if state.commit_sent.contains(&view) && nbr_commits >= quorum {
+            let (_, value) = key;
+            self.decide(round, value);
+        }
+    }
+
+    /// Decides once f + 1 replicas, thus at least a correct one, sent the same decision
+    fn handle_decision(&mut self, from: Identity, round: RoundNumber, value: Value<O>) {
+        if self.decided.contains_key(&round) {
+            return;
+        }
+        let f = self.communicator.network_info().f();
+        let deciders = self
+            .round_mut(round)
+            .decisions
+            .entry(value.clone())
+            .or_default();
+        deciders.insert(from);
+        if deciders.len() > f {
+            self.decide(round, value);
+        }
+    }
+
+    fn decide(&mut self, round: RoundNumber, value: Value<O>) {
+        self.rounds.remove(&round);
+        self.decided.insert(round, value.clone());
+        let (nc_set, c_set) = value;
+        // The replica may not listen yet: the decision stays buffered in the channel
+        let _ = self.broadcaster.send((round, nc_set, c_set));
+    }
+
+    /// Moves every round whose timer expired to the next view
+    fn handle_timeouts(&mut self) {
+        let now = Instant::now();
+        let expired = self
+            .rounds
+            .iter()
+            .filter(|(_, state)| state.deadline.map(|d| d <= now).unwrap_or(false))
+            .map(|(round, _)| *round)
+            .collect::<Vec<_>>();
+        let view_timeout = Self::view_timeout(self.communicator.network_info());
+
+        for round in expired {
+            let state = self.round_mut(round);
+            state.view += 1;
+            state.accepted = None;
+            state.deadline = Some(now + view_timeout * (state.view as u32 + 1));
+            let view_change = ViewChange {
+                round,
+                view: state.view,
+                prepared: state.prepared.clone(),
+            };
+            let signature = self.communicator.sign(&PbftViewChange {
+                view_change: &view_change,
+            });
+            if let Some(signature) = signature {
+                self.broadcast(Message::ConsensusViewChange(view_change, signature));
+            }
+        }
+    }
+
+    /// Collects the view changes, and pre-prepares the value re-derived from them once this
+    /// replica leads the new view and a quorum of replicas moved to it
+    fn handle_view_change(
+        &mut self,
+        from: Identity,
+        view_change: ViewChange<O>,
+        signature: Signature,
+    ) {
+        let (round, view) = (view_change.round, view_change.view);
+        if self.decided.contains_key(&round) {
+            self.send_decision(from, round);
+            return;
+        }
+        if !self.verify_view_change(&from, &view_change, &signature) {
+            return;
+        }
+        let quorum = self.quorum();
+        let n_ack = self.communicator.network_info().n_ack();
+        let is_leader = self.is_leader(round, view);
+        let state = self.round_mut(round);
+        let view_changes = state.view_changes.entry(view).or_default();
+        view_changes.insert(from, (view_change, signature));
+
+        if !is_leader || view_changes.len() < quorum || state.pre_prepared.contains(&view) {
+            return;
+        }
+
+        let new_view = view_changes
+            .iter()
+            .map(|(signer, (view_change, signature))| {
+                (signer.clone(), view_change.clone(), *signature)
+            })
+            .collect::<Vec<_>>();
+        let prepared = Self::new_view_value(new_view.iter().map(|(_, vc, _)| vc));
+        let (value, proposals) = match prepared {
+            Some(value) => (value, Vec::new()),
+            None => match state.aggregate(n_ack) {
+                Some(aggregate) => aggregate,
+                None => return,
+            },
+        };
+        state.pre_prepared.insert(view);
+        let (nc_set, c_set) = value;
+        self.broadcast(Message::ConsensusPrePrepare(
+            round, view, nc_set, c_set, proposals, new_view,
+        ));
+    }
+
+    /// Returns the message to send to the `index`-th replica, according to the strategy of this
+    /// replica if it is faulty
+    fn tamper(&self, message: Message<O>, index: usize) -> Message<O> {
+        let strategy = match &self.strategy {
+            Some(strategy) => strategy,
+            None => return message,
+        };
+        match message {
+            Message::ConsensusPrePrepare(k, v, nc_set, c_set, proposals, view_changes) => {
+                let (nc_set, c_set) = strategy.consensus_value(nc_set, c_set, index);
+                Message::ConsensusPrePrepare(k, v, nc_set, c_set, proposals, view_changes)
+            }
+            Message::ConsensusPrepare(k, v, nc_set, c_set, signature) => {
+                let (nc_set, c_set) = strategy.consensus_value(nc_set, c_set, index);
+                let signature = self
+                    .communicator
+                    .sign(&PbftPrepare {
+                        round: &k,
+                        view: &v,
+                        nc_set: &nc_set,
+                        c_set: &c_set,
+                    })
+                    .unwrap_or(signature);
+                Message::ConsensusPrepare(k, v, nc_set, c_set, signature)
+            }
+            Message::ConsensusCommit(k, v, nc_set, c_set) => {
+                let (nc_set, c_set) = strategy.consensus_value(nc_set, c_set, index);
+                Message::ConsensusCommit(k, v, nc_set, c_set)
+            }
+            message => message,
+        }
+    }
+
+    /// Sends the queued messages to the other replicas, and handles the local copies
+    async fn flush(&mut self) {
+        while let Some((to, message)) = self.outbox.pop_front() {
+            let key = self.communicator.key().clone();
+            for (index, replica) in self.replicas().clone().into_iter().enumerate() {
+                let recipient = to.as_ref().map(|to| to.eq(&replica)).unwrap_or(true);
+                if recipient && !replica.eq(&key) {
+                    let message = self.tamper(message.clone(), index);
+                    self.communicator.spawn_send_message(replica, message).await;
+                }
+            }
+            if to.is_none() {
+                self.handle_message(key, message);
+            }
+        }
+    }
+
+    async fn wait(deadline: Option<Instant>) {
+        match deadline {
+            Some(deadline) => sleep_until(deadline).await,
+            None => futures::future::pending().await,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<O: Operation> Runner for PbftReplica<O> {
+    async fn run(mut self) {
+        loop {
+            let deadline = self.next_deadline();
+            tokio::select! {
+                Some((id, message)) = self.message_outlet.recv() => {
+                    self.handle_message(id, message);
+                }
+
+                Some((_, k, nc_set, c_set)) = self.proposal_outlet.recv() => {
+                    self.handle_local_proposal(k, nc_set, c_set);
+                }
+
+                _ = Self::wait(deadline) => {
+                    self.handle_timeouts();
+                }
+            }
+            self.flush().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use tokio::time::timeout;
+
+    use crate::{
+        banking::action::Action,
+        crypto::identity_table::IdentityTableBuilder,
+        network::Topology,
+        peer::{
+            consensus::{Consensus, ConsensusMode, ConsensusSystem},
+            runner::Runner,
+        },
+        talk::{Command, FeedbackChannel},
+        tests::util::Utils,
+    };
+
+    use super::*;
+
+    /// Runs the consensus instances of the replicas over a mock network. The messages of the
+    /// replicas go through their `Communicator`, and thus through the faults of `network_info`.
+    async fn setup(network_info: NetworkInfo) -> HashMap<PeerId, Box<dyn Consensus>> {
+        let (keys, senders, receivers) = Utils::mock_network(network_info.size()).await;
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        for key in keys.iter() {
+            identity_table_builder.add_peer(Utils::keychain(&key));
+        }
+        let identity_table = identity_table_builder.build();
+
+        let system = ConsensusSystem::setup(network_info.clone()).await;
+        let (feedback_inlet, _feedback_outlet) = FeedbackChannel::channel();
+        let (_, _, replica_range, faulty_replica_range) = network_info.compute_ranges();
+        let mut endpoints = HashMap::new();
+        for (id, ((key, sender), mut receiver)) in
+            keys.into_iter().zip(senders).zip(receivers).enumerate()
+        {
+            if !replica_range.contains(&id) && !faulty_replica_range.contains(&id) {
+                continue;
+            }
+            let communicator = Communicator::new(
+                id,
+                key.clone(),
+                identity_table_builder.keychain(&key).unwrap(),
+                sender,
+                feedback_inlet.clone(),
+                network_info.clone(),
+                identity_table.clone(),
+            );
+            let consensus = system.consensus(&communicator);
+            let inlet = consensus.inlet().expect("Pbft receives messages");
+            tokio::spawn(async move {
+                loop {
+                    let (from, message, _) = receiver.receive().await;
+                    if inlet.send((from, message)).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            endpoints.insert(id, consensus);
+        }
+        tokio::spawn(system.run());
+
+        endpoints
+    }
+
+    fn proposal() -> (Set, Set) {
+        let mut nc_set: Set = BTreeSet::new();
+        let mut c_set: Set = BTreeSet::new();
+        nc_set.insert(Command::new(0, Action::Deposit(1)));
+        nc_set.insert(Command::new(1, Action::Deposit(2)));
+        c_set.insert(Command::new(0, Action::Withdraw(1)));
+        (nc_set, c_set)
+    }
+
+    async fn propose_all(
+        endpoints: &mut HashMap<PeerId, Box<dyn Consensus>>,
+        round: RoundNumber,
+        (nc_set, c_set): (Set, Set),
+    ) {
+        let (key, _, _) = Utils::unicast_channel().await;
+        for consensus in endpoints.values_mut() {
+            consensus
+                .propose((key.clone(), round, nc_set.clone(), c_set.clone()))
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn replicas_decide_the_aggregated_proposal() {
+        let mut network_info = NetworkInfo::with_default_report_folder(0, 4, 0, 0, 1, 4);
+        network_info.set_consensus_mode(ConsensusMode::Pbft);
+        let mut endpoints = setup(network_info).await;
+        let (key, _, _) = Utils::unicast_channel().await;
+
+        let (nc_set, c_set) = proposal();
+        let mut proposals = Vec::new();
+        for id in 0..4 {
+            // One replica considers a command as conflicting
+            let mut nc = nc_set.clone();
+            let mut c = c_set.clone();
+            if id == 0 {
+                let cmd = nc.iter().next().unwrap().clone();
+                nc.remove(&cmd);
+                c.insert(cmd);
+            }
+            proposals.push((nc.clone(), c.clone()));
+            let consensus = endpoints.get_mut(&id).unwrap();
+            consensus.propose((key.clone(), 1, nc, c)).await.unwrap();
+        }
+
+        let expected = Coordinator::aggregate(proposals, 4);
+        for consensus in endpoints.values_mut() {
+            let (k, nc, c) = timeout(Duration::from_secs(5), consensus.decide(1))
+                .await
+                .expect("Replica did not decide")
+                .unwrap();
+            assert_eq!(k, 1);
+            assert_eq!((nc, c), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn replicas_change_view_when_the_leader_crashed() {
+        let mut network_info = NetworkInfo::with_default_report_folder(0, 6, 0, 1, 1, 6);
+        network_info.set_consensus_mode(ConsensusMode::Pbft);
+        // The faulty replica (last one) crashed, and leads the first view of round 6
+        network_info.set_faulty_replicas_strategy(FaultyReplicaStrategy::Crash);
+        let mut endpoints = setup(network_info).await;
+        endpoints.remove(&6);
+
+        let (nc_set, c_set) = proposal();
+        propose_all(&mut endpoints, 6, (nc_set.clone(), c_set.clone())).await;
+
+        for consensus in endpoints.values_mut() {
+            let (k, nc, c) = timeout(Duration::from_secs(10), consensus.decide(6))
+                .await
+                .expect("Replica did not decide after the view change")
+                .unwrap();
+            assert_eq!(k, 6);
+            assert_eq!(nc, nc_set);
+            assert_eq!(c, c_set);
+        }
+    }
+
+    #[tokio::test]
+    async fn replicas_agree_despite_an_equivocating_leader() {
+        let mut network_info = NetworkInfo::with_default_report_folder(0, 3, 0, 1, 1, 4);
+        network_info.set_consensus_mode(ConsensusMode::Pbft);
+        // The faulty replica (last one) leads the first view of round 3, and pre-prepares a
+        // conflicting value to the replica #1
+        network_info.set_faulty_replicas_strategy(FaultyReplicaStrategy::EquivocateConsensus);
+        let mut endpoints = setup(network_info).await;
+
+        let (nc_set, c_set) = proposal();
+        propose_all(&mut endpoints, 3, (nc_set.clone(), c_set.clone())).await;
+        endpoints.remove(&3);
+
+        let mut decisions = Vec::new();
+        for consensus in endpoints.values_mut() {
+            let decision = timeout(Duration::from_secs(10), consensus.decide(3))
+                .await
+                .expect("Replica did not decide despite the equivocation")
+                .unwrap();
+            decisions.push(decision);
+        }
+        // The conflicting value is not the aggregate of the proposals, and is never prepared
+        for decision in decisions {
+            assert_eq!(decision, (3, nc_set.clone(), c_set.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn partitions_delay_the_decision() {
+        let mut network_info = NetworkInfo::with_default_report_folder(0, 4, 0, 0, 1, 4);
+        network_info.set_consensus_mode(ConsensusMode::Pbft);
+        // No side of the partition holds a quorum of replicas
+        let window = Duration::from_secs(2);
+        network_info
+            .faults()
+            .partition(vec![vec![0, 1], vec![2, 3]], Some(window));
+        let mut endpoints = setup(network_info).await;
+
+        propose_all(&mut endpoints, 1, proposal()).await;
+
+        let consensus = endpoints.get_mut(&0).unwrap();
+        assert!(
+            timeout(window / 2, consensus.decide(1)).await.is_err(),
+            "A replica decided without a quorum"
+        );
+        for consensus in endpoints.values_mut() {
+            timeout(Duration::from_secs(20), consensus.decide(1))
+                .await
+                .expect("Replica did not decide after the partition healed")
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn view_timeout_covers_the_link_delays() {
+        let view_timeout = |transmission_delay| {
+            let network_info =
+                NetworkInfo::with_default_report_folder(0, 4, 0, 0, transmission_delay, 4);
+            PbftReplica::<Action>::view_timeout(&network_info)
+        };
+        assert_eq!(view_timeout(10), MIN_VIEW_TIMEOUT);
+        assert!(view_timeout(300) > Duration::from_millis(300) * VIEW_MESSAGE_DELAYS);
+
+        let mut network_info = NetworkInfo::with_default_report_folder(0, 4, 0, 0, 10, 4);
+        network_info
+            .set_topology(Topology::Regions {
+                regions: vec![0, 0, 1, 1],
+                rtts: vec![vec![2, 800], vec![800, 2]],
+            })
+            .unwrap();
+        let timeout = PbftReplica::<Action>::view_timeout(&network_info);
+        assert!(timeout > Duration::from_millis(400) * VIEW_MESSAGE_DELAYS);
+    }
+
+    #[tokio::test]
+    async fn pre_prepares_of_later_views_need_a_new_view_certificate() {
+        let mut network_info = NetworkInfo::with_default_report_folder(0, 4, 0, 0, 1, 4);
+        network_info.set_consensus_mode(ConsensusMode::Pbft);
+        let (keys, senders, _receivers) = Utils::mock_network(4).await;
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        for key in keys.iter() {
+            identity_table_builder.add_peer(Utils::keychain(&key));
+        }
+        let identity_table = identity_table_builder.build();
+        let (feedback_inlet, _feedback_outlet) = FeedbackChannel::channel();
+        let communicator = Communicator::new(
+            0,
+            keys[0].clone(),
+            identity_table_builder.keychain(&keys[0]).unwrap(),
+            senders[0].clone(),
+            feedback_inlet,
+            network_info,
+            identity_table,
+        );
+        let mut replica = PbftReplica::new(communicator);
+
+        // The leader of view 1 of round 0 pre-prepares its own value, without any view change
+        let (nc_set, c_set) = proposal();
+        replica.handle_pre_prepare(
+            keys[1].clone(),
+            0,
+            1,
+            (nc_set, c_set),
+            Vec::new(),
+            Vec::new(),
+        );
+        assert!(
+            replica.outbox.is_empty(),
+            "The replica prepared a forged view"
+        );
+        assert!(replica.round_mut(0).accepted.is_none());
+    }
+
+    #[tokio::test]
+    async fn pre_prepares_of_the_first_view_need_the_aggregated_proposals() {
+        let mut network_info = NetworkInfo::with_default_report_folder(0, 4, 0, 0, 1, 3);
+        network_info.set_consensus_mode(ConsensusMode::Pbft);
+        let (keys, senders, _receivers) = Utils::mock_network(4).await;
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        for key in keys.iter() {
+            identity_table_builder.add_peer(Utils::keychain(&key));
+        }
+        let identity_table = identity_table_builder.build();
+        let (feedback_inlet, _feedback_outlet) = FeedbackChannel::channel();
+        let communicator = Communicator::new(
+            0,
+            keys[0].clone(),
+            identity_table_builder.keychain(&keys[0]).unwrap(),
+            senders[0].clone(),
+            feedback_inlet,
+            network_info,
+            identity_table,
+        );
+        let mut replica = PbftReplica::new(communicator);
+
+        // `proposer` proposes the value, with a signature made by `signer`
+        let (nc_set, c_set) = proposal();
+        let sign = |proposer: usize, signer: usize| {
+            let signature = Utils::keychain(&keys[signer])
+                .sign(&PbftProposal {
+                    round: &1,
+                    nc_set: &nc_set,
+                    c_set: &c_set,
+                })
+                .unwrap();
+            (
+                (keys[proposer].clone(), 1, nc_set.clone(), c_set.clone()),
+                signature,
+            )
+        };
+        let proposals = (1..4).map(|id| sign(id, id)).collect::<Vec<_>>();
+        let value = (nc_set.clone(), c_set.clone());
+        let mut pre_prepare = |value: Value<Action>, proposals: Vec<SignedProposal>| {
+            // The replica #1 leads the first view of round 1
+            replica.handle_pre_prepare(keys[1].clone(), 1, 0, value, proposals, Vec::new());
+            replica.round_mut(1).accepted.clone()
+        };
+
+        // The leader pre-prepares every command as conflicting
+        let garbage = (BTreeSet::new(), nc_set.union(&c_set).cloned().collect());
+        assert_eq!(pre_prepare(garbage, proposals.clone()), None);
+        // The leader does not relay the proposals, or relays forged or duplicated ones
+        assert_eq!(pre_prepare(value.clone(), Vec::new()), None);
+        let mut forged = proposals.clone();
+        forged[2] = sign(3, 2);
+        assert_eq!(pre_prepare(value.clone(), forged), None);
+        let mut duplicated = proposals.clone();
+        duplicated[2] = sign(2, 2);
+        assert_eq!(pre_prepare(value.clone(), duplicated), None);
+
+        assert_eq!(pre_prepare(value.clone(), proposals), Some(value));
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    error::ConsensusError,
+    peer::coordinator::{Coordinator, ProposalData, ProposalSignedData},
+    talk::RoundNumber,
+    types::*,
+};
+
+use super::{receive_decision, Consensus, PendingDecisions};
+
+/// Simulates a consensus that always takes the same time to decide.
+/// The replicas share the validation logic of the `Coordinator`, but no task runs it:
+/// the replica completing the quorum of a round publishes the decision after a fixed latency.
+pub struct SimulatedConsensus<O: Operation = Action> {
+    coordinator: Arc<Mutex<Coordinator<O>>>,
+    broadcaster: BroadcastSender<ProposalData<O>>,
+    decisions: BroadcastReceiver<ProposalData<O>>,
+    pending: PendingDecisions<O>,
+    latency: Duration,
+}
+
+impl<O: Operation> SimulatedConsensus<O> {
+    pub fn new(
+        coordinator: Arc<Mutex<Coordinator<O>>>,
+        broadcaster: BroadcastSender<ProposalData<O>>,
+        latency: Duration,
+    ) -> Self {
+        let decisions = broadcaster.subscribe();
+        SimulatedConsensus {
+            coordinator,
+            broadcaster,
+            decisions,
+            pending: PendingDecisions::new(),
+            latency,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<O: Operation> Consensus<O> for SimulatedConsensus<O> {
+    async fn propose(&mut self, proposal: ProposalSignedData<O>) -> Result<(), ConsensusError> {
+        let (_, k, _, _) = &proposal;
+        let k = *k;
+        let decision = self
+            .coordinator
+            .lock()
+            .expect("The simulated consensus is poisoned")
+            .propose(proposal);
+
+        if let Some((nc_set, c_set)) = decision {
+            let broadcaster = self.broadcaster.clone();
+            let latency = self.latency;
+            tokio::spawn(async move {
+                sleep(latency).await;
+                let _ = broadcaster.send((k, nc_set, c_set));
+            });
+        }
+        Ok(())
+    }
+
+    async fn decide(&mut self, round: RoundNumber) -> Result<ProposalData<O>, ConsensusError> {
+        receive_decision(&mut self.decisions, &mut self.pending, round).await
+    }
+}
@@ -6,29 +6,31 @@ use tokio::sync::broadcast::error::SendError;
 use tokio::sync::broadcast::{self, Receiver as BroadcastReceiver, Sender as BroadcastSender};
 use tokio::sync::mpsc;
 
-use crate::network::NetworkInfo;
+use crate::application::Operation;
+use crate::banking::action::Action;
+use crate::network::{trace::Event, NetworkInfo};
 use crate::talk::{Command, RoundNumber};
 use crate::types::*;
 
 use super::runner::Runner;
 
-type ProposalSet = BTreeSet<Command>;
+pub type ProposalSet<O = Action> = BTreeSet<Command<O>>;
 /// Represents data handled by the coordinator, namely (k, NCSet, CSet) (according to the paper)
-pub type ProposalSignedData = (Identity, RoundNumber, ProposalSet, ProposalSet);
-pub type ProposalData = (RoundNumber, ProposalSet, ProposalSet);
-type ReceivedMap = HashMap<RoundNumber, HashMap<Identity, (ProposalSet, ProposalSet)>>;
+pub type ProposalSignedData<O = Action> = (Identity, RoundNumber, ProposalSet<O>, ProposalSet<O>);
+pub type ProposalData<O = Action> = (RoundNumber, ProposalSet<O>, ProposalSet<O>);
+type ReceivedMap<O> = HashMap<RoundNumber, HashMap<Identity, (ProposalSet<O>, ProposalSet<O>)>>;
 const BUFFER_SIZE: usize = 100;
-pub struct Coordinator {
+pub struct Coordinator<O: Operation = Action> {
     network_info: NetworkInfo,
-    broadcaster: BroadcastSender<ProposalData>,
-    receiver: MPSCReceiver<ProposalSignedData>,
-    sender: MPSCSender<ProposalSignedData>, // Cannot be used, only to add a proposer.
-    received: ReceivedMap,
+    broadcaster: BroadcastSender<ProposalData<O>>,
+    receiver: MPSCReceiver<ProposalSignedData<O>>,
+    sender: MPSCSender<ProposalSignedData<O>>, // Cannot be used, only to add a proposer.
+    received: ReceivedMap<O>,
     already_received: HashMap<RoundNumber, HashSet<Identity>>,
     validated: HashSet<RoundNumber>, // To discard treated consensus
 }
 
-impl Coordinator {
+impl<O: Operation> Coordinator<O> {
     pub fn new(network_info: NetworkInfo) -> Self {
         let (broadcaster, _) = broadcast::channel(BUFFER_SIZE);
         let (sender, receiver) = mpsc::channel(BUFFER_SIZE);
@@ -44,16 +46,28 @@ impl Coordinator {
         coordinator
     }
 
-    pub fn subscribe(&self) -> BroadcastReceiver<ProposalData> {
+    pub fn subscribe(&self) -> BroadcastReceiver<ProposalData<O>> {
         self.broadcaster.subscribe()
     }
 
-    pub fn proposer(&self) -> MPSCSender<ProposalSignedData> {
+    pub fn proposer(&self) -> MPSCSender<ProposalSignedData<O>> {
         self.sender.clone()
     }
 
-    fn propose(&mut self, data: ProposalSignedData) -> Option<(ProposalSet, ProposalSet)> {
+    /// Records the event in the trace of the network
+    fn trace(&self, event: Event) {
+        self.network_info
+            .tracer()
+            .record(self.network_info.coordinator_id(), None, || event);
+    }
+
+    /// Records the proposal of a replica. Returns the validated (NCSet, CSet) once the round is complete
+    pub fn propose(
+        &mut self,
+        data: ProposalSignedData<O>,
+    ) -> Option<(ProposalSet<O>, ProposalSet<O>)> {
         let (from, k, nc, c) = data;
+        self.trace(Event::Gather(k));
         let is_unique = self
             .already_received
             .get(&k)
@@ -79,7 +93,7 @@ impl Coordinator {
     }
 
     /// Returns Some(NCSet, CSet) if it is validated
-    fn validate(&mut self, k: RoundNumber) -> Option<(ProposalSet, ProposalSet)> {
+    fn validate(&mut self, k: RoundNumber) -> Option<(ProposalSet<O>, ProposalSet<O>)> {
         let is_complete = self.received.get(&k).map(|id_map| {
             //println!("Coordinator received: {}", id_map.len());
             id_map.len() >= self.network_info.n_ack()
@@ -87,61 +101,75 @@ impl Coordinator {
 
         if let Some(true) = is_complete {
             return self.received.remove(&k).map(|id_map| {
-                let values: Vec<(ProposalSet, ProposalSet)> = id_map.into_values().collect();
-                let (non_conflictings, conflictings): (Vec<ProposalSet>, Vec<ProposalSet>) =
-                    values.into_iter().unzip();
-
-                let mut reduced_nc: HashMap<Command, usize> = HashMap::new();
-                let mut reduced_conflicting: BTreeSet<Command> = conflictings.into_iter().fold(
-                    BTreeSet::<Command>::new(),
-                    |mut acc, mut item| {
-                        acc.append(&mut item);
-                        acc
-                    },
-                );
-                let non_conflictings = non_conflictings.into_iter();
-
-                for commands in non_conflictings {
-                    for cmd in commands {
-                        reduced_nc
-                            .entry(cmd)
-                            .and_modify(|nbr| *nbr += 1)
-                            .or_insert(1);
-                    }
-                }
-
-                let reduced_nc = reduced_nc.into_iter();
-                let threshold: usize = self.network_info.n_ack() + 1;
-                let threshold: usize = if threshold % 2 == 0 {
-                    threshold / 2
-                } else {
-                    (threshold + 1) / 2
-                };
-
-                let reduced_non_conflicting: ProposalSet =
-                    reduced_nc.fold(BTreeSet::new(), |mut acc, (command, nbr)| {
-                        if nbr >= threshold {
-                            acc.insert(command);
-                        } else {
-                            reduced_conflicting.insert(command);
-                        }
-                        acc
-                    });
-
-                let reduced_conflicting: ProposalSet = reduced_conflicting
-                    .difference(&reduced_non_conflicting)
-                    .cloned()
-                    .collect();
-
+                let values: Vec<(ProposalSet<O>, ProposalSet<O>)> = id_map.into_values().collect();
                 self.validated.insert(k);
+                self.network_info
+                    .metrics()
+                    .coordinator(|metrics| metrics.rounds_validated += 1);
+                self.trace(Event::Validate(k));
 
-                (reduced_non_conflicting, reduced_conflicting)
+                Self::aggregate(values, self.network_info.n_ack())
             });
         }
 
         None
     }
 
+    /// Reduces the (NCSet, CSet) proposals of a round into the validated (NCSet, CSet).
+    /// A command is kept in the NCSet if it was proposed as non-conflicting by a majority of the
+    /// `n_ack` proposals, otherwise it is moved to the CSet.
+    pub fn aggregate(
+        values: Vec<(ProposalSet<O>, ProposalSet<O>)>,
+        n_ack: usize,
+    ) -> (ProposalSet<O>, ProposalSet<O>) {
+        let (non_conflictings, conflictings): (Vec<ProposalSet<O>>, Vec<ProposalSet<O>>) =
+            values.into_iter().unzip();
+
+        let mut reduced_nc: HashMap<Command<O>, usize> = HashMap::new();
+        let mut reduced_conflicting: ProposalSet<O> =
+            conflictings
+                .into_iter()
+                .fold(BTreeSet::new(), |mut acc, mut item| {
+                    acc.append(&mut item);
+                    acc
+                });
+        let non_conflictings = non_conflictings.into_iter();
+
+        for commands in non_conflictings {
+            for cmd in commands {
+                reduced_nc
+                    .entry(cmd)
+                    .and_modify(|nbr| *nbr += 1)
+                    .or_insert(1);
+            }
+        }
+
+        let reduced_nc = reduced_nc.into_iter();
+        let threshold: usize = n_ack + 1;
+        let threshold: usize = if threshold % 2 == 0 {
+            threshold / 2
+        } else {
+            (threshold + 1) / 2
+        };
+
+        let reduced_non_conflicting: ProposalSet<O> =
+            reduced_nc.fold(BTreeSet::new(), |mut acc, (command, nbr)| {
+                if nbr >= threshold {
+                    acc.insert(command);
+                } else {
+                    reduced_conflicting.insert(command);
+                }
+                acc
+            });
+
+        let reduced_conflicting: ProposalSet<O> = reduced_conflicting
+            .difference(&reduced_non_conflicting)
+            .cloned()
+            .collect();
+
+        (reduced_non_conflicting, reduced_conflicting)
+    }
+
     #[cfg(test)]
     pub fn display_internals(&self) {
         println!(
@@ -153,32 +181,32 @@ impl Coordinator {
     async fn broadcast(
         &self,
         k: RoundNumber,
-        nc_set: BTreeSet<Command>,
-        c_set: BTreeSet<Command>,
-    ) -> Result<usize, SendError<ProposalData>> {
+        nc_set: ProposalSet<O>,
+        c_set: ProposalSet<O>,
+    ) -> Result<usize, SendError<ProposalData<O>>> {
         tokio::time::sleep(Duration::from_secs_f64(
             self.network_info.consensus_transmission_delay(),
         ))
         .await;
         self.broadcaster.send((k, nc_set, c_set))
     }
-    pub fn received(&self) -> &ReceivedMap {
+    pub fn received(&self) -> &ReceivedMap<O> {
         &self.received
     }
 
     #[cfg(test)]
-    pub fn receiver(&mut self) -> &mut MPSCReceiver<ProposalSignedData> {
+    pub fn receiver(&mut self) -> &mut MPSCReceiver<ProposalSignedData<O>> {
         &mut self.receiver
     }
 
     #[cfg(test)]
-    pub fn broadcaster(&self) -> &BroadcastSender<ProposalData> {
+    pub fn broadcaster(&self) -> &BroadcastSender<ProposalData<O>> {
         &self.broadcaster
     }
 }
 
 #[async_trait::async_trait]
-impl Runner for Coordinator {
+impl<O: Operation> Runner for Coordinator<O> {
     async fn run(mut self) {
         while let Some(data) = self.receiver.recv().await {
             //println!("Coordinator received: {:#?}", data);
@@ -194,12 +222,51 @@ impl Runner for Coordinator {
 
 #[cfg(test)]
 mod tests {
+    use rand::Rng;
     use talk::unicast::test::UnicastSystem;
 
-    use crate::{banking::action::Action, network::network::Network, talk::Message};
+    use crate::{
+        banking::action::Action,
+        network::network::Network,
+        relation::{conflict::ConflictingRelation, Relation},
+        talk::Message,
+        tests::generator::Generator,
+    };
 
     use super::*;
 
+    /// Checks that the validated (NCSet, CSet) partition the proposed commands, and that the
+    /// NCSet never contains two related commands
+    fn check_validation(
+        proposals: &[(ProposalSet, ProposalSet)],
+        (nc_set, c_set): &(ProposalSet, ProposalSet),
+    ) -> Result<(), String> {
+        if let Some(command) = nc_set.intersection(c_set).next() {
+            return Err(format!("{:?} is in both the NCSet and the CSet", command));
+        }
+        let proposed: ProposalSet = proposals
+            .iter()
+            .flat_map(|(nc, c)| nc.iter().chain(c.iter()))
+            .cloned()
+            .collect();
+        let validated: ProposalSet = nc_set.union(c_set).cloned().collect();
+        if proposed != validated {
+            return Err(format!(
+                "{:?} are validated instead of {:?}",
+                validated, proposed
+            ));
+        }
+        for x in nc_set.iter() {
+            if let Some(y) = nc_set
+                .iter()
+                .find(|y| ConflictingRelation::is_related(x, y))
+            {
+                return Err(format!("{:?} and {:?} are both in the NCSet", x, y));
+            }
+        }
+        Ok(())
+    }
+
     #[test]
     fn btree_set_equality() {
         let mut set1: BTreeSet<u64> = BTreeSet::new();
@@ -285,7 +352,7 @@ mod tests {
     async fn validate_correctly_handle_conflicting_sets() {
         let network_info = NetworkInfo::with_default_report_folder(5, 5, 0, 0, 10, 4);
         let mut coordinator = Coordinator::new(network_info.clone());
-        let network = Network::setup(network_info).await;
+        let network: Network = Network::setup(network_info).await;
 
         let ids: Vec<Identity> = network.identity_table().replicas().clone();
         let round = 1;
@@ -340,7 +407,7 @@ mod tests {
     async fn does_not_propose_out_of_order() {
         let network_info = NetworkInfo::with_default_report_folder(5, 5, 0, 0, 10, 3);
         let mut coordinator = Coordinator::new(network_info.clone());
-        let network = Network::setup(network_info.clone()).await;
+        let network: Network = Network::setup(network_info.clone()).await;
 
         let ids: Vec<Identity> = network.identity_table().replicas().clone();
         let round = 1;
@@ -386,4 +453,43 @@ mod tests {
 
         assert_eq!(received, coordinator.received.clone());
     }
+
+    #[test]
+    fn aggregate_partitions_the_proposed_commands() {
+        Generator::check(|generator| {
+            let commands = generator.commands(16);
+            let n_ack = generator.rng().gen_range(1..8);
+            let proposals = generator.proposals(&commands, n_ack);
+            let validated = Coordinator::aggregate(proposals.clone(), n_ack);
+            check_validation(&proposals, &validated)
+        });
+    }
+
+    #[tokio::test]
+    async fn validate_partitions_the_proposed_commands() {
+        let network_info = NetworkInfo::with_default_report_folder(5, 5, 0, 0, 10, 4);
+        let UnicastSystem { keys, .. } = UnicastSystem::<Message>::setup(4).await;
+
+        Generator::check(|generator| {
+            let mut coordinator = Coordinator::new(network_info.clone());
+            let commands = generator.commands(16);
+            let proposals = generator.proposals(&commands, network_info.n_ack());
+
+            let mut validated = None;
+            for (key, (nc_set, c_set)) in keys.iter().zip(proposals.iter()) {
+                if validated.is_some() {
+                    return Err(String::from(
+                        "The round is validated before n_ack proposals",
+                    ));
+                }
+                validated = coordinator.propose((key.clone(), 1, nc_set.clone(), c_set.clone()));
+            }
+            match validated {
+                Some(validated) => check_validation(&proposals, &validated),
+                None => Err(String::from(
+                    "The round is not validated after n_ack proposals",
+                )),
+            }
+        });
+    }
 }
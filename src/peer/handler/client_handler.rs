@@ -1,30 +1,56 @@
-use talk::{crypto::Identity, unicast::Acknowledger};
+use std::{collections::HashMap, time::Duration};
+
+use talk::{
+    crypto::{primitives::sign::Signature, Identity},
+    unicast::Acknowledger,
+};
+use tokio::{sync::oneshot, time::sleep};
 
 use crate::{
+    application::Operation,
+    banking::action::Action,
+    crypto::statements::{CommandAcknowledgement, IssuedCommand},
     database::client_database::{ClientDatabase, RequestResult},
-    network::NetworkInfo,
+    network::{trace::Event, NetworkInfo},
     peer::{peer::PeerId, shutdownable::Shutdownable},
-    talk::{Command, CommandId, Feedback, Instruction, Message, Phase},
+    talk::{Command, CommandId, Feedback, Instruction, Message, Phase, Receipt},
 };
 
 use super::{Communicator, Handler};
-pub struct ClientHandler {
-    communicator: Communicator<Message>,
-    database: ClientDatabase,
+
+/// Round trips the fast path takes at most: the command reaches the replicas, which broadcast
+/// their sets to each other and then acknowledge the command
+const ACK_ROUND_TRIPS: u32 = 2;
+
+/// Lower bound of the timeout, which then stays positive on links without delay
+const MIN_ACK_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Upper bound of the timeout once doubled by the successive checks of a command, unless the
+/// first timeout is already longer
+const MAX_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct ClientHandler<O: Operation = Action> {
+    communicator: Communicator<O>,
+    database: ClientDatabase<O>,
+    timeouts: HashMap<CommandId, oneshot::Sender<()>>, // Dropped once the request completes
 }
 
-impl ClientHandler {
-    pub fn new(communicator: Communicator<Message>) -> Self {
+impl<O: Operation> ClientHandler<O> {
+    pub fn new(communicator: Communicator<O>) -> Self {
+        let replicas = communicator.identity_table().replicas().clone();
         ClientHandler {
             communicator,
-            database: ClientDatabase::new(),
+            database: ClientDatabase::new(replicas),
+            timeouts: HashMap::new(),
         }
     }
 
     /// Handling command functions
-    async fn handle_instruction_execute(&mut self, command: Command) {
+    async fn handle_instruction_execute(&mut self, command: Command<O>) {
         let id = command.id().clone();
-        let message = Message::Command(command);
+        let signature = self.communicator.sign(&IssuedCommand { command: &command });
+        let check = Message::CheckCommand(command.clone(), signature);
+        let message = Message::Command(command, signature);
         // Do not execute if there is a db error
         if self.database.contains_request(&id) {
             self.communicator
@@ -37,9 +63,44 @@ impl ClientHandler {
         } else {
             self.database.add_request(id).unwrap();
             self.broadast_to_replicas(&message).await;
+            self.spawn_timeout(id, check);
         }
     }
 
+    /// Longest time a client waits for the acknowledgements of a command before asking the replicas
+    /// to check it, e.g. because a replica is too slow for the fast path
+    fn ack_timeout(network_info: &NetworkInfo) -> Duration {
+        let round_trip = Duration::from_millis(2 * network_info.max_link_delay());
+        (round_trip * ACK_ROUND_TRIPS).max(MIN_ACK_TIMEOUT)
+    }
+
+    /// Broadcasts the check of the request on every expiry of the timeout, until it completes:
+    /// the replicas then decide the command through the consensus, which a quorum of `f + 1`
+    /// acknowledges. Checks lost to a partition are sent again once it heals. The timeout doubles
+    /// on every expiry, as the consensus may take much longer than the fast path.
+    fn spawn_timeout(&mut self, id: CommandId, check: Message<O>) {
+        let (completed, mut completion) = oneshot::channel();
+        self.timeouts.insert(id, completed);
+        let communicator = self.communicator.clone();
+        let mut ack_timeout = Self::ack_timeout(communicator.network_info());
+        let max_timeout = MAX_ACK_TIMEOUT.max(ack_timeout);
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut completion => return,
+                    _ = sleep(ack_timeout) => {
+                        ack_timeout = (ack_timeout * 2).min(max_timeout);
+                        for replica in communicator.identity_table().replicas() {
+                            communicator
+                                .spawn_send_message(replica.clone(), check.clone())
+                                .await;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
     fn handle_instruction_testing(&mut self) {
         self.communicator
             .spawn_send_feedback(Feedback::Acknowledgement(*self.id()));
@@ -49,27 +110,81 @@ impl ClientHandler {
 
     async fn handle_command_acknowledgement(
         &mut self,
+        replica: Identity,
         id: &CommandId,
-        request_result: RequestResult,
+        request_result: RequestResult<O>,
+        signature: Signature,
     ) {
-        let (_, command_result, phase) = request_result.clone();
-        if let Ok(count) = self.database.update_request(id, request_result) {
-            let bound = match phase {
-                Phase::ACK => self.communicator.network_info().n_ack(),
-                Phase::CHK => self.communicator.network_info().nbr_faulty_replicas(),
-            };
+        if !self.verify_acknowledgement(&replica, id, &request_result, &signature) {
+            return;
+        }
 
-            if count >= bound {
+        let (round, command_result, phase) = request_result.clone();
+        if let Ok(count) =
+            self.database
+                .update_request(id, request_result.clone(), replica, signature)
+        {
+            if count >= self.quorum(&phase) {
+                let signatures = self
+                    .database
+                    .votes(id, &request_result)
+                    .map(|votes| {
+                        votes
+                            .iter()
+                            .map(|(replica, signature)| (replica.clone(), *signature))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                self.communicator
+                    .network_info()
+                    .tracer()
+                    .record(*self.id(), None, || Event::Complete {
+                        command: *id,
+                        round,
+                        phase: phase.clone(),
+                    });
+                let receipt = Receipt::new(*id, round, command_result, phase, signatures);
                 self.database.complete_request(id).unwrap();
+                self.timeouts.remove(id);
                 self.communicator
-                    .send_feedback(Feedback::Result(*self.id(), command_result))
+                    .send_feedback(Feedback::Result(*self.id(), receipt))
                     .await
                     .unwrap();
             }
         }
     }
 
-    fn handle_message_testing(&self, message: &Message) {
+    /// Returns true if the acknowledgement is signed by its sender.
+    /// Whether the sender is a replica is checked by the database.
+    fn verify_acknowledgement(
+        &self,
+        replica: &Identity,
+        id: &CommandId,
+        (round, command_result, phase): &RequestResult<O>,
+        signature: &Signature,
+    ) -> bool {
+        let identity_table = self.communicator.identity_table();
+        let statement = CommandAcknowledgement {
+            id,
+            round,
+            result: command_result,
+            phase,
+        };
+        identity_table
+            .keycard(replica)
+            .map(|keycard| signature.verify(keycard, &statement).is_ok())
+            .unwrap_or(false)
+    }
+
+    /// Number of distinct replicas that must acknowledge a result during the given phase
+    fn quorum(&self, phase: &Phase) -> usize {
+        match phase {
+            Phase::ACK => self.communicator.network_info().n_ack(),
+            Phase::CHK => self.communicator.network_info().f() + 1,
+        }
+    }
+
+    fn handle_message_testing(&self, message: &Message<O>) {
         println!(
             "Client #{} receives {:?} during the test",
             self.communicator.id(),
@@ -77,7 +192,7 @@ impl ClientHandler {
         );
     }
 
-    async fn broadast_to_replicas(&self, message: &Message) {
+    async fn broadast_to_replicas(&self, message: &Message<O>) {
         for replica in self.communicator.identity_table().replicas() {
             let _spawn = self
                 .communicator
@@ -87,21 +202,26 @@ impl ClientHandler {
     }
 }
 #[async_trait::async_trait]
-impl Handler<Message> for ClientHandler {
-    async fn handle_message(&mut self, _id: Identity, message: Message, _ack: Acknowledger) {
+impl<O: Operation> Handler<O> for ClientHandler<O> {
+    async fn handle_message(&mut self, id: Identity, message: Message<O>, _ack: Acknowledger) {
         match message {
             Message::Testing => {
                 self.handle_message_testing(&message);
             }
-            Message::CommandAcknowledgement(command, round, command_result, phase) => {
-                self.handle_command_acknowledgement(command.id(), (round, command_result, phase))
-                    .await;
+            Message::CommandAcknowledgement(command, round, command_result, phase, signature) => {
+                self.handle_command_acknowledgement(
+                    id,
+                    command.id(),
+                    (round, command_result, phase),
+                    signature,
+                )
+                .await;
             }
             _ => {}
         }
     }
 
-    async fn handle_instruction(&mut self, instruction: Instruction) {
+    async fn handle_instruction(&mut self, instruction: Instruction<O>) {
         match instruction {
             Instruction::Execute(command) => self.handle_instruction_execute(command).await,
             Instruction::Testing => self.handle_instruction_testing(),
@@ -123,12 +243,12 @@ mod tests {
     use std::time::Duration;
 
     use talk::sync::fuse::Fuse;
-    use tokio::time::timeout;
+    use tokio::time::{timeout, Instant};
 
     use crate::{
         banking::action::Action,
         crypto::identity_table::IdentityTableBuilder,
-        network::NetworkInfo,
+        network::{DelayDistribution, NetworkInfo, Topology},
         peer::handler::{Communicator, Handler},
         talk::{Command, CommandResult, FeedbackChannel, Message},
         tests::util::Utils,
@@ -149,14 +269,16 @@ mod tests {
 
         let (rx, mut tx) = FeedbackChannel::channel();
         let client_id = client.clone();
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(client.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .build();
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&client))
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2));
+        let identity_table = identity_table_builder.build();
         let mut client = ClientHandler::new(Communicator::new(
             0,
-            client,
+            client.clone(),
+            identity_table_builder.keychain(&client).unwrap(),
             sender,
             rx,
             network_info.clone(),
@@ -167,7 +289,13 @@ mod tests {
         // Test broadcast to replicas as well
         let cmd = Command::new(0, Action::Deposit(10));
         client.handle_instruction_execute(cmd.clone()).await;
-        let message = Message::Command(cmd.clone());
+        let signature = identity_table_builder
+            .keychain(&client_id)
+            .unwrap()
+            .sign(&IssuedCommand { command: &cmd })
+            .ok();
+        assert_eq!(signature.is_some(), true);
+        let message = Message::Command(cmd.clone(), signature);
         let (id1, msg1, _) = timeout(Duration::from_secs(2), receiver1.receive())
             .await
             .unwrap();
@@ -186,92 +314,176 @@ mod tests {
         println!("{:?}", err);
     }
 
-    #[tokio::test]
-    async fn correclty_handle_request() {
-        let network_info = NetworkInfo::with_default_report_folder(1, 2, 0, 0, 10, 7);
+    #[test]
+    fn ack_timeout_scales_with_the_transmission_delay() {
+        let ack_timeout = |transmission_delay| {
+            let network_info =
+                NetworkInfo::with_default_report_folder(1, 4, 0, 0, transmission_delay, 4);
+            ClientHandler::<Action>::ack_timeout(&network_info)
+        };
+        // The delays follow a Poisson distribution of mean `transmission_delay`
+        assert!(ack_timeout(1) < ack_timeout(10));
+        assert!(ack_timeout(10) < ack_timeout(100));
+        assert!(ack_timeout(100) < ack_timeout(300));
+        assert!(ack_timeout(10) < Duration::from_millis(100));
+        // Sampled delays are bounded, and so is the timeout
+        assert_eq!(ack_timeout(5000), ack_timeout(10_000));
+
+        let mut network_info = NetworkInfo::with_default_report_folder(1, 4, 0, 0, 10, 4);
+        let base = ClientHandler::<Action>::ack_timeout(&network_info);
+        network_info
+            .set_topology(Topology::Regions {
+                regions: vec![0, 0, 1, 1, 1],
+                rtts: vec![vec![2, 400], vec![400, 2]],
+            })
+            .unwrap();
+        let regions = ClientHandler::<Action>::ack_timeout(&network_info);
+        assert_eq!(regions - base, Duration::from_millis(2 * 200) * ACK_ROUND_TRIPS);
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn checks_are_sent_again_once_a_partition_heals() {
+        let mut network_info = NetworkInfo::with_default_report_folder(1, 2, 0, 0, 10, 2);
+        network_info.set_transmission_delay_distribution(DelayDistribution::Constant(10));
+        let ack_timeout = ClientHandler::<Action>::ack_timeout(&network_info);
+        assert_eq!(ack_timeout, Duration::from_millis(40));
+
         let (mut keys, mut senders, mut receivers) = Utils::mock_network(3).await;
-        let (client, sender, mut receiver) = Utils::pop(&mut keys, &mut senders, &mut receivers);
-        let (replica1, sender1, mut _receiver1) =
-            Utils::pop(&mut keys, &mut senders, &mut receivers);
-        let (replica2, _sender2, mut _receiver2) =
+        let (client, sender, mut _receiver) = Utils::pop(&mut keys, &mut senders, &mut receivers);
+        let (replica1, _sender1, mut receiver1) =
             Utils::pop(&mut keys, &mut senders, &mut receivers);
+        let (replica2, _sender2, _receiver2) = Utils::pop(&mut keys, &mut senders, &mut receivers);
+
+        let (rx, _tx) = FeedbackChannel::channel();
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&client))
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2));
+        let identity_table = identity_table_builder.build();
+        let keychain = identity_table_builder.keychain(&client).unwrap();
+        let mut client = ClientHandler::new(Communicator::new(
+            0,
+            client.clone(),
+            keychain.clone(),
+            sender,
+            rx,
+            network_info.clone(),
+            identity_table,
+        ));
+
+        // The partition holds the messages until it heals: the lossy links lose them instead
+        let faults = network_info.faults();
+        faults.partition(vec![vec![0], vec![1, 2]], None);
+        faults.set_drop_probability(1.0);
+
+        let start = Instant::now();
+        let cmd = Command::new(0, Action::Deposit(10));
+        client.handle_instruction_execute(cmd.clone()).await;
+        // The command and the first check are lost
+        tokio::time::sleep(ack_timeout + Duration::from_millis(1)).await;
+        faults.heal();
+        faults.set_drop_probability(0.0);
+
+        let signature = keychain.sign(&IssuedCommand { command: &cmd }).ok();
+        let (_, message, _) = timeout(Duration::from_secs(10), receiver1.receive())
+            .await
+            .expect("The check was not sent again");
+        assert_eq!(message, Message::CheckCommand(cmd.clone(), signature));
+        // Sent on the second expiry of the timeout, which doubled, after the partition healed
+        assert!(start.elapsed() >= 3 * ack_timeout);
+    }
+
+    #[tokio::test]
+    async fn correclty_handle_request() {
+        let network_info = NetworkInfo::with_default_report_folder(1, 8, 0, 1, 10, 7);
+        let (keys, senders, mut receivers) = Utils::mock_network(10).await;
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        for key in keys.iter() {
+            identity_table_builder.add_peer(Utils::keychain(&key));
+        }
+        let identity_table = identity_table_builder.build();
 
+        let mut senders = senders.into_iter();
         let (rx, mut tx) = FeedbackChannel::channel();
-        let client_id = client.clone();
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(client.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .build();
+        let client_id = keys[0].clone();
         let mut client = ClientHandler::new(Communicator::new(
             0,
-            client,
-            sender,
+            client_id.clone(),
+            identity_table_builder.keychain(&client_id).unwrap(),
+            senders.next().unwrap(),
             rx,
             network_info.clone(),
             identity_table.clone(),
         ));
+        let mut receiver = receivers.remove(0);
+        let replicas = keys[1..].to_vec();
+        // The key material of each replica, as the builder hands it to the replica itself
+        let replica_keychains = replicas
+            .iter()
+            .map(|replica| identity_table_builder.keychain(replica).unwrap())
+            .collect::<Vec<_>>();
+        let replica_senders = senders.collect::<Vec<_>>();
 
-        let cmd = Command::new(0, Action::Register);
-        client.database.add_request(cmd.id().clone()).unwrap();
-
-        // Case 1: Receives a bunch of requests with wrong round numbers
         let fuse = Fuse::new();
-        for i in 10..100 {
-            sender1.spawn_send(
+        // `replica` acknowledges the command, with a signature made by `signer`
+        let acknowledge = |replica: usize, signer: usize, cmd: &Command, round, phase: Phase| {
+            let result = CommandResult::Success(None);
+            let statement = CommandAcknowledgement {
+                id: cmd.id(),
+                round: &round,
+                result: &result,
+                phase: &phase,
+            };
+            let signature = replica_keychains[signer].sign(&statement).unwrap();
+            replica_senders[replica].spawn_send(
                 client_id.clone(),
-                Message::CommandAcknowledgement(
-                    cmd.clone(),
-                    i,
-                    CommandResult::Success(None),
-                    Phase::ACK,
-                ),
+                Message::CommandAcknowledgement(cmd.clone(), round, result, phase, signature),
                 &fuse,
             );
-        }
+        };
 
-        for _ in 10..100 {
-            let future = receiver.receive();
-            timeout(Duration::from_millis(100), future)
+        // Case 1: Receives a bunch of requests with wrong round numbers
+        let cmd = Command::new(0, Action::Register);
+        client.database.add_request(cmd.id().clone()).unwrap();
+        for i in 10..100 {
+            acknowledge(0, 0, &cmd, i, Phase::ACK);
+            let (id, message, ack) = timeout(Duration::from_millis(100), receiver.receive())
                 .await
                 .expect("Error when sending");
+            client.handle_message(id, message, ack).await;
         }
 
-        for (_, v) in client
+        for (_, votes) in client
             .database
             .requests()
             .get(cmd.id())
             .expect("Failed to find the request")
             .iter()
         {
-            assert_eq!(*v, 1);
+            assert_eq!(votes.len(), 1);
         }
 
-        // Case 2: Need exactly 7 ACK
+        // Case 2: A replica votes at most once, and forged signatures are ignored
+        let cmd = Command::new(0, Action::Register);
+        client.database.add_request(cmd.id().clone()).unwrap();
+        for (replica, signer) in [(0, 0), (0, 0), (1, 2), (2, 1)] {
+            acknowledge(replica, signer, &cmd, 0, Phase::ACK);
+            let (id, message, ack) = timeout(Duration::from_millis(100), receiver.receive())
+                .await
+                .expect("Timeout");
+            client.handle_message(id, message, ack).await;
+        }
+        let request = (0, CommandResult::Success(None), Phase::ACK);
+        assert_eq!(client.database.votes(cmd.id(), &request).unwrap().len(), 1);
+
+        // Case 3: Need exactly 7 ACK from distinct replicas
         let cmd = Command::new(0, Action::Register);
         client.database.add_request(cmd.id().clone()).unwrap();
 
         let mut i = 0;
-        let mut feedback: Feedback = Feedback::Error(*client.id(), String::new());
-        while {
-            match timeout(Duration::from_millis(100), tx.recv()).await {
-                Ok(v) => {
-                    feedback = v.unwrap();
-                    false
-                }
-                Err(_) => true,
-            }
-        } {
-            sender1.spawn_send(
-                client_id.clone(),
-                Message::CommandAcknowledgement(
-                    cmd.clone(),
-                    0,
-                    CommandResult::Success(None),
-                    Phase::ACK,
-                ),
-                &fuse,
-            );
+        while client.database.contains_request(cmd.id()) {
+            acknowledge(i, i, &cmd, 0, Phase::ACK);
             let (id, message, ack) = timeout(Duration::from_millis(100), receiver.receive())
                 .await
                 .expect("Timeout");
@@ -280,27 +492,35 @@ mod tests {
         }
 
         assert_eq!(i, network_info.n_ack());
-        assert_eq!(
-            feedback,
-            Feedback::Result(*client.id(), CommandResult::Success(None))
-        );
+        let feedback = timeout(Duration::from_secs(1), tx.recv())
+            .await
+            .expect("Timeout fb")
+            .unwrap();
+        match feedback {
+            Feedback::Result(id, receipt) => {
+                assert_eq!(id, *client.id());
+                assert_eq!(*receipt.result(), CommandResult::Success(None));
+                assert_eq!(receipt.signatures().len(), network_info.n_ack());
+                let voters = receipt.voters();
+                for replica in replicas[..network_info.n_ack()].iter() {
+                    assert_eq!(voters.contains(replica), true);
+                }
+                assert_eq!(receipt.verify(&identity_table, network_info.n_ack()), true);
+                assert_eq!(
+                    receipt.verify(&identity_table, network_info.n_ack() + 1),
+                    false
+                );
+            }
+            _ => panic!("Expected a result"),
+        }
 
-        // Needs exactly 2 + 1 CHK
+        // Needs exactly f + 1 CHK
         let cmd = Command::new(0, Action::Register);
         client.database.add_request(cmd.id().clone()).unwrap();
 
         let mut i = 0;
         while client.database.contains_request(cmd.id()) {
-            sender1.spawn_send(
-                client_id.clone(),
-                Message::CommandAcknowledgement(
-                    cmd.clone(),
-                    0,
-                    CommandResult::Success(None),
-                    Phase::CHK,
-                ),
-                &fuse,
-            );
+            acknowledge(i, i, &cmd, 0, Phase::CHK);
             let (id, message, ack) = timeout(Duration::from_millis(100), receiver.receive())
                 .await
                 .expect("Timeout");
@@ -313,9 +533,12 @@ mod tests {
             .await
             .expect("Timeout fb")
             .unwrap();
-        assert_eq!(
-            res,
-            Feedback::Result(*client.id(), CommandResult::Success(None))
-        );
+        match res {
+            Feedback::Result(_, receipt) => {
+                assert_eq!(*receipt.phase(), Phase::CHK);
+                assert_eq!(receipt.verify(&identity_table, network_info.f() + 1), true);
+            }
+            _ => panic!("Expected a result"),
+        }
     }
 }
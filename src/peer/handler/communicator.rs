@@ -1,50 +1,82 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use doomstack::Top;
 
 use talk::{
-    crypto::Identity,
+    crypto::{primitives::sign::Signature, Identity, KeyChain, Statement},
     sync::fuse::Fuse,
     unicast::{Acknowledgement, SenderError},
 };
 use tokio::{task::JoinHandle, time::sleep};
 
 use crate::{
+    application::Operation,
+    banking::action::Action,
     crypto::identity_table::IdentityTable,
-    network::NetworkInfo,
+    network::{
+        fault_injector::{Fate, FaultInjector},
+        trace::{Event, Tracer},
+        NetworkInfo,
+    },
     peer::{peer::PeerId, shutdownable::Shutdownable},
-    talk::{Feedback, FeedbackSender},
+    talk::{Feedback, FeedbackSender, Message},
     types::*,
 };
 
-pub struct Communicator<T>
-where
-    T: UnicastMessage,
-{
+/// One result per copy of a message delivered by the network: none if the message was lost
+pub type Deliveries = Vec<Result<Acknowledgement, Top<SenderError>>>;
+
+/// How the network carries a message
+struct Transmission {
+    from: PeerId,
+    to: Option<PeerId>,
+    delay: u64,
+    fate: Fate,
+    faults: FaultInjector,
+    tracer: Tracer,
+}
+
+/// Sends the `Message` of a peer through the network, and its `Feedback` to the network
+pub struct Communicator<O: Operation = Action> {
     id: PeerId,
     key: Identity,
-    sender: UnicastSender<T>,
-    feedback_inlet: FeedbackSender,
+    keychain: Arc<KeyChain>,
+    sender: UnicastSender<Message<O>>,
+    feedback_inlet: FeedbackSender<O>,
     network_info: NetworkInfo,
     identity_table: IdentityTable,
     _fuse: Fuse,
 }
 
-impl<T> Communicator<T>
-where
-    T: UnicastMessage + Clone,
-{
+/// Copy sending through the same network channel, e.g. for the consensus instance of a replica
+impl<O: Operation> Clone for Communicator<O> {
+    fn clone(&self) -> Self {
+        Communicator::new(
+            self.id,
+            self.key.clone(),
+            self.keychain.clone(),
+            self.sender.clone(),
+            self.feedback_inlet.clone(),
+            self.network_info.clone(),
+            self.identity_table.clone(),
+        )
+    }
+}
+
+impl<O: Operation> Communicator<O> {
     pub fn new(
         id: PeerId,
         key: Identity,
-        sender: UnicastSender<T>,
-        feedback_inlet: FeedbackSender,
+        keychain: Arc<KeyChain>,
+        sender: UnicastSender<Message<O>>,
+        feedback_inlet: FeedbackSender<O>,
         network_info: NetworkInfo,
         identity_table: IdentityTable,
     ) -> Self {
         Communicator {
             id,
             key,
+            keychain,
             sender,
             feedback_inlet,
             network_info,
@@ -57,39 +89,41 @@ where
         &self.key
     }
 
-    pub async fn send_message(
-        &self,
-        remote: Identity,
-        message: T,
-    ) -> Result<Acknowledgement, Top<SenderError>> {
-        Self::transmit(self.network_info().transmission_delay()).await;
-        self.sender.send(remote, message).await
+    /// Signs the statement with the `KeyChain` of this peer, which no other peer holds.
+    /// Returns `None` if the statement cannot be signed.
+    pub fn sign<S: Statement>(&self, statement: &S) -> Option<Signature> {
+        self.keychain.sign(statement).ok()
+    }
+
+    pub async fn send_message(&self, remote: Identity, message: Message<O>) -> Deliveries {
+        let transmission = self.transmission(&remote, &message);
+        Self::deliver(self.sender.clone(), transmission, remote, message).await
     }
 
     pub async fn spawn_send_message(
         &self,
         remote: Identity,
-        message: T,
-    ) -> JoinHandle<Result<Acknowledgement, Top<SenderError>>> {
+        message: Message<O>,
+    ) -> JoinHandle<Deliveries> {
         let sender = self.sender.clone();
-        let delay = self.network_info().transmission_delay();
-        Self::transmit(delay).await;
-        tokio::spawn(async move { sender.send(remote, message).await })
+        // The delay is applied inside the task: messages sent in a row may be delivered in any order
+        let transmission = self.transmission(&remote, &message);
+        tokio::spawn(async move { Self::deliver(sender, transmission, remote, message).await })
     }
 
     // Sends the feedback on the current thread
     pub async fn send_feedback(
         &self,
-        feedback: Feedback,
-    ) -> Result<(), tokio::sync::mpsc::error::SendError<Feedback>> {
+        feedback: Feedback<O>,
+    ) -> Result<(), tokio::sync::mpsc::error::SendError<Feedback<O>>> {
         self.feedback_inlet.send(feedback).await
     }
 
     /// Spawns a tokio Task to send the feedback
     pub fn spawn_send_feedback(
         &self,
-        feedback: Feedback,
-    ) -> JoinHandle<Result<(), tokio::sync::mpsc::error::SendError<Feedback>>> {
+        feedback: Feedback<O>,
+    ) -> JoinHandle<Result<(), tokio::sync::mpsc::error::SendError<Feedback<O>>>> {
         let sender = self.feedback_inlet.clone();
         tokio::spawn(async move { sender.send(feedback).await })
     }
@@ -106,16 +140,84 @@ where
         &self.network_info
     }
 
+    /// Samples the delay of a message sent to `remote`, following the topology of the network,
+    /// and draws its fate from the fault-injection layer
+    fn transmission(&self, remote: &Identity, message: &Message<O>) -> Transmission {
+        let to = self.identity_table.peer_id(remote);
+        let faults = self.network_info.faults().clone();
+        let delay = match to {
+            Some(to) => {
+                self.network_info.sample_link_delay(self.id, to) + faults.extra_delay(self.id, to)
+            }
+            None => self.network_info.sample_transmission_delay(),
+        };
+        let fate = self.network_info.with_rng(|rng| faults.fate(rng));
+        let tracer = self.network_info.tracer().clone();
+        tracer.record(self.id, None, || Event::Send {
+            to,
+            delay,
+            message: Tracer::value(message),
+        });
+        Transmission {
+            from: self.id,
+            to,
+            delay,
+            fate,
+            faults,
+            tracer,
+        }
+    }
+
+    /// Delivers the message once the delay has elapsed and the peers are not partitioned anymore.
+    /// Messages from or to a crashed peer are lost.
+    async fn deliver(
+        sender: UnicastSender<Message<O>>,
+        transmission: Transmission,
+        remote: Identity,
+        message: Message<O>,
+    ) -> Deliveries {
+        Self::transmit(transmission.delay).await;
+        let faults = &transmission.faults;
+        let (from, to) = (transmission.from, transmission.to);
+        let lost = || Event::Lost {
+            to,
+            message: Tracer::value(&message),
+        };
+        if let Some(to) = to {
+            faults.wait_until_connected(from, to).await;
+            if faults.is_crashed(from) || faults.is_crashed(to) {
+                transmission.tracer.record(from, None, lost);
+                return Vec::new();
+            }
+        }
+        let copies = match transmission.fate {
+            Fate::Lost => {
+                transmission.tracer.record(from, None, lost);
+                0
+            }
+            Fate::Delivered(copies) => copies,
+        };
+        let mut deliveries = Vec::with_capacity(copies);
+        for _ in 0..copies {
+            let delivery = sender.send(remote.clone(), message.clone()).await;
+            if let (Ok(_), Some(to)) = (&delivery, to) {
+                transmission.tracer.record(to, None, || Event::Receive {
+                    from,
+                    message: Tracer::value(&message),
+                });
+            }
+            deliveries.push(delivery);
+        }
+        deliveries
+    }
+
     async fn transmit(delay: u64) {
         sleep(Duration::from_millis(delay)).await;
     }
 }
 
 #[async_trait::async_trait]
-impl<T> Shutdownable for Communicator<T>
-where
-    T: UnicastMessage + Clone,
-{
+impl<O: Operation> Shutdownable for Communicator<O> {
     async fn shutdown(&mut self) {
         self.send_feedback(Feedback::ShutdownComplete(self.id))
             .await
@@ -146,6 +248,7 @@ mod tests {
         let communicator = Communicator::new(
             0,
             key.clone(),
+            Arc::new(Utils::keychain(&key)),
             sender,
             rx,
             network_info.clone(),
@@ -160,4 +263,42 @@ mod tests {
         assert_eq!(id, key.clone());
         assert_eq!(recv, Message::Testing);
     }
+
+    #[tokio::test]
+    async fn faults_drop_and_duplicate_messages() {
+        let network_info = NetworkInfo::with_default_report_folder(0, 0, 0, 0, 1, 0);
+        let (mut keys, mut senders, mut receivers) = Utils::mock_network(2).await;
+        let (key, sender, _) = Utils::pop(&mut keys, &mut senders, &mut receivers);
+        let (target, _, mut receiver) = Utils::pop(&mut keys, &mut senders, &mut receivers);
+        let (rx, mut _tx) = FeedbackChannel::channel();
+        let communicator = Communicator::new(
+            0,
+            key.clone(),
+            Arc::new(Utils::keychain(&key)),
+            sender,
+            rx,
+            network_info.clone(),
+            IdentityTableBuilder::new(network_info.clone()).build(),
+        );
+
+        network_info.faults().set_drop_probability(1.0);
+        let deliveries = communicator
+            .send_message(target.clone(), Message::Testing)
+            .await;
+        assert!(deliveries.is_empty());
+        let lost = tokio::time::timeout(Duration::from_millis(100), receiver.receive()).await;
+        assert!(lost.is_err());
+
+        network_info.faults().set_drop_probability(0.0);
+        network_info.faults().set_duplicate_probability(1.0);
+        let deliveries = communicator
+            .send_message(target.clone(), Message::Testing)
+            .await;
+        assert_eq!(deliveries.len(), 2);
+        for _ in 0..2 {
+            let (id, recv, _) = receiver.receive().await;
+            assert_eq!(id, key.clone());
+            assert_eq!(recv, Message::Testing);
+        }
+    }
 }
@@ -1,20 +1,61 @@
+use serde::{Deserialize, Serialize};
 use talk::{crypto::Identity, unicast::Acknowledger};
 
 use crate::{
+    application::Application,
+    banking::banking::Banking,
+    crypto::statements::IssuedCommand,
     network::NetworkInfo,
     peer::{peer::PeerId, shutdownable::Shutdownable},
-    talk::{Instruction, Message},
+    talk::{Command, Feedback, Instruction, Message},
 };
 
 use super::{Communicator, Handler};
 
-pub struct FaultyClientHandler {
-    communicator: Communicator<Message>,
+/// Adversarial behavior of a faulty client, selected per client through the `NetworkInfo`.
+/// A faulty client does not wait for the results: it acknowledges every instruction
+/// as soon as its commands are sent.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FaultyClientStrategy {
+    /// Ignores every instruction
+    #[default]
+    Idle,
+    /// Sends the given number of commands that conflict with every command, along with it,
+    /// to force the replicas on the slow path
+    Flood(usize),
+    /// Sends the command to half of the replicas, and a different command
+    /// with the same `CommandId` to the other half
+    DuplicateIds,
+    /// Sends the command along with every command it previously sent
+    Replay,
+    /// Sends the command only to the given number of replicas
+    Partial(usize),
 }
 
-impl FaultyClientHandler {
-    pub fn new(communicator: Communicator<Message>) -> Self {
-        FaultyClientHandler { communicator }
+/// Application whose faulty clients can issue commands that conflict with their own
+pub trait Adversarial: Application {
+    /// Action that conflicts with `action` when issued by the same client, e.g. to flood the
+    /// replicas with conflicting commands
+    fn conflicting(action: &Self::Action) -> Self::Action;
+}
+
+pub struct FaultyClientHandler<A: Adversarial = Banking> {
+    communicator: Communicator<A::Action>,
+    strategy: FaultyClientStrategy,
+    sent: Vec<Command<A::Action>>,
+}
+
+impl<A: Adversarial> FaultyClientHandler<A> {
+    pub fn new(communicator: Communicator<A::Action>, strategy: FaultyClientStrategy) -> Self {
+        FaultyClientHandler {
+            communicator,
+            strategy,
+            sent: Vec::new(),
+        }
+    }
+
+    pub fn strategy(&self) -> &FaultyClientStrategy {
+        &self.strategy
     }
 
     fn handle_message_testing(&self) {
@@ -23,18 +64,75 @@ impl FaultyClientHandler {
             self.communicator.id()
         );
     }
+
+    async fn handle_instruction_execute(&mut self, command: Command<A::Action>) {
+        let replicas = self.communicator.identity_table().replicas().clone();
+        match self.strategy.clone() {
+            FaultyClientStrategy::Idle => {}
+            FaultyClientStrategy::Flood(nbr) => {
+                self.send(&command, &replicas).await;
+                for _ in 0..nbr {
+                    let id = self.network_info().generate_command_id();
+                    let conflicting =
+                        Command::with_id(id, *self.id(), A::conflicting(command.action()));
+                    self.send(&conflicting, &replicas).await;
+                }
+            }
+            FaultyClientStrategy::DuplicateIds => {
+                let (first, second) = replicas.split_at(replicas.len() / 2);
+                let twin = Command::with_id(
+                    *command.id(),
+                    *command.issuer(),
+                    A::conflicting(command.action()),
+                );
+                self.send(&command, first).await;
+                self.send(&twin, second).await;
+            }
+            FaultyClientStrategy::Replay => {
+                for old in self.sent.clone().iter() {
+                    self.send(old, &replicas).await;
+                }
+                self.send(&command, &replicas).await;
+            }
+            FaultyClientStrategy::Partial(nbr) => {
+                let nbr = nbr.min(replicas.len());
+                self.send(&command, &replicas[..nbr]).await;
+            }
+        }
+        self.sent.push(command);
+        self.communicator
+            .spawn_send_feedback(Feedback::Acknowledgement(*self.id()));
+    }
+
+    /// Signs the command and sends it to the given replicas
+    async fn send(&self, command: &Command<A::Action>, replicas: &[Identity]) {
+        let signature = self.communicator.sign(&IssuedCommand { command });
+        let message = Message::Command(command.clone(), signature);
+        for replica in replicas.iter() {
+            let _spawn = self
+                .communicator
+                .spawn_send_message(replica.clone(), message.clone())
+                .await;
+        }
+    }
 }
 
 #[async_trait::async_trait]
-impl Handler<Message> for FaultyClientHandler {
-    async fn handle_message(&mut self, _id: Identity, message: Message, _ack: Acknowledger) {
+impl<A: Adversarial> Handler<A::Action> for FaultyClientHandler<A> {
+    async fn handle_message(
+        &mut self,
+        _id: Identity,
+        message: Message<A::Action>,
+        _ack: Acknowledger,
+    ) {
         match message {
             Message::Testing => self.handle_message_testing(),
             _ => (),
         }
     }
-    async fn handle_instruction(&mut self, instruction: Instruction) {
+    async fn handle_instruction(&mut self, instruction: Instruction<A::Action>) {
         match instruction {
+            Instruction::Execute(command) => self.handle_instruction_execute(command).await,
             Instruction::Shutdown => self.communicator.shutdown().await,
             _ => {}
         }
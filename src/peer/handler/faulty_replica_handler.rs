@@ -1,38 +1,168 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
 use talk::{crypto::Identity, unicast::Acknowledger};
 
 use crate::{
+    application::{Application, Operation},
+    banking::banking::Banking,
+    database::replica_database::{Set, SignedSet},
     network::NetworkInfo,
-    peer::peer::PeerId,
-    talk::{Instruction, Message},
+    peer::{coordinator::ProposalSignedData, peer::PeerId},
+    talk::{Command, CommandResult, Instruction, Message},
+    types::ConsensusInlet,
 };
 
 use super::{Handler, ReplicaHandler};
 
-pub struct FaultyReplicaHandler {
-    replica_handler: ReplicaHandler,
+/// Byzantine behavior of a faulty replica, selected per replica through the `NetworkInfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FaultyReplicaStrategy {
+    /// Ignores every message, as a crashed replica. Its consensus instance stays silent too.
+    #[default]
+    Crash,
+    /// Acknowledges the right result to the clients with an even identifier,
+    /// and a wrong one to the others
+    EquivocateAcknowledgements,
+    /// Broadcasts a different truncated set to each replica
+    TruncateBroadcasts,
+    /// Adds to its broadcasts a new command for each relayed one, with the same issuer and action
+    /// but a fresh identifier, signed with the signature of the relayed command
+    ForgeCommands,
+    /// Sends the value of its consensus messages to the replicas with an even index, and a
+    /// conflicting one to the others. As the leader of a view, it thus pre-prepares two values.
+    /// Only affects the `Pbft` consensus.
+    EquivocateConsensus,
+    /// Acknowledges a wrong result to every client
+    WrongResults,
+    /// Proposes every command as conflicting to the consensus
+    GarbageProposals,
+    /// Holds the acknowledgements to the clients with an odd identifier
+    /// for the given number of milliseconds
+    Delay(u64),
+}
+
+impl FaultyReplicaStrategy {
+    /// Returns the result to acknowledge to the client #`issuer`, and how long to hold it
+    pub(super) fn acknowledgement<O: Operation>(
+        &self,
+        issuer: PeerId,
+        result: CommandResult<O>,
+    ) -> (CommandResult<O>, Option<Duration>) {
+        match self {
+            FaultyReplicaStrategy::WrongResults => (Self::wrong_result(result), None),
+            FaultyReplicaStrategy::EquivocateAcknowledgements if issuer % 2 == 1 => {
+                (Self::wrong_result(result), None)
+            }
+            FaultyReplicaStrategy::Delay(delay) if issuer % 2 == 1 => {
+                (result, Some(Duration::from_millis(*delay)))
+            }
+            _ => (result, None),
+        }
+    }
+
+    /// Returns the set to broadcast to the `index`-th replica
+    pub(super) fn broadcast<O: Operation>(&self, set: &SignedSet<O>, index: usize) -> SignedSet<O> {
+        match self {
+            FaultyReplicaStrategy::TruncateBroadcasts => set
+                .iter()
+                .skip(index)
+                .map(|(command, signature)| (command.clone(), *signature))
+                .collect(),
+            FaultyReplicaStrategy::ForgeCommands => {
+                let mut forged = set.clone();
+                for (command, signature) in set.iter() {
+                    let command = Command::new(*command.issuer(), command.action().clone());
+                    forged.insert(command, *signature);
+                }
+                forged
+            }
+            _ => set.clone(),
+        }
+    }
+
+    /// Returns the (NCSet, CSet) to send to the `index`-th replica in a consensus message
+    pub(crate) fn consensus_value<O: Operation>(
+        &self,
+        nc_set: Set<O>,
+        c_set: Set<O>,
+        index: usize,
+    ) -> (Set<O>, Set<O>) {
+        match self {
+            FaultyReplicaStrategy::EquivocateConsensus if index % 2 == 1 => {
+                let (mut nc_set, mut c_set) = (nc_set, c_set);
+                c_set.append(&mut nc_set);
+                (nc_set, c_set)
+            }
+            _ => (nc_set, c_set),
+        }
+    }
+
+    pub(super) fn proposal<O: Operation>(
+        &self,
+        data: ProposalSignedData<O>,
+    ) -> ProposalSignedData<O> {
+        match self {
+            FaultyReplicaStrategy::GarbageProposals => {
+                let (key, k, mut nc_set, mut c_set) = data;
+                c_set.append(&mut nc_set);
+                (key, k, nc_set, c_set)
+            }
+            _ => data,
+        }
+    }
+
+    /// Returns a result that differs from `result`, whatever the application
+    fn wrong_result<O: Operation>(result: CommandResult<O>) -> CommandResult<O> {
+        match result {
+            CommandResult::Success(Some(_)) => CommandResult::Success(None),
+            CommandResult::Success(None) => CommandResult::Failure(String::from("Forged")),
+            CommandResult::Failure(_) => CommandResult::Success(None),
+        }
+    }
+}
+
+pub struct FaultyReplicaHandler<A: Application = Banking> {
+    replica_handler: ReplicaHandler<A>,
+    strategy: FaultyReplicaStrategy,
 }
 
-impl FaultyReplicaHandler {
-    pub fn new(replica_handler: ReplicaHandler) -> Self {
-        FaultyReplicaHandler { replica_handler }
+impl<A: Application> FaultyReplicaHandler<A> {
+    pub fn new(mut replica_handler: ReplicaHandler<A>, strategy: FaultyReplicaStrategy) -> Self {
+        replica_handler.corrupt(strategy.clone());
+        FaultyReplicaHandler {
+            replica_handler,
+            strategy,
+        }
+    }
+
+    pub fn strategy(&self) -> &FaultyReplicaStrategy {
+        &self.strategy
     }
 
     fn handle_message_testing(&self) {
-        print!("Faulty replica #{} received the test", self.id());
+        println!("Faulty replica #{} received the test", self.id());
     }
 }
 
 #[async_trait::async_trait]
-impl Handler<Message> for FaultyReplicaHandler {
-    async fn handle_message(&mut self, _id: Identity, message: Message, _ack: Acknowledger) {
-        match message {
-            Message::Testing => {
+impl<A: Application> Handler<A::Action> for FaultyReplicaHandler<A> {
+    async fn handle_message(
+        &mut self,
+        id: Identity,
+        message: Message<A::Action>,
+        ack: Acknowledger,
+    ) {
+        match (&self.strategy, message) {
+            (_, Message::Testing) => {
                 self.handle_message_testing();
             }
-            _ => (),
+            (FaultyReplicaStrategy::Crash, _) => (),
+            (_, message) => self.replica_handler.handle_message(id, message, ack).await,
         }
     }
-    async fn handle_instruction(&mut self, instruction: Instruction) {
+    async fn handle_instruction(&mut self, instruction: Instruction<A::Action>) {
         match instruction {
             Instruction::Shutdown => self.replica_handler.shutdown().await,
             _ => {}
@@ -46,4 +176,102 @@ impl Handler<Message> for FaultyReplicaHandler {
     fn network_info(&self) -> &NetworkInfo {
         self.replica_handler.network_info()
     }
+
+    fn consensus_inlet(&self) -> Option<ConsensusInlet<A::Action>> {
+        self.replica_handler.consensus_inlet()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use talk::crypto::KeyChain;
+
+    use crate::{banking::action::Action, talk::Command};
+
+    use super::*;
+
+    #[test]
+    fn strategies_tamper_with_acknowledgements() {
+        let result: CommandResult = CommandResult::Success(Some(10));
+        let wrong = CommandResult::Success(None);
+
+        let strategy = FaultyReplicaStrategy::WrongResults;
+        assert_eq!(
+            strategy.acknowledgement(0, result.clone()),
+            (wrong.clone(), None)
+        );
+
+        let strategy = FaultyReplicaStrategy::EquivocateAcknowledgements;
+        assert_eq!(
+            strategy.acknowledgement(0, result.clone()),
+            (result.clone(), None)
+        );
+        assert_eq!(strategy.acknowledgement(1, result.clone()), (wrong, None));
+
+        let strategy = FaultyReplicaStrategy::Delay(50);
+        assert_eq!(
+            strategy.acknowledgement(0, result.clone()),
+            (result.clone(), None)
+        );
+        assert_eq!(
+            strategy.acknowledgement(1, result.clone()),
+            (result.clone(), Some(Duration::from_millis(50)))
+        );
+
+        let strategy = FaultyReplicaStrategy::Crash;
+        assert_eq!(strategy.acknowledgement(1, result.clone()), (result, None));
+    }
+
+    #[test]
+    fn strategies_tamper_with_sets() {
+        let set: Set = (0..4)
+            .map(|issuer| Command::new(issuer, Action::Register))
+            .collect();
+
+        let signed: SignedSet = set.iter().map(|command| (command.clone(), None)).collect();
+
+        let strategy = FaultyReplicaStrategy::TruncateBroadcasts;
+        assert_eq!(strategy.broadcast(&signed, 0), signed);
+        assert_eq!(strategy.broadcast(&signed, 3).len(), 1);
+        assert_eq!(strategy.broadcast(&signed, 5).is_empty(), true);
+
+        // Every relayed command comes along with a forged copy
+        let strategy = FaultyReplicaStrategy::ForgeCommands;
+        let forged = strategy.broadcast(&signed, 0);
+        assert_eq!(forged.len(), 2 * signed.len());
+        for command in set.iter() {
+            let copies = forged
+                .keys()
+                .filter(|forged| {
+                    forged.issuer() == command.issuer() && forged.action() == command.action()
+                })
+                .count();
+            assert_eq!(copies, 2);
+        }
+
+        // Replicas with an odd index get a value where every command conflicts
+        let strategy = FaultyReplicaStrategy::EquivocateConsensus;
+        assert_eq!(
+            strategy.consensus_value(set.clone(), BTreeSet::new(), 0),
+            (set.clone(), BTreeSet::new())
+        );
+        assert_eq!(
+            strategy.consensus_value(set.clone(), BTreeSet::new(), 1),
+            (BTreeSet::new(), set.clone())
+        );
+
+        let key = KeyChain::random().keycard().identity();
+        let strategy = FaultyReplicaStrategy::GarbageProposals;
+        let (_, k, nc_set, c_set) =
+            strategy.proposal((key.clone(), 3, set.clone(), BTreeSet::new()));
+        assert_eq!(k, 3);
+        assert_eq!(nc_set.is_empty(), true);
+        assert_eq!(c_set, set);
+
+        let strategy = FaultyReplicaStrategy::Crash;
+        let (_, _, nc_set, _) = strategy.proposal((key, 3, set.clone(), BTreeSet::new()));
+        assert_eq!(nc_set, set);
+    }
 }
@@ -1,9 +1,16 @@
+use std::sync::Arc;
+
 use crate::{
+    application::Operation,
+    banking::action::Action,
     crypto::identity_table::IdentityTable,
     network::{NetworkInfo, NetworkPeer},
     talk::{FeedbackSender, Message},
 };
-use talk::{crypto::Identity, unicast::Acknowledger};
+use talk::{
+    crypto::{Identity, KeyChain},
+    unicast::Acknowledger,
+};
 pub mod client_handler;
 pub mod communicator;
 pub mod faulty_client_handler;
@@ -12,67 +19,82 @@ pub mod replica_handler;
 
 pub use client_handler::ClientHandler;
 pub use communicator::Communicator;
-pub use faulty_client_handler::FaultyClientHandler;
-pub use faulty_replica_handler::FaultyReplicaHandler;
+pub use faulty_client_handler::{Adversarial, FaultyClientHandler, FaultyClientStrategy};
+pub use faulty_replica_handler::{FaultyReplicaHandler, FaultyReplicaStrategy};
 pub use replica_handler::ReplicaHandler;
 
 use crate::{talk::Instruction, types::*};
 
-use super::{coordinator::Coordinator, peer::PeerId};
+use super::{consensus::ConsensusSystem, peer::PeerId};
 
 #[async_trait::async_trait]
-pub trait Handler<T>: Sync + Send
-where
-    T: UnicastMessage,
-{
-    async fn handle_message(&mut self, id: Identity, message: T, ack: Acknowledger);
-    async fn handle_instruction(&mut self, instruction: Instruction);
+pub trait Handler<O: Operation = Action>: Sync + Send {
+    async fn handle_message(&mut self, id: Identity, message: Message<O>, ack: Acknowledger);
+    async fn handle_instruction(&mut self, instruction: Instruction<O>);
 
     fn id(&self) -> &PeerId;
     fn network_info(&self) -> &NetworkInfo;
+
+    /// Inlet of the consensus messages received by the peer, which its `Peer` forwards there
+    /// instead of handing them to the handler
+    fn consensus_inlet(&self) -> Option<ConsensusInlet<O>> {
+        None
+    }
 }
 
 pub struct HandlerBuilder {}
 impl HandlerBuilder {
-    fn get_corresponding_handler(
+    fn get_corresponding_handler<A: Adversarial>(
         peer_type: NetworkPeer,
-        peer_handler: Communicator<Message>,
-        coordinator: &Coordinator,
-    ) -> Box<dyn Handler<Message>> {
+        peer_handler: Communicator<A::Action>,
+        consensus: &ConsensusSystem<A::Action>,
+    ) -> Box<dyn Handler<A::Action>> {
         match peer_type {
             NetworkPeer::Client => Box::new(ClientHandler::new(peer_handler)),
-            NetworkPeer::FaultyClient => Box::new(FaultyClientHandler::new(peer_handler)),
-            NetworkPeer::Replica => Box::new(ReplicaHandler::new(
-                peer_handler,
-                coordinator.proposer(),
-                coordinator.subscribe(),
-            )),
-            NetworkPeer::FaultyReplica => Box::new(FaultyReplicaHandler::new(ReplicaHandler::new(
-                peer_handler,
-                coordinator.proposer(),
-                coordinator.subscribe(),
-            ))),
+            NetworkPeer::FaultyClient => {
+                let strategy = peer_handler
+                    .network_info()
+                    .faulty_client_strategy(peer_handler.id());
+                Box::new(FaultyClientHandler::<A>::new(peer_handler, strategy))
+            }
+            NetworkPeer::Replica => {
+                let consensus = consensus.consensus(&peer_handler);
+                Box::new(ReplicaHandler::<A>::new(peer_handler, consensus))
+            }
+            NetworkPeer::FaultyReplica => {
+                let consensus = consensus.consensus(&peer_handler);
+                let strategy = peer_handler
+                    .network_info()
+                    .faulty_replica_strategy(peer_handler.id());
+                Box::new(FaultyReplicaHandler::new(
+                    ReplicaHandler::<A>::new(peer_handler, consensus),
+                    strategy,
+                ))
+            }
         }
     }
 
-    pub fn handler(
+    /// Builds the handler of a peer replicating the application `A`
+    pub fn handler<A: Adversarial>(
         peer_type: NetworkPeer,
         id: PeerId,
         key: Identity,
-        sender: UnicastSender<Message>,
-        feedback_inlet: FeedbackSender,
-        coordinator: &Coordinator,
+        keychain: Arc<KeyChain>,
+        sender: UnicastSender<Message<A::Action>>,
+        feedback_inlet: FeedbackSender<A::Action>,
+        consensus: &ConsensusSystem<A::Action>,
         network_info: NetworkInfo,
         identity_table: IdentityTable,
-    ) -> Box<dyn Handler<Message>> {
+    ) -> Box<dyn Handler<A::Action>> {
         let peer_handler = Communicator::new(
             id,
             key,
+            keychain,
             sender,
             feedback_inlet,
             network_info,
             identity_table,
         );
-        Self::get_corresponding_handler(peer_type, peer_handler, coordinator)
+        Self::get_corresponding_handler::<A>(peer_type, peer_handler, consensus)
     }
 }
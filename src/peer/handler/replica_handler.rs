@@ -1,56 +1,71 @@
-use std::{collections::BTreeSet, fs::File, io::Write, path::Path, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::File,
+    hash::{Hash, Hasher},
+    io::Write,
+    path::Path,
+    time::Duration,
+};
 
-use talk::{crypto::Identity, unicast::Acknowledger};
-use tokio::sync::broadcast::error::RecvError;
-use tokio::time::timeout;
+use talk::{
+    crypto::{primitives::sign::Signature, Identity},
+    unicast::Acknowledger,
+};
+use tokio::time::{sleep, timeout, Instant};
 
 use crate::{
+    application::Application,
     banking::banking::Banking,
-    banking::transaction::Transaction,
-    database::replica_database::{ReplicaDatabase, Set},
-    error::BankingError,
-    network::NetworkInfo,
+    crypto::statements::{CommandAcknowledgement, IssuedCommand},
+    database::{
+        replica_database::{ReplicaDatabase, Set, SignedSet},
+        transaction::Transaction,
+    },
+    error::{CommandRejection, ConsensusError},
+    network::{
+        metrics::ReplicaMetrics,
+        safety::{Decision, FinalState},
+        trace::{Event, Tracer},
+        NetworkInfo,
+    },
     peer::{
-        coordinator::{ProposalData, ProposalSignedData},
-        peer::PeerId,
-        shutdownable::Shutdownable,
+        consensus::Consensus, coordinator::ProposalSignedData, handler::FaultyReplicaStrategy,
+        peer::PeerId, shutdownable::Shutdownable,
     },
-    relation::{conflict::ConflictingRelation, Relation},
-    talk::{Command, CommandResult, Instruction, Message, Phase, RoundNumber},
-    types::*,
+    talk::{Command, CommandId, CommandResult, Instruction, Message, Phase, RoundNumber},
+    types::ConsensusInlet,
 };
 
 use super::{communicator::Communicator, Handler};
 
-pub struct ReplicaHandler {
-    communicator: Communicator<Message>,
-    proposal_inlet: MPSCSender<ProposalSignedData>,
-    proposal_outlet: BroadcastReceiver<ProposalData>,
-    database: ReplicaDatabase,
-    received_to_resolve: Set, // Cache the received commands that need to resolve the conflicting relation
-    // If a command is not in this set, it does not conflict with any other command in received \ delivered.
-    // NB: deprecated, leads to bug with the coordinator
-    banking: Banking,
+/// Replica of the application `A`
+pub struct ReplicaHandler<A: Application = Banking> {
+    communicator: Communicator<A::Action>,
+    consensus: Box<dyn Consensus<A::Action>>,
+    database: ReplicaDatabase<A::Action>,
+    application: A,
+    rejections: HashMap<CommandRejection, usize>,
+    signatures: HashMap<Command<A::Action>, Signature>, // Issuer signatures of the received commands, relayed in the broadcasts
+    strategy: Option<FaultyReplicaStrategy>,            // Only set for faulty replicas
+    buffered_broadcasts: BTreeMap<RoundNumber, Vec<(Set<A::Action>, Phase)>>,
+    checks: Set<A::Action>, // Commands their client asked to check, proposed even without conflicts
+    awaiting_decision: Option<RoundNumber>, // Round proposed to the consensus, not decided yet
 }
 
+/// Longest time a replica waits for a decision before handling other messages
+const DECISION_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[async_trait::async_trait]
-impl Handler<Message> for ReplicaHandler {
-    async fn handle_message(&mut self, _id: Identity, message: Message, _ack: Acknowledger) {
-        match message {
-            Message::Testing => {
-                println!("Replica #{} received the test", self.communicator.id())
-            }
-            Message::Command(command) => self.handle_command(command),
-            Message::ReplicaBroadcast(k, set, phase) => {
-                self.handle_replica_broadcast(k, set, phase)
-            }
-            _ => {}
-        }
-        let process = self.process_commands();
-        let process = timeout(Duration::from_secs(60), process);
-        process.await.unwrap(); // Crash sometimes -> WHY ?
+impl<A: Application> Handler<A::Action> for ReplicaHandler<A> {
+    async fn handle_message(
+        &mut self,
+        id: Identity,
+        message: Message<A::Action>,
+        _ack: Acknowledger,
+    ) {
+        self.receive(id, message).await
     }
-    async fn handle_instruction(&mut self, instruction: Instruction) {
+    async fn handle_instruction(&mut self, instruction: Instruction<A::Action>) {
         match instruction {
             Instruction::Testing => {
                 println!("Replica #{} received the test", self.communicator.id())
@@ -69,69 +84,316 @@ impl Handler<Message> for ReplicaHandler {
     fn network_info(&self) -> &NetworkInfo {
         self.communicator.network_info()
     }
+
+    fn consensus_inlet(&self) -> Option<ConsensusInlet<A::Action>> {
+        self.consensus.inlet()
+    }
 }
 
-impl ReplicaHandler {
+impl<A: Application> ReplicaHandler<A> {
     pub fn new(
-        communicator: Communicator<Message>,
-        proposal_inlet: MPSCSender<ProposalSignedData>,
-        proposal_outlet: BroadcastReceiver<ProposalData>,
+        communicator: Communicator<A::Action>,
+        consensus: Box<dyn Consensus<A::Action>>,
     ) -> Self {
         ReplicaHandler {
             communicator,
-            proposal_inlet,
-            proposal_outlet,
+            consensus,
             database: ReplicaDatabase::new(),
-            received_to_resolve: BTreeSet::new(),
-            banking: Banking::new(),
+            application: A::default(),
+            rejections: HashMap::new(),
+            signatures: HashMap::new(),
+            strategy: None,
+            buffered_broadcasts: BTreeMap::new(),
+            checks: BTreeSet::new(),
+            awaiting_decision: None,
+        }
+    }
+
+    /// Handles the message sent by `id`, then processes the new commands
+    pub async fn receive(&mut self, id: Identity, message: Message<A::Action>) {
+        match message {
+            Message::Testing => {
+                println!("Replica #{} received the test", self.communicator.id())
+            }
+            Message::Command(command, signature) => {
+                match self.authenticate_command(&id, &command, &signature) {
+                    Ok(()) => {
+                        if let Some(signature) = signature {
+                            self.signatures.insert(command.clone(), signature);
+                        }
+                        self.handle_command(command)
+                    }
+                    Err(reason) => self.reject_command(&command, reason),
+                }
+            }
+            Message::CheckCommand(command, signature) => {
+                match self.authenticate_command(&id, &command, &signature) {
+                    Ok(()) => {
+                        if let Some(signature) = signature {
+                            self.signatures.insert(command.clone(), signature);
+                        }
+                        self.handle_check(command)
+                    }
+                    Err(reason) => self.reject_command(&command, reason),
+                }
+            }
+            Message::ReplicaBroadcast(k, set, phase) => {
+                let set = self.authenticate_set(&id, set);
+                self.handle_replica_broadcast(k, set, phase)
+            }
+            _ => {}
         }
+        self.process().await;
+    }
+
+    /// Runs task 2 until no broadcast of the current round is left, e.g. once the decision the
+    /// replica waits for is available
+    pub async fn process(&mut self) {
+        loop {
+            let process = self.process_commands();
+            let process = timeout(Duration::from_secs(60), process);
+            if process.await.is_err() {
+                println!(
+                    "Replica #{} timed out while processing the commands",
+                    self.communicator.id()
+                );
+                break;
+            }
+
+            // Broadcasts received ahead of the current round are handled once the replica catches up
+            if !self.handle_buffered_broadcasts() {
+                break;
+            }
+        }
+    }
+
+    /// Makes this replica behave according to the given Byzantine strategy
+    pub(crate) fn corrupt(&mut self, strategy: FaultyReplicaStrategy) {
+        self.strategy = Some(strategy);
     }
 
     pub async fn shutdown(&mut self) {
         if self.communicator.network_info().write_logs() {
             self.write_logs();
         }
+        self.record_final_state();
 
         self.communicator.shutdown().await;
     }
 
-    pub fn handle_command(&mut self, command: Command) {
-        if self.database.receive_command(command.clone()) {
-            if !self.database.delivered().contains(&command) {
-                self.received_to_resolve.insert(command);
+    /// Checks that the command is signed by its issuer, and that the issuer sent it
+    pub fn authenticate_command(
+        &self,
+        sender: &Identity,
+        command: &Command<A::Action>,
+        signature: &Option<Signature>,
+    ) -> Result<(), CommandRejection> {
+        let issuer = self
+            .communicator
+            .identity_table()
+            .get_client_id(*command.issuer())
+            .ok_or(CommandRejection::UnknownIssuer)?;
+        if issuer != sender {
+            return Err(CommandRejection::SenderMismatch);
+        }
+        self.verify_issuer_signature(command, signature)
+    }
+
+    /// Checks that the command is signed by its issuer, whoever relays it
+    pub fn verify_issuer_signature(
+        &self,
+        command: &Command<A::Action>,
+        signature: &Option<Signature>,
+    ) -> Result<(), CommandRejection> {
+        let signature = signature.as_ref().ok_or(CommandRejection::Unsigned)?;
+        let identity_table = self.communicator.identity_table();
+        let keycard = identity_table
+            .get_client_id(*command.issuer())
+            .and_then(|issuer| identity_table.keycard(issuer))
+            .ok_or(CommandRejection::UnknownIssuer)?;
+        signature
+            .verify(keycard, &IssuedCommand { command })
+            .map_err(|_| CommandRejection::InvalidSignature)
+    }
+
+    /// Returns the commands of the broadcast signed by their issuer, and drops the others.
+    /// A broadcast that does not come from a replica is dropped as a whole.
+    fn authenticate_set(&mut self, sender: &Identity, set: SignedSet<A::Action>) -> Set<A::Action> {
+        let from_replica = self.communicator.identity_table().is_replica(sender);
+        let mut authenticated = Set::new();
+        for (command, signature) in set {
+            let verification = if from_replica {
+                self.verify_issuer_signature(&command, &signature)
+            } else {
+                Err(CommandRejection::NotReplica)
+            };
+            match (verification, signature) {
+                (Ok(()), Some(signature)) => {
+                    self.signatures.insert(command.clone(), signature);
+                    authenticated.insert(command);
+                }
+                (Ok(()), None) => unreachable!("Unsigned commands are rejected"),
+                (Err(reason), _) => self.reject_command(&command, reason),
             }
         }
+        authenticated
+    }
+
+    /// Attaches the signature of its issuer to each command of the set, if known
+    fn sign_set(&self, set: Set<A::Action>) -> SignedSet<A::Action> {
+        set.into_iter()
+            .map(|command| {
+                let signature = self.signatures.get(&command).cloned();
+                (command, signature)
+            })
+            .collect()
+    }
+
+    fn reject_command(&mut self, command: &Command<A::Action>, reason: CommandRejection) {
+        *self.rejections.entry(reason).or_insert(0) += 1;
+        self.record(|metrics| metrics.commands_rejected += 1);
+        println!(
+            "Replica #{} drops the command #{}: {}",
+            self.communicator.id(),
+            command.id(),
+            reason
+        );
+    }
+
+    pub fn database(&self) -> &ReplicaDatabase<A::Action> {
+        &self.database
+    }
+
+    /// Round proposed to the consensus, whose decision the replica waits for
+    pub fn awaiting_decision(&self) -> Option<RoundNumber> {
+        self.awaiting_decision
+    }
+
+    /// Feeds the protocol state of the replica into the hasher: its sets, round, speculative
+    /// results, application state, buffered broadcasts, checked commands and awaited decision.
+    /// Two replicas with the same digest behave the same from then on.
+    pub fn digest<H: Hasher>(&self, state: &mut H) {
+        self.database.received().hash(state);
+        self.database.delivered().hash(state);
+        self.database.pending().hash(state);
+        self.database.round().hash(state);
+        let mut results: Vec<(&Command<A::Action>, &CommandResult<A::Action>)> =
+            self.database.results().iter().collect();
+        results.sort();
+        results.hash(state);
+        self.application.snapshot().hash(state);
+        self.buffered_broadcasts.hash(state);
+        self.checks.hash(state);
+        self.awaiting_decision.hash(state);
+    }
+
+    /// Records the commands this replica delivered and executed, and the state of its application,
+    /// for `Decisions::check_final`
+    fn record_final_state(&self) {
+        let ids = |set: &Set<A::Action>| set.iter().map(|command| *command.id()).collect();
+        let state = FinalState {
+            round: *self.database.round(),
+            delivered: ids(self.database.delivered()),
+            pending: ids(self.database.pending()),
+            state: Tracer::value(&self.application.snapshot()),
+        };
+        self.communicator
+            .network_info()
+            .decisions()
+            .record_final(*self.communicator.id(), state);
+    }
+
+    /// Returns the number of dropped commands, per rejection reason
+    pub fn rejections(&self) -> &HashMap<CommandRejection, usize> {
+        &self.rejections
+    }
+
+    /// Updates the metrics of this replica
+    fn record<F: FnOnce(&mut ReplicaMetrics)>(&self, update: F) {
+        self.communicator
+            .network_info()
+            .metrics()
+            .replica(*self.communicator.id(), update);
+    }
+
+    /// Records the event in the trace of the network
+    fn trace<F: FnOnce() -> Event>(&self, event: F) {
+        self.communicator.network_info().tracer().record(
+            *self.communicator.id(),
+            Some(*self.database.round()),
+            event,
+        );
+    }
+
+    /// State of the replicated application
+    pub fn application(&self) -> &A {
+        &self.application
+    }
+
+    pub fn handle_command(&mut self, command: Command<A::Action>) {
+        if self.database.receive_command(command) {
+            self.record(|metrics| metrics.commands_received += 1);
+        }
+    }
+
+    /// Receives the command if needed, and has it decided by the consensus unless it is
+    /// already delivered
+    pub fn handle_check(&mut self, command: Command<A::Action>) {
+        self.handle_command(command.clone());
+        if !self.database.delivered().contains(&command) {
+            self.checks.insert(command);
+        }
     }
 
     /// Implements task 1b and 1c
     pub fn handle_replica_broadcast(
         &mut self,
         round: RoundNumber,
-        mut set: BTreeSet<Command>,
+        mut set: Set<A::Action>,
         phase: Phase,
     ) {
         if round.eq(self.database.round()) {
-            self.received_to_resolve.append(&mut set.difference(self.database.delivered()).cloned().collect());
             match phase {
                 Phase::ACK => self.database.receive_set(&mut set),
                 Phase::CHK => self.database.receive_set(&mut set),
             }
+        } else if round > *self.database.round() {
+            self.buffered_broadcasts
+                .entry(round)
+                .or_insert_with(Vec::new)
+                .push((set, phase));
+        }
+    }
+
+    /// Handles the broadcasts buffered for the current round, and discards the ones of past rounds.
+    /// Returns true if at least one broadcast was handled.
+    fn handle_buffered_broadcasts(&mut self) -> bool {
+        let round = *self.database.round();
+        self.buffered_broadcasts = self.buffered_broadcasts.split_off(&round);
+        match self.buffered_broadcasts.remove(&round) {
+            Some(broadcasts) => {
+                for (set, phase) in broadcasts {
+                    self.handle_replica_broadcast(round, set, phase);
+                }
+                true
+            }
+            None => false,
         }
     }
 
     /// Returns true if there are new command to process
     /// This corresponds to the condition to enter task 2
-    fn is_pending(unprocessed_commands: &Set) -> bool {
+    fn is_pending(unprocessed_commands: &Set<A::Action>) -> bool {
         !unprocessed_commands.is_empty()
     }
 
-    fn compute_unprocessed_commands(&self) -> (Set, Set) {
+    fn compute_unprocessed_commands(&self) -> (Set<A::Action>, Set<A::Action>) {
         let received = self.database.received();
         let g_del = self.database.delivered();
         let pending_k = self.database.pending();
 
-        let received_minus_del: BTreeSet<Command> = received.difference(g_del).cloned().collect();
-        let new_commands: BTreeSet<Command> =
+        let received_minus_del: Set<A::Action> = received.difference(g_del).cloned().collect();
+        let new_commands: Set<A::Action> =
             received_minus_del.difference(pending_k).cloned().collect();
 
         (new_commands, received_minus_del)
@@ -139,17 +401,22 @@ impl ReplicaHandler {
 
     /// Defines task 2
     pub async fn process_commands(&mut self) {
+        if self.awaiting_decision.is_some() {
+            self.wait_for_decision().await;
+            if self.awaiting_decision.is_some() {
+                return;
+            }
+        }
         let (unprocessed_commands, received_diff_delivered) = self.compute_unprocessed_commands();
-        if Self::is_pending(&unprocessed_commands) {
-            if !ConflictingRelation::is_conflicting(
-                &received_diff_delivered,
-                &received_diff_delivered,
-            ) {
-                self.received_to_resolve.clear();
+        // A checked command takes the CHK path, even if it was acknowledged on the fast path
+        let checking = !self.checks.is_disjoint(&received_diff_delivered);
+        if Self::is_pending(&unprocessed_commands) || checking {
+            if !checking && !Self::is_conflicting(&received_diff_delivered) {
                 let unprocessed_commands = unprocessed_commands.into_iter();
                 for command in unprocessed_commands {
                     let result = self.execute(&command);
                     self.acknowledge_client(command, result, Phase::ACK).await;
+                    self.record(|metrics| metrics.fast_path_deliveries += 1);
                 }
 
                 self.database.set_pending(received_diff_delivered);
@@ -158,87 +425,182 @@ impl ReplicaHandler {
             } else {
                 self.broadcast_to_replicas(received_diff_delivered, Phase::CHK)
                     .await;
-                let (k, nc_set, c_set) = timeout(
-                    Duration::from_secs(300),
-                    self.propose((
-                        self.communicator.key().clone(),
-                        *self.database.round(),
-                        self.database.pending().clone(),
-                        unprocessed_commands,
-                    )),
-                )
-                .await
-                .expect("Timeout")
-                .expect("Fails to unwrap the proposal");
-                if k.eq(self.database.round()) {
-                    let pending = self.database.pending();
-                    let pending_diff_nc_set: Set = pending.difference(&nc_set).cloned().collect();
-                    let pending_diff_nc_set = pending_diff_nc_set.into_iter();
-
-                    for command in pending_diff_nc_set {
-                        self.rollback(&command).expect("Rollback failed");
+                let round = *self.database.round();
+                let proposal = (
+                    self.communicator.key().clone(),
+                    round,
+                    self.database.pending().clone(),
+                    unprocessed_commands,
+                );
+                self.record(|metrics| metrics.chk_rounds += 1);
+                self.trace(|| Event::Propose {
+                    nc_set: Tracer::set(&proposal.2),
+                    c_set: Tracer::set(&proposal.3),
+                });
+                match self.propose(proposal).await {
+                    Ok(()) => {
+                        self.awaiting_decision = Some(round);
+                        self.wait_for_decision().await;
                     }
+                    Err(error) => println!(
+                        "Replica #{} failed to propose for round {}: {:?}",
+                        self.communicator.id(),
+                        round,
+                        error
+                    ),
+                }
+            }
+        }
+    }
 
-                    let nc_set_diff_delivered: Set = nc_set
-                        .difference(self.database.delivered())
-                        .cloned()
-                        .collect();
-                    let nc_set_diff_delivered = nc_set_diff_delivered.into_iter();
+    /// Waits for the decision of the proposed round, at most `DECISION_TIMEOUT`.
+    /// If the decision does not come in time, e.g. because the replica is partitioned from the
+    /// others, the replica keeps on handling messages and waits again on the next ones.
+    async fn wait_for_decision(&mut self) {
+        let round = match self.awaiting_decision {
+            Some(round) => round,
+            None => return,
+        };
+        let start = Instant::now();
+        let decision = timeout(DECISION_TIMEOUT, self.consensus.decide(round)).await;
+        self.record(|metrics| metrics.consensus_wait += start.elapsed());
+        match decision {
+            Ok(Ok((k, nc_set, c_set))) => {
+                self.awaiting_decision = None;
+                if k.eq(self.database.round()) {
+                    self.apply_decision(nc_set, c_set).await;
+                }
+            }
+            Ok(Err(error)) => {
+                self.awaiting_decision = None;
+                println!(
+                    "Replica #{} did not get the decision of round {}: {:?}",
+                    self.communicator.id(),
+                    round,
+                    error
+                );
+            }
+            Err(_) => println!(
+                "Replica #{} is still waiting for the decision of round {}",
+                self.communicator.id(),
+                round
+            ),
+        }
+    }
 
-                    for command in nc_set_diff_delivered {
-                        let result = self
-                            .database
-                            .results_mut()
-                            .remove(&command)
-                            .unwrap_or_else(|| self.execute(&command));
+    /// Rollbacks the speculative executions missing from `nc_set`, then delivers `nc_set` and `c_set`
+    async fn apply_decision(&mut self, nc_set: Set<A::Action>, c_set: Set<A::Action>) {
+        self.trace(|| Event::Decide {
+            nc_set: Tracer::set(&nc_set),
+            c_set: Tracer::set(&c_set),
+        });
+        let pending = self.database.pending();
+        let mut pending_diff_nc_set: HashMap<CommandId, Command<A::Action>> = pending
+            .difference(&nc_set)
+            .map(|command| (*command.id(), command.clone()))
+            .collect();
+        // Rollbacks in the reverse order of execution, e.g. a registration is undone last
+        let mut rollbacks: Vec<Command<A::Action>> = Vec::new();
+        for transaction in self.database.logs().iter().rev() {
+            if pending_diff_nc_set.is_empty() {
+                break;
+            }
+            rollbacks.extend(pending_diff_nc_set.remove(transaction.id()));
+        }
+        // The commands never executed have nothing to undo
+        rollbacks.extend(pending_diff_nc_set.into_values());
 
-                        self.acknowledge_client(command, result, Phase::CHK).await;
-                    }
+        for command in rollbacks {
+            self.rollback(&command).expect("Rollback failed");
+            self.record(|metrics| metrics.rollbacks += 1);
+        }
 
-                    let mut c_set_ordered: Vec<Command> = c_set
-                        .difference(self.database.delivered())
-                        .cloned()
-                        .collect();
-                    c_set_ordered.sort();
+        let nc_set_diff_delivered: Set<A::Action> = nc_set
+            .difference(self.database.delivered())
+            .cloned()
+            .collect();
+        let mut decision = Decision::default();
+        let nc_set_diff_delivered = nc_set_diff_delivered.into_iter();
+
+        for command in nc_set_diff_delivered {
+            decision.nc_set.push(*command.id());
+            let result = self
+                .database
+                .results_mut()
+                .remove(&command)
+                .unwrap_or_else(|| self.execute(&command));
+
+            self.acknowledge_client(command, result, Phase::CHK).await;
+        }
 
-                    let c_set_ordered = c_set_ordered.into_iter();
+        let mut c_set_ordered: Vec<Command<A::Action>> = c_set
+            .difference(self.database.delivered())
+            .cloned()
+            .collect();
+        c_set_ordered.sort();
 
-                    for command in c_set_ordered {
-                        let result = self.execute(&command);
-                        self.acknowledge_client(command, result, Phase::CHK).await;
-                    }
+        let c_set_ordered = c_set_ordered.into_iter();
 
-                    self.database.delivered_all(&nc_set);
-                    self.database.delivered_all(&c_set);
-                    self.received_to_resolve = self.received_to_resolve.difference(&nc_set).cloned().collect();
-                    self.received_to_resolve = self.received_to_resolve.difference(&c_set).cloned().collect();
-                    self.database.increment_round();
-                    self.database.reset_pending();
-                    self.database.reset_result();
-                }
-            }
+        for command in c_set_ordered {
+            decision.c_set.push(*command.id());
+            let result = self.execute(&command);
+            self.acknowledge_client(command, result, Phase::CHK).await;
         }
+
+        self.database.delivered_all(&nc_set);
+        self.database.delivered_all(&c_set);
+        self.checks = self
+            .checks
+            .difference(self.database.delivered())
+            .cloned()
+            .collect();
+        decision.state = Tracer::value(&self.application.snapshot());
+        self.communicator.network_info().decisions().record(
+            *self.communicator.id(),
+            *self.database.round(),
+            decision,
+        );
+        self.database.increment_round();
+        self.trace(|| Event::NewRound(*self.database.round()));
+        self.database.reset_pending();
+        self.database.reset_result();
     }
 
     async fn acknowledge_client(
         &self,
-        command: Command,
-        command_result: CommandResult,
+        command: Command<A::Action>,
+        command_result: CommandResult<A::Action>,
         phase: Phase,
     ) -> bool {
-        if let Some(key) = self
+        let (command_result, delay) = match &self.strategy {
+            Some(strategy) => strategy.acknowledgement(*command.issuer(), command_result),
+            None => (command_result, None),
+        };
+        if let Some(delay) = delay {
+            sleep(delay).await;
+        }
+
+        let round = *self.database.round();
+        let signature = self.communicator.sign(&CommandAcknowledgement {
+            id: command.id(),
+            round: &round,
+            result: &command_result,
+            phase: &phase,
+        });
+        let key = self
             .communicator
             .identity_table()
-            .get_client_id(*command.issuer())
-        {
+            .get_client_id(*command.issuer());
+        if let (Some(key), Some(signature)) = (key, signature) {
             self.communicator
                 .spawn_send_message(
                     key.clone(),
                     Message::CommandAcknowledgement(
                         command,
-                        *self.database.round(),
+                        round,
                         command_result,
                         phase,
+                        signature,
                     ),
                 )
                 .await;
@@ -249,111 +611,63 @@ impl ReplicaHandler {
     }
 
     /// Warning: It will block if mutliple replicas are spawned on the same thread
-    async fn propose(&mut self, data: ProposalSignedData) -> Result<ProposalData, RecvError> {
-        self.proposal_inlet.send(data).await.unwrap();
-        self.proposal_outlet.recv().await
+    async fn propose(&mut self, data: ProposalSignedData<A::Action>) -> Result<(), ConsensusError> {
+        let data = match &self.strategy {
+            Some(strategy) => strategy.proposal(data),
+            None => data,
+        };
+        self.consensus.propose(data).await
     }
 
-    async fn broadcast_to_replicas(&self, set: Set, phase: Phase) {
-        let message = Message::ReplicaBroadcast(*self.database.round(), set, phase);
+    async fn broadcast_to_replicas(&self, set: Set<A::Action>, phase: Phase) {
         let replicas = self.communicator.identity_table().replicas();
-        for replica in replicas.iter() {
+        let set = self.sign_set(set);
+        for (index, replica) in replicas.iter().enumerate() {
             if !self.communicator.key().eq(replica) {
+                let set = match &self.strategy {
+                    Some(strategy) => strategy.broadcast(&set, index),
+                    None => set.clone(),
+                };
+                let message = Message::ReplicaBroadcast(*self.database.round(), set, phase.clone());
+                let size = bincode::serialized_size(&message).unwrap_or(0);
+                self.record(|metrics| {
+                    metrics.broadcasts += 1;
+                    metrics.broadcast_bytes += size;
+                });
                 self.communicator
-                    .spawn_send_message(replica.clone(), message.clone())
+                    .spawn_send_message(replica.clone(), message)
                     .await;
             }
         }
     }
 
-    fn is_there_conflict(&self, set: &Set) -> bool {
-        let mut set2 = set.into_iter();
-        for e1 in self.received_to_resolve.iter() {
-            if set2.any(|e2| ConflictingRelation::is_related(&e1, e2)) {
-                return true;
-            }
-        }
-        false
+    /// Returns true if two commands of the set conflict
+    fn is_conflicting(set: &Set<A::Action>) -> bool {
+        set.iter()
+            .any(|x| set.iter().any(|y| A::conflict(x, y) && x != y))
     }
 
     /// Execute the given command, and stores the transaction in the log.
     /// Returns the result
-    fn execute(&mut self, command: &Command) -> CommandResult {
-        //println!("Execute {:?}", command);
-        let action = command.action();
-        let id = *command.issuer();
-        let result = match action {
-            crate::banking::action::Action::Register => {
-                if self.banking.register(id) {
-                    CommandResult::Success(None)
-                } else {
-                    CommandResult::Failure(format!("Client #{} is already registered", id))
-                }
-            }
-            crate::banking::action::Action::Get => self
-                .banking
-                .get(&id)
-                .map(|amount| CommandResult::Success(Some(amount)))
-                .unwrap_or(CommandResult::Failure(format!(
-                    "Client #{} is not registered",
-                    id
-                ))),
-            crate::banking::action::Action::Deposit(amount) => self
-                .banking
-                .deposit(&id, *amount)
-                .map(|_res| CommandResult::Success(None))
-                .unwrap_or(CommandResult::Failure(format!(
-                    "Client #{} cannot deposit because he is not registered",
-                    id
-                ))),
-            crate::banking::action::Action::Withdraw(amount) => self
-                .banking
-                .withdraw(&id, *amount)
-                .map(|_amount| CommandResult::Success(None))
-                .unwrap_or_else(|err| match err {
-                    BankingError::ClientNotFound => {
-                        CommandResult::Failure(format!("Client #{} is not registered", id))
-                    }
-                    BankingError::UnsufficientBalance => {
-                        CommandResult::Failure(format!("Unsufficient balance"))
-                    }
-                }),
-        };
+    fn execute(&mut self, command: &Command<A::Action>) -> CommandResult<A::Action> {
+        let result = self.application.execute(command);
         self.database
             .log(Transaction::from_command(command, &result));
         self.database.add_result(command.clone(), result.clone());
+        self.trace(|| Event::Execute {
+            command: Tracer::command(command),
+            result: Tracer::value(&result),
+        });
         return result;
     }
 
-    fn rollback(&mut self, command: &Command) -> Result<(), BankingError> {
-        //println!("Rollback: {:?}", command);
-        let action = command.action();
-        let id = command.issuer();
-        let banking = &mut self.banking;
-        let speculative_result = self
-            .database
-            .remove_result(&command)
-            .map(|result| {
-                //println!("Rollback successful");
-                match result {
-                    // Only rollback the effect if the command was successful
-                    CommandResult::Success(_) => match action {
-                        crate::banking::action::Action::Register => {
-                            banking.unregister(id);
-                            Ok(())
-                        }
-                        crate::banking::action::Action::Get => Ok(()),
-                        crate::banking::action::Action::Deposit(amount) => {
-                            banking.withdraw(id, *amount).map(|_| ())
-                        }
-                        crate::banking::action::Action::Withdraw(amount) => {
-                            banking.deposit(id, *amount).map(|_| ())
-                        }
-                    },
-                    CommandResult::Failure(_) => Ok(()),
-                }
-            })
-            .unwrap_or(Err(BankingError::ClientNotFound));
+    fn rollback(&mut self, command: &Command<A::Action>) -> Result<(), A::Error> {
+        self.trace(|| Event::Rollback(Tracer::command(command)));
+        let speculative_result = match self.database.remove_result(&command) {
+            Some(result) => self.application.rollback(command, &result),
+            // The command has no speculative effect if it was never executed
+            None => Ok(()),
+        };
         for transaction in self.database.logs_mut().iter_mut() {
             if transaction.id().eq(command.id()) {
                 transaction.rollback();
@@ -383,7 +697,10 @@ impl ReplicaHandler {
         for transaction in self.database.logs().iter() {
             write!(file, "{} \n", transaction).expect("Cannot write logs");
         }
-        write!(file, "{:#?} \n", self.banking.clients()).expect("Fails to write logs");
+        write!(file, "{:#?} \n", self.application.snapshot()).expect("Fails to write logs");
+        for (reason, count) in self.rejections.iter() {
+            write!(file, "Dropped {} commands: {} \n", count, reason).expect("Fails to write logs");
+        }
 
         println!(
             "[{:#?}] #{} wrote logs",
@@ -395,18 +712,160 @@ impl ReplicaHandler {
 
 #[cfg(test)]
 mod tests {
-    use talk::unicast::test::UnicastSystem;
+    use std::sync::Arc;
+
+    use talk::sync::fuse::Fuse;
 
     use crate::{
         banking::action::Action,
         crypto::identity_table::IdentityTableBuilder,
-        peer::{coordinator::Coordinator, handler::ClientHandler},
+        peer::{consensus::ChannelConsensus, coordinator::Coordinator, handler::ClientHandler},
         talk::FeedbackChannel,
         tests::util::Utils,
     };
 
     use super::*;
 
+    /// A replica of a network of 3 replicas, deciding through an oracle
+    async fn lone_replica() -> ReplicaHandler<Banking> {
+        let network_info = NetworkInfo::with_default_report_folder(0, 3, 0, 0, 10, 1);
+        let mut mock_network = Utils::unicast_system(3).await;
+        let (replica, sender, _receiver) = Utils::pop_from_network(&mut mock_network);
+        let (rx, _tx) = FeedbackChannel::channel();
+        let coordinator = Coordinator::new(network_info.clone());
+
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        for key in mock_network.keys.iter().chain(Some(&replica)) {
+            identity_table_builder.add_peer(Utils::keychain(&key));
+        }
+        let identity_table = identity_table_builder.build();
+        ReplicaHandler::<Banking>::new(
+            Communicator::new(
+                0,
+                replica.clone(),
+                identity_table_builder.keychain(&replica).unwrap(),
+                sender,
+                rx,
+                network_info,
+                identity_table,
+            ),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
+        )
+    }
+
+    #[tokio::test]
+    async fn broadcasts_of_later_rounds_are_buffered() {
+        let mut replica = lone_replica().await;
+        let round = *replica.database.round();
+        let early = Command::new(4, Action::Register);
+        let late = Command::new(5, Action::Register);
+        let set = |command: &Command| -> Set { vec![command.clone()].into_iter().collect() };
+
+        replica.handle_replica_broadcast(round + 1, set(&early), Phase::ACK);
+        replica.handle_replica_broadcast(round + 2, set(&late), Phase::ACK);
+        assert!(!replica.database.received().contains(&early));
+        assert!(!replica.handle_buffered_broadcasts());
+
+        // The broadcast of the skipped round is discarded, the one of the current round is handled
+        replica.database.increment_round();
+        replica.database.increment_round();
+        assert!(replica.handle_buffered_broadcasts());
+        assert!(!replica.database.received().contains(&early));
+        assert!(replica.database.received().contains(&late));
+        assert!(replica.buffered_broadcasts.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn checked_commands_are_decided_by_the_consensus() {
+        let mut replica = lone_replica().await;
+        let mut coordinator = Coordinator::new(replica.network_info().clone());
+        replica.consensus = Box::new(ChannelConsensus::oracle(&coordinator));
+        // The coordinator decides the round on the proposal of the replica, as n_ack = 1
+        tokio::spawn(async move {
+            if let Some(proposal) = coordinator.receiver().recv().await {
+                let round = proposal.1;
+                let (nc_set, c_set) = coordinator.propose(proposal).unwrap();
+                coordinator
+                    .broadcaster()
+                    .send((round, nc_set, c_set))
+                    .unwrap();
+            }
+        });
+        let command = Command::new(0, Action::Register);
+        let round = *replica.database.round();
+
+        replica.handle_command(command.clone());
+        replica.process_commands().await;
+        assert!(replica.database.pending().contains(&command));
+        assert_eq!(*replica.database.round(), round);
+
+        // The command was acknowledged on the fast path, its check still goes through a round
+        replica.handle_check(command.clone());
+        replica.process_commands().await;
+        assert!(replica.database.delivered().contains(&command));
+        assert_eq!(*replica.database.round(), round + 1);
+        assert!(replica.checks.is_empty());
+
+        // Delivered commands are not checked again
+        replica.handle_check(command);
+        assert!(replica.checks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rollbacks_follow_the_reverse_order_of_execution() {
+        let mut replica = lone_replica().await;
+        let registration = Command::new(0, Action::Register);
+        let deposit = Command::new(0, Action::Deposit(10));
+        let other = Command::new(1, Action::Register);
+
+        for command in [&registration, &deposit, &other] {
+            replica.execute(command);
+            replica.database.pending_mut().insert(command.clone());
+        }
+        assert_eq!(replica.application.get(&0), Some(10));
+
+        // Undoing the registration first would leave the deposit without an account
+        let nc_set: Set = vec![other.clone()].into_iter().collect();
+        replica.apply_decision(nc_set, Set::new()).await;
+        assert_eq!(replica.application.get(&0), None);
+        assert_eq!(replica.application.get(&1), Some(0));
+        assert!(replica.database.delivered().contains(&other));
+    }
+
+    #[tokio::test]
+    async fn records_the_final_state() {
+        let mut replica = lone_replica().await;
+        let registration = Command::new(0, Action::Register);
+        replica.handle_command(registration.clone());
+        replica.process_commands().await;
+        replica.record_final_state();
+
+        let finals = replica.network_info().decisions().finals();
+        let state = &finals[replica.id()];
+        assert_eq!(state.round, *replica.database.round());
+        assert!(state.delivered.is_empty());
+        assert!(state.pending.contains(registration.id()));
+        assert_eq!(state.state, Tracer::value(&replica.application.snapshot()));
+    }
+
+    #[tokio::test]
+    async fn rollbacks_of_unexecuted_commands_succeed() {
+        let mut replica = lone_replica().await;
+        replica.application.register(0);
+        replica.application.deposit(&0, 10).unwrap();
+
+        // A command received in pending but never executed has no result to undo
+        let withdraw = Command::new(0, Action::Withdraw(10));
+        replica
+            .rollback(&withdraw)
+            .expect("Rollback of an unexecuted command should succeed");
+        assert_eq!(replica.application.get(&0), Some(10));
+
+        replica.database.pending_mut().insert(withdraw);
+        replica.apply_decision(Set::new(), Set::new()).await;
+        assert_eq!(replica.application.get(&0), Some(10));
+    }
+
     #[tokio::test]
     async fn correctly_receives_incoming_commands() {
         let network_info = NetworkInfo::with_default_report_folder(0, 3, 0, 0, 10, 1);
@@ -422,22 +881,23 @@ mod tests {
 
         let coordinator = Coordinator::new(network_info.clone());
 
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .add_peer(replica3.clone())
-            .build();
-        let mut rh1 = ReplicaHandler::new(
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2))
+            .add_peer(Utils::keychain(&replica3));
+        let identity_table = identity_table_builder.build();
+        let mut rh1 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 0,
                 replica1.clone(),
+                identity_table_builder.keychain(&replica1).unwrap(),
                 sender1,
                 rx1,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
         let cmd = Command::new(0, Action::Register);
@@ -446,6 +906,174 @@ mod tests {
         assert_eq!(rh1.database.received().contains(&cmd), true);
     }
 
+    #[tokio::test]
+    async fn drops_forged_commands() {
+        let network_info = NetworkInfo::with_default_report_folder(2, 1, 0, 0, 10, 1);
+        let (keys, senders, mut receivers) = Utils::mock_network(3).await;
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&keys[0]))
+            .add_peer(Utils::keychain(&keys[1]))
+            .add_peer(Utils::keychain(&keys[2]));
+        let identity_table = identity_table_builder.build();
+        let mut senders = senders.into_iter();
+        let (client0, client1) = (senders.next().unwrap(), senders.next().unwrap());
+
+        let (rx, mut _tx) = FeedbackChannel::channel();
+        let coordinator = Coordinator::new(network_info.clone());
+        let mut replica = ReplicaHandler::<Banking>::new(
+            Communicator::new(
+                2,
+                keys[2].clone(),
+                identity_table_builder.keychain(&keys[2]).unwrap(),
+                senders.next().unwrap(),
+                rx,
+                network_info.clone(),
+                identity_table.clone(),
+            ),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
+        );
+        let mut receiver = receivers.pop().unwrap();
+
+        let sign = |signer: usize, command: &Command| {
+            identity_table_builder
+                .keychain(&keys[signer])
+                .unwrap()
+                .sign(&IssuedCommand { command })
+                .ok()
+        };
+        let unsigned = Command::new(0, Action::Register);
+        let impersonated = Command::new(0, Action::Register);
+        let forged = Command::new(0, Action::Register);
+        let unknown = Command::new(7, Action::Register);
+        let valid = Command::new(0, Action::Register);
+
+        let fuse = Fuse::new();
+        let messages = vec![
+            (&client0, Message::Command(unsigned.clone(), None)),
+            (
+                &client1,
+                Message::Command(impersonated.clone(), sign(1, &impersonated)),
+            ),
+            (&client0, Message::Command(forged.clone(), sign(1, &forged))),
+            (
+                &client0,
+                Message::Command(unknown.clone(), sign(0, &unknown)),
+            ),
+            (&client0, Message::Command(valid.clone(), sign(0, &valid))),
+        ];
+        for (sender, message) in messages {
+            sender.spawn_send(keys[2].clone(), message, &fuse);
+            let (id, message, ack) = timeout(Duration::from_secs(1), receiver.receive())
+                .await
+                .expect("Timeout");
+            replica.handle_message(id, message, ack).await;
+        }
+
+        for reason in [
+            CommandRejection::Unsigned,
+            CommandRejection::SenderMismatch,
+            CommandRejection::InvalidSignature,
+            CommandRejection::UnknownIssuer,
+        ] {
+            assert_eq!(replica.rejections().get(&reason), Some(&1));
+        }
+        let received = replica.database.received();
+        assert_eq!(received.contains(&valid), true);
+        for command in [unsigned, impersonated, forged, unknown].iter() {
+            assert_eq!(received.contains(command), false);
+        }
+    }
+
+    #[tokio::test]
+    async fn drops_forged_broadcasts() {
+        let network_info = NetworkInfo::with_default_report_folder(1, 2, 0, 0, 10, 1);
+        let (keys, senders, mut receivers) = Utils::mock_network(3).await;
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&keys[0]))
+            .add_peer(Utils::keychain(&keys[1]))
+            .add_peer(Utils::keychain(&keys[2]));
+        let identity_table = identity_table_builder.build();
+        let mut senders = senders.into_iter();
+        let (client, other_replica) = (senders.next().unwrap(), senders.next().unwrap());
+
+        let (rx, mut _tx) = FeedbackChannel::channel();
+        let coordinator = Coordinator::new(network_info.clone());
+        let mut replica = ReplicaHandler::<Banking>::new(
+            Communicator::new(
+                2,
+                keys[2].clone(),
+                identity_table_builder.keychain(&keys[2]).unwrap(),
+                senders.next().unwrap(),
+                rx,
+                network_info.clone(),
+                identity_table.clone(),
+            ),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
+        );
+        let mut receiver = receivers.pop().unwrap();
+
+        let sign = |signer: usize, command: &Command| {
+            identity_table_builder
+                .keychain(&keys[signer])
+                .unwrap()
+                .sign(&IssuedCommand { command })
+                .ok()
+        };
+        let valid = Command::new(0, Action::Register);
+        let unsigned = Command::new(0, Action::Register);
+        let forged = Command::new(0, Action::Register);
+        let relayed_by_client = Command::new(0, Action::Register);
+
+        let fuse = Fuse::new();
+        let from_replica: SignedSet = vec![
+            (valid.clone(), sign(0, &valid)),
+            (unsigned.clone(), None),
+            // Signed by the relaying replica instead of the issuer
+            (forged.clone(), sign(1, &forged)),
+        ]
+        .into_iter()
+        .collect();
+        let from_client: SignedSet = vec![(relayed_by_client.clone(), sign(0, &relayed_by_client))]
+            .into_iter()
+            .collect();
+        let messages = vec![
+            (
+                &other_replica,
+                Message::ReplicaBroadcast(1, from_replica, Phase::ACK),
+            ),
+            (
+                &client,
+                Message::ReplicaBroadcast(1, from_client, Phase::ACK),
+            ),
+        ];
+        for (sender, message) in messages {
+            sender.spawn_send(keys[2].clone(), message, &fuse);
+            let (id, message, ack) = timeout(Duration::from_secs(1), receiver.receive())
+                .await
+                .expect("Timeout");
+            replica.handle_message(id, message, ack).await;
+        }
+
+        for reason in [
+            CommandRejection::Unsigned,
+            CommandRejection::InvalidSignature,
+            CommandRejection::NotReplica,
+        ] {
+            assert_eq!(replica.rejections().get(&reason), Some(&1));
+        }
+        let received = replica.database.received();
+        assert_eq!(received.contains(&valid), true);
+        for command in [unsigned, forged, relayed_by_client].iter() {
+            assert_eq!(received.contains(command), false);
+        }
+
+        // The replica relays the signature of the issuer along with the command
+        let relayed = replica.sign_set(vec![valid.clone()].into_iter().collect());
+        assert_eq!(relayed.get(&valid), Some(&sign(0, &valid)));
+    }
+
     #[tokio::test]
     async fn correctly_receives_broadcast() {
         /* Template for a Network of 3 replicas */
@@ -464,48 +1092,49 @@ mod tests {
 
         let coordinator = Coordinator::new(network_info.clone());
 
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .add_peer(replica3.clone())
-            .build();
-        let mut rh1 = ReplicaHandler::new(
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2))
+            .add_peer(Utils::keychain(&replica3));
+        let identity_table = identity_table_builder.build();
+        let mut rh1 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 0,
                 replica1.clone(),
+                identity_table_builder.keychain(&replica1).unwrap(),
                 sender1,
                 rx1,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
-        let mut rh2 = ReplicaHandler::new(
+        let mut rh2 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 0,
                 replica2.clone(),
+                identity_table_builder.keychain(&replica2).unwrap(),
                 sender2,
                 rx2,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
-        let mut _rh3 = ReplicaHandler::new(
+        let mut _rh3 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 0,
                 replica3.clone(),
+                identity_table_builder.keychain(&replica3).unwrap(),
                 sender3,
                 rx3,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
         /* ______________________ */
 
@@ -545,48 +1174,49 @@ mod tests {
 
         let coordinator = Coordinator::new(network_info.clone());
 
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .add_peer(replica3.clone())
-            .build();
-        let mut rh1 = ReplicaHandler::new(
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2))
+            .add_peer(Utils::keychain(&replica3));
+        let identity_table = identity_table_builder.build();
+        let mut rh1 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 0,
                 replica1.clone(),
+                identity_table_builder.keychain(&replica1).unwrap(),
                 sender1,
                 rx1,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
-        let mut _rh2 = ReplicaHandler::new(
+        let mut _rh2 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 0,
                 replica2.clone(),
+                identity_table_builder.keychain(&replica2).unwrap(),
                 sender2,
                 rx2,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
-        let mut _rh3 = ReplicaHandler::new(
+        let mut _rh3 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 0,
                 replica3.clone(),
+                identity_table_builder.keychain(&replica3).unwrap(),
                 sender3,
                 rx3,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
         let cmd1 = Command::new(5, Action::Register);
@@ -649,44 +1279,46 @@ mod tests {
 
         let coordinator = Coordinator::new(network_info.clone());
 
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .add_peer(replica3.clone())
-            .build();
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2))
+            .add_peer(Utils::keychain(&replica3));
+        let identity_table = identity_table_builder.build();
         let mut _client = ClientHandler::new(Communicator::new(
             0,
             replica1.clone(),
+            identity_table_builder.keychain(&replica1).unwrap(),
             sender1,
             rx1,
             network_info.clone(),
             identity_table.clone(),
         ));
 
-        let rh2 = ReplicaHandler::new(
+        let rh2 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 1,
                 replica2.clone(),
+                identity_table_builder.keychain(&replica2).unwrap(),
                 sender2,
                 rx2,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
-        let mut _rh3 = ReplicaHandler::new(
+        let mut _rh3 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 2,
                 replica3.clone(),
+                identity_table_builder.keychain(&replica3).unwrap(),
                 sender3,
                 rx3,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
         let cmd = Command::new(0, Action::Register);
@@ -704,10 +1336,23 @@ mod tests {
         let (id, msg, _) = timeout(Duration::from_secs(1), receiver1.receive())
             .await
             .unwrap();
-        assert_eq!(
-            msg,
-            Message::CommandAcknowledgement(cmd.clone(), 1, result.clone(), Phase::ACK)
-        );
+        match msg {
+            Message::CommandAcknowledgement(command, k, res, phase, signature) => {
+                assert_eq!(
+                    (command.clone(), k, res.clone(), phase.clone()),
+                    (cmd.clone(), 1, result.clone(), Phase::ACK)
+                );
+                let statement = CommandAcknowledgement {
+                    id: command.id(),
+                    round: &k,
+                    result: &res,
+                    phase: &phase,
+                };
+                let keycard = identity_table.keycard(&replica2).unwrap();
+                assert_eq!(signature.verify(keycard, &statement).is_ok(), true);
+            }
+            _ => panic!("Expected a CommandAcknowledgement"),
+        }
         assert_eq!(id, replica2.clone());
 
         let cmd2 = Command::new(35, Action::Get);
@@ -725,7 +1370,7 @@ mod tests {
     #[tokio::test]
     async fn correctly_recover_consensus() {
         let network_info = NetworkInfo::with_default_report_folder(1, 3, 2, 0, 10, 3);
-        let mut mock_network = UnicastSystem::<Message>::setup(6).await;
+        let mut mock_network = Utils::unicast_system(6).await;
 
         let (client1, client_sender1, mut _client_receiver1) =
             Utils::pop_from_network(&mut mock_network);
@@ -739,55 +1384,57 @@ mod tests {
         let (rx1, mut _tx1) = FeedbackChannel::channel();
         let (rx2, mut _tx2) = FeedbackChannel::channel();
         let (rx3, mut _tx3) = FeedbackChannel::channel();
-        let (_rx4, mut _tx4) = FeedbackChannel::channel();
+        let (_rx4, mut _tx4) = FeedbackChannel::channel::<Action>();
 
         let coordinator = Coordinator::new(network_info.clone());
 
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .add_peer(replica3.clone())
-            .build();
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2))
+            .add_peer(Utils::keychain(&replica3));
+        let identity_table = identity_table_builder.build();
         let mut _client = ClientHandler::new(Communicator::new(
             0,
             client1.clone(),
+            Arc::new(Utils::keychain(&client1)),
             client_sender1,
             rx1,
             network_info.clone(),
             identity_table.clone(),
         ));
 
-        let mut _rh2 = ReplicaHandler::new(
+        let mut _rh2 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 1,
                 replica2.clone(),
+                identity_table_builder.keychain(&replica2).unwrap(),
                 replica_sender2,
                 rx2,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
-        let mut _rh3 = ReplicaHandler::new(
+        let mut _rh3 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 2,
                 replica3.clone(),
+                identity_table_builder.keychain(&replica3).unwrap(),
                 replica_sender3,
                 rx3,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
         // Replica handlers must be runned on different threads
     }
     #[tokio::test]
     async fn process_non_conflicting_commands_correctly() {
         let network_info = NetworkInfo::with_default_report_folder(1, 3, 0, 2, 10, 3);
-        let mut mock_network = UnicastSystem::<Message>::setup(6).await;
+        let mut mock_network = Utils::unicast_system(6).await;
         let (client1, _cli_ent_sender1, mut client_receiver1) =
             Utils::pop_from_network(&mut mock_network);
         let (replica2, _replica_sender2, mut replica_receiver2) =
@@ -805,25 +1452,26 @@ mod tests {
 
         let coordinator = Coordinator::new(network_info.clone());
 
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(client1.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .add_peer(replica3.clone())
-            .build();
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&client1))
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2))
+            .add_peer(Utils::keychain(&replica3));
+        let identity_table = identity_table_builder.build();
         println!("TABLE: {:#?}", identity_table);
 
-        let mut rh1 = ReplicaHandler::new(
+        let mut rh1 = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 1,
                 replica1.clone(),
+                identity_table_builder.keychain(&replica1).unwrap(),
                 replica_sender1,
                 rx1,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
         // Replica handlers must be runned on different threads
@@ -840,8 +1488,10 @@ mod tests {
         rh1.database.delivered_mut().insert(cmd3.clone());
         rh1.database.pending_mut().insert(cmd4.clone());
 
-        rh1.banking.register(0);
-        rh1.banking.deposit(&0, 4).expect("Failed to execute cmd 4");
+        rh1.application.register(0);
+        rh1.application
+            .deposit(&0, 4)
+            .expect("Failed to execute cmd 4");
         rh1.database.increment_round();
         rh1.database.increment_round();
 
@@ -850,7 +1500,7 @@ mod tests {
         }
 
         let mut i = 0;
-        while let Ok((_, Message::CommandAcknowledgement(_, k, res, phase), _)) =
+        while let Ok((_, Message::CommandAcknowledgement(_, k, res, phase, _), _)) =
             timeout(Duration::from_secs(1), client_receiver1.receive()).await
         {
             assert_eq!(phase, Phase::ACK);
@@ -870,6 +1520,8 @@ mod tests {
         set2.insert(cmd1.clone());
         set2.insert(cmd2.clone());
         set2.insert(cmd4.clone());
+        // The commands were not received from their issuer, so no signature is relayed
+        let set2 = set2.into_iter().map(|command| (command, None)).collect();
         let broadcast = Message::ReplicaBroadcast(3, set2, Phase::ACK);
         let (_, recv, _) = timeout(Duration::from_secs(10), replica_receiver2.receive())
             .await
@@ -898,58 +1550,13 @@ mod tests {
         assert_eq!(rh1.database.results().contains_key(&cmd1), true);
         assert_eq!(rh1.database.results().contains_key(&cmd2), true);
 
-        assert_eq!(rh1.banking.get(&0), Some(7));
-    }
-
-    #[tokio::test]
-    async fn is_conflict_working() {
-        /*
-        let mut set: Set = BTreeSet::new();
-
-        let network_info = NetworkInfo::new(0, 1, 0, 0, 10, 3);
-        let mut mock_network = UnicastSystem::<Message>::setup(6).await;
-        let (replica1, replica_sender1, mut _replica_receiver1) =
-            Utils::pop_from_network(&mut mock_network);
-        let (rx1, mut _tx1) = FeedbackChannel::channel();
-        let coordinator = Coordinator::new(network_info.clone());
-
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(replica1.clone())
-            .build();
-        println!("TABLE: {:#?}", identity_table);
-
-        let mut replica = ReplicaHandler::new(
-            Communicator::new(
-                1,
-                replica1.clone(),
-                replica_sender1,
-                rx1,
-                network_info.clone(),
-                identity_table.clone(),
-            ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
-        );
-
-        set.insert(Command::new(0, Action::Deposit(10)));
-        set.insert(Command::new(1, Action::Get));
-        replica.handle_replica_broadcast(0, set.clone(), Phase::ACK);
-
-        assert_eq!(replica.is_there_conflict(&set), false);
-
-        set.insert(Command::new(0, Action::Register));
-
-        assert_eq!(ReplicaHandler::is_there_conflict(&set), true);
-        set.insert(Command::new(0, Action::Withdraw(3)));
-
-        assert_eq!(ReplicaHandler::is_there_conflict(&set), true);
-        */
+        assert_eq!(rh1.application.get(&0), Some(7));
     }
 
     #[tokio::test]
     async fn execute_correctly() {
         let network_info = NetworkInfo::with_default_report_folder(1, 3, 0, 2, 10, 3);
-        let mut mock_network = UnicastSystem::<Message>::setup(6).await;
+        let mut mock_network = Utils::unicast_system(6).await;
         let (client1, _client_sender1, mut _client_receiver1) =
             Utils::pop_from_network(&mut mock_network);
         let (replica2, _replica_sender2, mut _replica_receiver2) =
@@ -967,25 +1574,26 @@ mod tests {
 
         let coordinator = Coordinator::new(network_info.clone());
 
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(client1.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .add_peer(replica3.clone())
-            .build();
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&client1))
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2))
+            .add_peer(Utils::keychain(&replica3));
+        let identity_table = identity_table_builder.build();
         println!("TABLE: {:#?}", identity_table);
 
-        let mut replica = ReplicaHandler::new(
+        let mut replica = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 1,
                 replica1.clone(),
+                identity_table_builder.keychain(&replica1).unwrap(),
                 replica_sender1,
                 rx1,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
         let registration = Command::new(0, Action::Register);
@@ -1039,7 +1647,7 @@ mod tests {
     #[tokio::test]
     async fn correctly_rollback_commands() {
         let network_info = NetworkInfo::with_default_report_folder(1, 3, 0, 2, 10, 3);
-        let mut mock_network = UnicastSystem::<Message>::setup(6).await;
+        let mut mock_network = Utils::unicast_system(6).await;
         let (client1, _client_sender1, mut _client_receiver1) =
             Utils::pop_from_network(&mut mock_network);
         let (replica2, _replica_sender2, mut _replica_receiver2) =
@@ -1057,25 +1665,26 @@ mod tests {
 
         let coordinator = Coordinator::new(network_info.clone());
 
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(client1.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .add_peer(replica3.clone())
-            .build();
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&client1))
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2))
+            .add_peer(Utils::keychain(&replica3));
+        let identity_table = identity_table_builder.build();
         println!("TABLE: {:#?}", identity_table);
 
-        let mut replica = ReplicaHandler::new(
+        let mut replica = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 1,
                 replica1.clone(),
+                identity_table_builder.keychain(&replica1).unwrap(),
                 replica_sender1,
                 rx1,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
         let registration = Command::new(0, Action::Register);
@@ -1088,9 +1697,9 @@ mod tests {
         let __rollback = replica
             .rollback(&registration)
             .expect("Unregister should be successful");
-        assert_eq!(replica.banking.get(&0), None);
+        assert_eq!(replica.application.get(&0), None);
 
-        replica.banking.register(0);
+        replica.application.register(0);
         let res = replica.execute(&deposit);
         replica.database.add_result(deposit.clone(), res);
         replica.execute(&Command::new(1, Action::Deposit(10)));
@@ -1098,26 +1707,26 @@ mod tests {
             .rollback(&deposit)
             .expect("Deposit should be removed");
 
-        assert_eq!(replica.banking.get(&0), Some(0));
+        assert_eq!(replica.application.get(&0), Some(0));
 
-        replica.banking.register(0);
-        replica.banking.register(1);
-        replica.banking.deposit(&1, 12).unwrap();
-        replica.banking.deposit(&0, 10).unwrap();
+        replica.application.register(0);
+        replica.application.register(1);
+        replica.application.deposit(&1, 12).unwrap();
+        replica.application.deposit(&0, 10).unwrap();
         let res = replica.execute(&withdraw);
         replica.database.add_result(withdraw.clone(), res);
         replica
             .rollback(&withdraw)
             .expect("Rollback should succeed");
 
-        assert_eq!(replica.banking.get(&0), Some(10));
-        assert_eq!(replica.banking.get(&1), Some(12));
+        assert_eq!(replica.application.get(&0), Some(10));
+        assert_eq!(replica.application.get(&1), Some(12));
     }
 
     #[tokio::test]
     async fn correctly_execute_conflicting_messages() {
         let network_info = NetworkInfo::with_default_report_folder(2, 3, 0, 2, 10, 3);
-        let mut mock_network = UnicastSystem::<Message>::setup(7).await;
+        let mut mock_network = Utils::unicast_system(7).await;
         let (client1, _client_sender1, mut client_receiver1) =
             Utils::pop_from_network(&mut mock_network);
         let (client2, _client_sender2, mut client_receiver2) =
@@ -1142,34 +1751,35 @@ mod tests {
 
         let mut coordinator = Coordinator::new(network_info.clone());
 
-        let identity_table = IdentityTableBuilder::new(network_info.clone())
-            .add_peer(client1.clone())
-            .add_peer(client2.clone())
-            .add_peer(replica1.clone())
-            .add_peer(replica2.clone())
-            .add_peer(replica3.clone())
-            .add_peer(faulty_replica1.clone())
-            .add_peer(faulty_replica2.clone())
-            .build();
+        let mut identity_table_builder = IdentityTableBuilder::new(network_info.clone());
+        identity_table_builder
+            .add_peer(Utils::keychain(&client1))
+            .add_peer(Utils::keychain(&client2))
+            .add_peer(Utils::keychain(&replica1))
+            .add_peer(Utils::keychain(&replica2))
+            .add_peer(Utils::keychain(&replica3))
+            .add_peer(Utils::keychain(&faulty_replica1))
+            .add_peer(Utils::keychain(&faulty_replica2));
+        let identity_table = identity_table_builder.build();
         println!("TABLE: {:#?}", identity_table);
 
-        let mut replica = ReplicaHandler::new(
+        let mut replica = ReplicaHandler::<Banking>::new(
             Communicator::new(
                 1,
                 replica1.clone(),
+                identity_table_builder.keychain(&replica1).unwrap(),
                 replica_sender1,
                 rx1,
                 network_info.clone(),
                 identity_table.clone(),
             ),
-            coordinator.proposer(),
-            coordinator.subscribe(),
+            Box::new(ChannelConsensus::oracle(&coordinator)),
         );
 
         // We previously register different clients
 
         for i in 0..2 {
-            replica.banking.register(i);
+            replica.application.register(i);
         }
 
         // Define the commands
@@ -1224,7 +1834,7 @@ mod tests {
         let (unprocessed, r_dif_g) = replica.compute_unprocessed_commands();
         println!("Unprocessed: {:#?}, dif: {:#?}", unprocessed, r_dif_g);
 
-        assert_eq!(ReplicaHandler::is_pending(&unprocessed), true);
+        assert_eq!(ReplicaHandler::<Banking>::is_pending(&unprocessed), true);
         //assert_eq!(ReplicaHandler::is_there_conflict(&r_dif_g), true);
 
         let mut nc_set = BTreeSet::<Command>::new();
@@ -1266,7 +1876,7 @@ mod tests {
             match msg {
                 Message::ReplicaBroadcast(k, set, phase) => {
                     assert_eq!(k, round);
-                    assert_eq!(set, set_test);
+                    assert_eq!(set.into_keys().collect::<Set>(), set_test);
                     assert_eq!(phase, Phase::CHK);
                 }
                 _ => panic!("Wrong broadcast"),
@@ -1277,7 +1887,7 @@ mod tests {
             .await
             .expect("Client #0 fails");
         match msg {
-            Message::CommandAcknowledgement(cmd, k, res, phase) => {
+            Message::CommandAcknowledgement(cmd, k, res, phase, _) => {
                 if cmd.ne(&cmd1) && cmd.ne(&cmd13) {
                     panic!();
                 }
@@ -1291,7 +1901,7 @@ mod tests {
             .await
             .expect("Client #0 fails");
         match msg {
-            Message::CommandAcknowledgement(cmd, k, res, phase) => {
+            Message::CommandAcknowledgement(cmd, k, res, phase, _) => {
                 if cmd.ne(&cmd8) && cmd.ne(&cmd12) {
                     panic!();
                 }
@@ -1306,7 +1916,7 @@ mod tests {
             .await
             .expect("Client #0 fails");
         match msg {
-            Message::CommandAcknowledgement(cmd, k, res, phase) => {
+            Message::CommandAcknowledgement(cmd, k, res, phase, _) => {
                 if cmd.ne(&cmd8) && cmd.ne(&cmd12) {
                     panic!();
                 }
@@ -1321,7 +1931,7 @@ mod tests {
             .await
             .expect("Client #0 fails");
         match msg {
-            Message::CommandAcknowledgement(cmd, k, res, phase) => {
+            Message::CommandAcknowledgement(cmd, k, res, phase, _) => {
                 if cmd.ne(&cmd1) && cmd.ne(&cmd13) {
                     panic!();
                 }
@@ -1341,8 +1951,8 @@ mod tests {
             println!("{}", log);
         }
 
-        assert_eq!(replica.banking.get(&0), Some(16));
-        assert_eq!(replica.banking.get(&1), Some(43));
+        assert_eq!(replica.application.get(&0), Some(16));
+        assert_eq!(replica.application.get(&1), Some(43));
 
         replica.write_logs();
     }
@@ -1,3 +1,4 @@
+pub mod consensus;
 pub mod coordinator;
 pub mod handler;
 pub mod peer;
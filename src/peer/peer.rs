@@ -1,33 +1,46 @@
-use talk::{unicast::Message};
+use talk::{crypto::Identity, sync::fuse::Fuse, unicast::Acknowledger};
+use tokio::sync::mpsc;
 
 use super::{handler::Handler, runner::Runner};
-use crate::{network::network_info::NetworkInfo, talk::Instruction, types::*};
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    network::{
+        network_info::NetworkInfo,
+        trace::{Event, Tracer},
+    },
+    talk::{Instruction, Message},
+    types::*,
+};
 pub type PeerId = usize;
-pub struct Peer<T: UnicastMessage> {
-    receiver: UnicastReceiver<T>,
-    network_outlet: InstructionReceiver,
-    handler: Box<dyn Handler<T>>,
+
+const BUFFER_SIZE: usize = 100;
+
+pub struct Peer<O: Operation = Action> {
+    receiver: UnicastReceiver<Message<O>>,
+    network_outlet: InstructionReceiver<O>,
+    handler: Box<dyn Handler<O>>,
+    consensus_inlet: Option<ConsensusInlet<O>>,
 }
 
 /// Structure that defines a `Peer`, i.e. an entity that can send and receive `Message`
 /// Its behavior is defined by a `PeerRunner`
 
-impl<T> Peer<T>
-where
-    T: UnicastMessage,
-{
+impl<O: Operation> Peer<O> {
     /// Create a new `Peer`from the given arguments
     ///
     /// This is essentially a single unit of a `UnicastSystem` from talk crate
     pub fn new(
-        receiver: UnicastReceiver<T>,
-        network_outlet: InstructionReceiver,
-        handler: Box<dyn Handler<T>>,
+        receiver: UnicastReceiver<Message<O>>,
+        network_outlet: InstructionReceiver<O>,
+        handler: Box<dyn Handler<O>>,
     ) -> Self {
+        let consensus_inlet = handler.consensus_inlet();
         Peer {
             receiver,
             network_outlet,
             handler,
+            consensus_inlet,
         }
     }
 
@@ -38,13 +51,35 @@ where
     pub fn network_info(&self) -> &NetworkInfo {
         self.handler.network_info()
     }
+
+    /// Forwards the consensus messages to the consensus instance of the peer, and the others to
+    /// the handler. The consensus thus keeps on receiving messages while the handler waits for
+    /// one of its decisions.
+    async fn dispatch(
+        mut receiver: UnicastReceiver<Message<O>>,
+        handler_inlet: mpsc::Sender<(Identity, Message<O>, Acknowledger)>,
+        consensus_inlet: Option<ConsensusInlet<O>>,
+    ) {
+        loop {
+            let (id, message, acknowledger) = receiver.receive().await;
+            let delivered = match &consensus_inlet {
+                Some(consensus_inlet) if message.is_consensus() => {
+                    consensus_inlet.send((id, message)).await.is_ok()
+                }
+                _ => handler_inlet
+                    .send((id, message, acknowledger))
+                    .await
+                    .is_ok(),
+            };
+            if !delivered {
+                break;
+            }
+        }
+    }
 }
 
 #[async_trait::async_trait]
-impl<T> Runner for Peer<T>
-where
-    T: Message,
-{
+impl<O: Operation> Runner for Peer<O> {
     async fn run(mut self) {
         let id = self.id().clone();
         let network_info = self.network_info().clone();
@@ -54,20 +89,34 @@ where
             id
         );
 
+        let (handler_inlet, mut handler_outlet) = mpsc::channel(BUFFER_SIZE);
+        let fuse = Fuse::new();
+        fuse.spawn(Self::dispatch(
+            self.receiver,
+            handler_inlet,
+            self.consensus_inlet.take(),
+        ));
+
         let handler = &mut self.handler;
         loop {
             tokio::select! {
-                (id, message, acknowledger) = self.receiver.receive() => {
+                Some((id, message, acknowledger)) = handler_outlet.recv() => {
                     handler.handle_message(id, message, acknowledger).await;
                 }
 
                 Some(instruction) = self.network_outlet.recv() => {
                     //println!("[{:#?}] #{} received instruction", network_info.elapsed().unwrap() ,id);
+                    let tracer = network_info.tracer();
                     match instruction {
                         Instruction::Shutdown => {
+                            tracer.record(id, None, || Event::Shutdown);
                             handler.handle_instruction(Instruction::Shutdown).await;
                             break;
                         },
+                        Instruction::Execute(ref command) => {
+                            tracer.record(id, None, || Event::Instruction(Tracer::command(command)));
+                            handler.handle_instruction(instruction).await
+                        },
                         _ => handler.handle_instruction(instruction).await,
                     }
                 }
@@ -77,7 +126,7 @@ where
         println!(
             "[{:#?}] Peer #{}: shutdown",
             network_info.elapsed().unwrap(),
-            self.id()
+            id
         )
     }
 }
@@ -14,9 +14,11 @@ pub struct ConflictingRelation;
 
 impl ConflictingRelation {
     pub fn is_conflicting(set1: &BTreeSet<Command>, set2: &BTreeSet<Command>) -> bool {
-        let mut set2 = set2.clone().into_iter();
         for elem1 in set1.iter() {
-            if set2.any(|elem2| Self::is_related(&elem1, &elem2) && elem1.ne(&elem2)) {
+            if set2
+                .iter()
+                .any(|elem2| Self::is_related(&elem1, &elem2) && elem1.ne(&elem2))
+            {
                 return true;
             }
         }
@@ -42,3 +44,64 @@ impl Relation<Command> for ConflictingRelation {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::generator::Generator;
+
+    use super::*;
+
+    #[test]
+    fn is_conflicting_checks_every_pair() {
+        let set: BTreeSet<Command> = vec![
+            Command::new(0, Action::Withdraw(1)),
+            Command::new(1, Action::Deposit(1)),
+            Command::new(2, Action::Deposit(1)),
+            Command::new(2, Action::Withdraw(1)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(ConflictingRelation::is_conflicting(&set, &set), true);
+
+        let set: BTreeSet<Command> = vec![
+            Command::new(0, Action::Withdraw(1)),
+            Command::new(1, Action::Deposit(1)),
+            Command::new(2, Action::Deposit(1)),
+            Command::new(2, Action::Deposit(2)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(ConflictingRelation::is_conflicting(&set, &set), false);
+    }
+
+    #[test]
+    fn is_related_is_symmetric_and_irreflexive() {
+        Generator::check_relation::<ConflictingRelation, _, _>(|generator| generator.command(3));
+    }
+
+    #[test]
+    fn is_conflicting_is_symmetric() {
+        Generator::check(|generator| {
+            let commands = generator.commands(12);
+            let (set1, set2) = (generator.subset(&commands), generator.subset(&commands));
+            let conflicting = ConflictingRelation::is_conflicting(&set1, &set2);
+            if conflicting != ConflictingRelation::is_conflicting(&set2, &set1) {
+                return Err(format!(
+                    "is_conflicting is not symmetric on {:?}, {:?}",
+                    set1, set2
+                ));
+            }
+            // Two sets conflict iff some pair of their commands is related
+            let related = set1
+                .iter()
+                .any(|x| set2.iter().any(|y| ConflictingRelation::is_related(x, y)));
+            if conflicting != related {
+                return Err(format!(
+                    "{:?} and {:?} conflict: {}",
+                    set1, set2, conflicting
+                ));
+            }
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{kv::action::KvAction, talk::Command};
+
+use super::Relation;
+
+/// Define the conflicting relation between key-value operations, regardless of their issuer:
+/// two commands conflict if one of them writes a key the other one accesses.
+/// Thus, operations on disjoint keys commute, and reads commute with reads.
+pub struct KeyRelation;
+
+impl Relation<Command<KvAction>> for KeyRelation {
+    fn is_related(x: &Command<KvAction>, y: &Command<KvAction>) -> bool {
+        if x.eq(y) {
+            return false;
+        }
+        let (x, y) = (x.action(), y.action());
+        !x.writes().is_disjoint(&y.keys()) || !y.writes().is_disjoint(&x.keys())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::generator::Generator;
+
+    use super::*;
+
+    fn command(issuer: usize, action: KvAction) -> Command<KvAction> {
+        Command::new(issuer, action)
+    }
+
+    #[test]
+    fn only_writes_to_shared_keys_conflict() {
+        let get = |key: &str| command(0, KvAction::Get(String::from(key)));
+        let put = |key: &str| command(1, KvAction::Put(String::from(key), String::from("1")));
+        assert_eq!(KeyRelation::is_related(&get("a"), &get("a")), false);
+        assert_eq!(KeyRelation::is_related(&put("a"), &put("b")), false);
+        assert_eq!(KeyRelation::is_related(&get("a"), &put("b")), false);
+        assert_eq!(KeyRelation::is_related(&get("a"), &put("a")), true);
+        assert_eq!(KeyRelation::is_related(&put("a"), &put("a")), true);
+
+        // A batch conflicts through any of its actions
+        let batch = command(
+            2,
+            KvAction::Batch(vec![
+                KvAction::Get(String::from("a")),
+                KvAction::Delete(String::from("b")),
+            ]),
+        );
+        assert_eq!(KeyRelation::is_related(&batch, &get("a")), false);
+        assert_eq!(KeyRelation::is_related(&batch, &get("b")), true);
+        assert_eq!(KeyRelation::is_related(&batch, &put("a")), true);
+    }
+
+    #[test]
+    fn is_related_is_symmetric_and_irreflexive() {
+        Generator::check_relation::<KeyRelation, _, _>(|generator| generator.kv_command(3));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod conflict;
+pub mod key;
 
 pub trait Relation<T> {
     fn is_related(x: &T, y: &T) -> bool;
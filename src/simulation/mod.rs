@@ -0,0 +1,11 @@
+pub mod record;
+pub mod scenario_file;
+pub mod scenarios;
+pub mod simulated;
+pub mod simulation;
+
+pub use record::{Run, ScenarioRecord};
+pub use scenario_file::ScenarioFile;
+pub use scenarios::Scenarios;
+pub use simulated::{ApplicationKind, Simulated};
+pub use simulation::Simulation;
@@ -0,0 +1,230 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    network::{
+        Completion, Counterexample, Divergence, LatencySummary, MetricsSnapshot, NemesisEvent,
+        Topology,
+    },
+    simulation::scenarios::ScenarioParameters,
+    talk::Phase,
+};
+
+/// Execution of one simulation of a scenario
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Run<O: Operation = Action> {
+    pub duration: Duration,
+    pub completions: Vec<Completion<O>>,
+    pub applied_events: Vec<(Duration, NemesisEvent)>,
+    /// Metrics of the network at the end of the execution
+    pub metrics: MetricsSnapshot,
+    /// Disagreement of the correct replicas at the end of the execution, if any
+    pub divergence: Option<Divergence>,
+    /// History of a correct client that is not linearizable, if any
+    pub counterexample: Option<Counterexample>,
+}
+
+/// Parameters and results of a scenario, written to the reports of `Scenarios`.
+/// Durations are in s and latencies in ms, as in the parameters.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioRecord {
+    pub title: String,
+    /// Seed of the `Scenarios` that generated the scenario
+    pub seed: u64,
+    /// Number of repetitions
+    pub precision: usize,
+    /// Number of commands of each repetition
+    pub commands: usize,
+    pub transmission_delay: u64,
+    pub consensus_duration: f64,
+    pub probability_of_conflict: f64,
+    pub clients: usize,
+    pub replicas: usize,
+    pub faulty_replicas: usize,
+    pub topology: String,
+    pub application: String,
+    /// Average duration of a repetition
+    pub duration: f64,
+    pub latency_p50: f64,
+    pub latency_p90: f64,
+    pub latency_p99: f64,
+    pub latency_max: f64,
+    /// Number of commands completed in `Phase::ACK`, over all repetitions
+    pub fast_path: usize,
+    /// Number of commands completed in `Phase::CHK`, over all repetitions
+    pub slow_path: usize,
+    /// Highest number of rounds reached by a repetition
+    pub rounds: usize,
+    /// Number of repetitions whose correct replicas diverged
+    pub divergences: usize,
+}
+
+const CSV_HEADER: &str = "title,seed,precision,commands,transmission_delay,consensus_duration,\
+probability_of_conflict,clients,replicas,faulty_replicas,topology,application,duration,\
+latency_p50,latency_p90,latency_p99,latency_max,fast_path,slow_path,rounds,divergences";
+
+impl ScenarioRecord {
+    pub fn new<O: Operation>(
+        title: String,
+        seed: u64,
+        parameters: &ScenarioParameters,
+        commands: usize,
+        topology: &Topology,
+        runs: &[Run<O>],
+    ) -> Self {
+        let completions = runs.iter().flat_map(|run| run.completions.iter());
+        let latency = LatencySummary::new(completions.clone().map(|c| c.latency));
+        let in_phase = |phase: Phase| completions.clone().filter(|c| c.phase == phase).count();
+        let duration = runs
+            .iter()
+            .map(|run| run.duration.as_secs_f64())
+            .sum::<f64>()
+            / runs.len().max(1) as f64;
+        let rounds = runs
+            .iter()
+            .filter_map(|run| run.completions.iter().map(|c| c.round + 1).max())
+            .max()
+            .unwrap_or(0);
+
+        ScenarioRecord {
+            title,
+            seed,
+            precision: runs.len(),
+            commands,
+            transmission_delay: parameters.transmission_delay,
+            consensus_duration: parameters.consensus_duration,
+            probability_of_conflict: parameters.probability_of_conflict,
+            clients: parameters.clients,
+            replicas: parameters.replicas,
+            faulty_replicas: parameters.faulty_replicas,
+            topology: topology.to_string(),
+            application: parameters.application.to_string(),
+            duration,
+            latency_p50: Self::ms(latency.p50),
+            latency_p90: Self::ms(latency.p90),
+            latency_p99: Self::ms(latency.p99),
+            latency_max: Self::ms(latency.max),
+            fast_path: in_phase(Phase::ACK),
+            slow_path: in_phase(Phase::CHK),
+            rounds,
+            divergences: runs.iter().filter(|run| run.divergence.is_some()).count(),
+        }
+    }
+
+    fn ms(duration: Duration) -> f64 {
+        duration.as_secs_f64() * 1000.0
+    }
+
+    pub fn csv_header() -> &'static str {
+        CSV_HEADER
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            Self::escape(&self.title),
+            self.seed,
+            self.precision,
+            self.commands,
+            self.transmission_delay,
+            self.consensus_duration,
+            self.probability_of_conflict,
+            self.clients,
+            self.replicas,
+            self.faulty_replicas,
+            Self::escape(&self.topology),
+            self.application,
+            self.duration,
+            self.latency_p50,
+            self.latency_p90,
+            self.latency_p99,
+            self.latency_max,
+            self.fast_path,
+            self.slow_path,
+            self.rounds,
+            self.divergences
+        )
+    }
+
+    /// Quotes a CSV field
+    fn escape(field: &str) -> String {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("A record is always serializable")
+    }
+
+    /// Lines of the text report of the scenario
+    pub fn report(&self) -> Vec<String> {
+        vec![
+            format!("SCENARIO: {} \n \t > PARAMS: \n\t\t - precision: (number of generated commands: {}, repetitions: {}) \n\t\t - transmission delay: {}ms \n\t\t - consensus duration: {}s \n\t\t - probability of conflict: {} \n\t\t - clients: {} \n\t\t - replica: {} (with faulty: {}) \n\t\t - topology: {} \n\t\t - application: {}", self.title, self.commands, self.precision, self.transmission_delay, self.consensus_duration, self.probability_of_conflict, self.clients, self.replicas + self.faulty_replicas, self.faulty_replicas, self.topology, self.application),
+            format!("\t > COMPLETE IN: {:#?}", Duration::from_secs_f64(self.duration)),
+            format!("\t > LATENCY: p50 {}ms, p90 {}ms, p99 {}ms, max {}ms", self.latency_p50, self.latency_p90, self.latency_p99, self.latency_max),
+            format!("\t > PATHS: {} fast, {} slow, in {} rounds", self.fast_path, self.slow_path, self.rounds),
+            format!("\t > SAFETY: {} of {} runs diverged", self.divergences, self.precision),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completion(phase: Phase, round: usize, latency: u64) -> Completion {
+        Completion {
+            client: 0,
+            action: Action::Get,
+            round,
+            phase,
+            latency: Duration::from_millis(latency),
+        }
+    }
+
+    #[test]
+    fn records_are_aggregated_over_the_runs() {
+        let runs = vec![
+            Run {
+                duration: Duration::from_secs(1),
+                completions: vec![completion(Phase::ACK, 0, 10), completion(Phase::CHK, 2, 30)],
+                applied_events: Vec::new(),
+                metrics: MetricsSnapshot::default(),
+                divergence: None,
+                counterexample: None,
+            },
+            Run {
+                duration: Duration::from_secs(3),
+                completions: vec![completion(Phase::ACK, 1, 20)],
+                applied_events: Vec::new(),
+                metrics: MetricsSnapshot::default(),
+                divergence: None,
+                counterexample: None,
+            },
+        ];
+        let record = ScenarioRecord::new(
+            String::from("a \"quoted\", title"),
+            7,
+            &ScenarioParameters::default(),
+            2,
+            &Topology::Uniform,
+            &runs,
+        );
+        assert_eq!(record.precision, 2);
+        assert_eq!(record.duration, 2.0);
+        assert_eq!(record.latency_p50, 20.0);
+        assert_eq!(record.latency_max, 30.0);
+        assert_eq!(
+            (record.fast_path, record.slow_path, record.rounds),
+            (2, 1, 3)
+        );
+
+        let csv = record.to_csv();
+        assert!(csv.starts_with("\"a \"\"quoted\"\", title\",7,2,2,"));
+        assert!(csv.ends_with(",2,1,3,0"));
+        let json: ScenarioRecord = serde_json::from_str(&record.to_json()).unwrap();
+        assert_eq!(json, record);
+    }
+}
@@ -0,0 +1,354 @@
+use std::{fs, path::Path, time::Duration};
+
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use rand_distr::{Distribution, Uniform};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    application::{Application, Operation},
+    banking::{action::Action, banking::Banking},
+    error::ScenarioError,
+    network::{
+        network_info::DEFAULT_REPORT_FOLDER, DelayDistribution, Nemesis, NemesisEvent, NetworkInfo,
+        Topology,
+    },
+    peer::{
+        consensus::ConsensusMode,
+        handler::{FaultyClientStrategy, FaultyReplicaStrategy},
+    },
+    simulation::{
+        simulated::{ApplicationKind, Simulated},
+        simulation::{Scenario, Simulation},
+    },
+};
+
+/// Describes a ready-to-run `Simulation` in JSON: the parameters of the network,
+/// the workload of the clients and the faults to inject. For example:
+/// ```json
+/// {
+///     "title": "One replica partitioned for 2s",
+///     "seed": 42,
+///     "network": { "clients": 2, "replicas": 6, "faulty_replicas": 1, "transmission_delay": 10 },
+///     "workload": { "Generated": { "commands": 20, "conflict_probability": 0.1 } },
+///     "faults": [
+///         { "at": 0.0, "event": { "Partition": [[2], [0, 1, 3, 4, 5, 6, 7, 8]] } },
+///         { "at": 2.0, "event": "Heal" }
+///     ]
+/// }
+/// ```
+/// The network replicates the application named by `"application"`, e.g. `"Kv"` for a
+/// `KvStore`, and `Banking` by default. The actions of the workload are those of the application.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, bound = "")]
+pub struct ScenarioFile<A: Application = Banking> {
+    pub title: String,
+    /// Seed of every random decision of the simulation, drawn at random if missing
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub application: ApplicationKind,
+    pub network: NetworkParameters,
+    pub workload: Workload<A::Action>,
+    #[serde(default)]
+    pub faults: Vec<ScheduledFault>,
+}
+
+/// Parameters of the `NetworkInfo` of the simulation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkParameters {
+    pub clients: usize,
+    pub replicas: usize,
+    #[serde(default)]
+    pub faulty_clients: usize,
+    #[serde(default)]
+    pub faulty_replicas: usize,
+    /// in ms
+    pub transmission_delay: u64,
+    /// in s
+    #[serde(default = "NetworkParameters::default_consensus_duration")]
+    pub consensus_duration: f64,
+    /// Number of acknowledgements of the fast path, every correct replica (n - f) by default.
+    /// The clients of a command not acknowledged in time fall back to the `f + 1` of the CHK path
+    #[serde(default)]
+    pub n_ack: Option<usize>,
+    #[serde(default)]
+    pub report_folder: Option<String>,
+    #[serde(default)]
+    pub write_logs: bool,
+    #[serde(default)]
+    pub consensus: Option<ConsensusMode>,
+    #[serde(default)]
+    pub delay_distribution: Option<DelayDistribution>,
+    #[serde(default)]
+    pub topology: Option<Topology>,
+    #[serde(default)]
+    pub faulty_replica_strategy: Option<FaultyReplicaStrategy>,
+    #[serde(default)]
+    pub faulty_client_strategy: Option<FaultyClientStrategy>,
+    /// Runs the scenario with the tokio clock paused, see the `virtual-time` feature
+    #[serde(default)]
+    pub virtual_time: bool,
+    /// Records the events of the run in `<report_folder>/trace_<seed>.jsonl`
+    #[serde(default)]
+    pub trace: bool,
+}
+
+impl NetworkParameters {
+    fn default_consensus_duration() -> f64 {
+        1.0
+    }
+}
+
+/// Commands executed by the clients
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub enum Workload<O: Operation = Action> {
+    /// Explicit list of `(client, action)`, executed in order by each client
+    Items(Scenario<O>),
+    /// Commands generated as in `Scenarios`: with the given probability, a client issues
+    /// two conflicting actions, otherwise one, see `Simulated::generate`
+    Generated {
+        commands: usize,
+        conflict_probability: f64,
+    },
+}
+
+/// Fault injected at the given time of the simulation, in s
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduledFault {
+    pub at: f64,
+    pub event: NemesisEvent,
+}
+
+impl<A: Simulated> ScenarioFile<A> {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ScenarioError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ScenarioError> {
+        let scenario: ScenarioFile<A> = serde_json::from_str(json)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<(), ScenarioError> {
+        if self.application != A::KIND {
+            return Err(ScenarioError::Invalid(format!(
+                "the scenario runs the {} application",
+                self.application
+            )));
+        }
+        let network = &self.network;
+        if network.replicas == 0 || 5 * network.faulty_replicas >= network.replicas {
+            return Err(ScenarioError::Invalid(String::from(
+                "the network does not satisfy the resilience condition",
+            )));
+        }
+        if let Some(topology) = &network.topology {
+            let size = network.clients
+                + network.replicas
+                + network.faulty_clients
+                + network.faulty_replicas;
+            topology
+                .validate(size)
+                .map_err(|error| ScenarioError::Invalid(error.to_string()))?;
+        }
+        if network.consensus_duration < 0.0 {
+            return Err(ScenarioError::Invalid(String::from(
+                "the consensus duration is negative",
+            )));
+        }
+        match &self.workload {
+            Workload::Items(items) => {
+                if let Some((client, _)) =
+                    items.iter().find(|(client, _)| *client >= network.clients)
+                {
+                    return Err(ScenarioError::Invalid(format!(
+                        "#{} is not a client",
+                        client
+                    )));
+                }
+            }
+            Workload::Generated {
+                conflict_probability,
+                ..
+            } => {
+                if !(0.0..=1.0).contains(conflict_probability) {
+                    return Err(ScenarioError::Invalid(String::from(
+                        "the conflict probability is not in [0, 1]",
+                    )));
+                }
+                if network.clients == 0 {
+                    return Err(ScenarioError::Invalid(String::from(
+                        "commands are generated without any client",
+                    )));
+                }
+            }
+        }
+        if let Some(fault) = self.faults.iter().find(|fault| !(fault.at >= 0.0)) {
+            return Err(ScenarioError::Invalid(format!(
+                "the fault {} is scheduled at {}s",
+                fault.event, fault.at
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn network_info(&self, seed: u64) -> NetworkInfo {
+        let parameters = &self.network;
+        let mut network_info = NetworkInfo::new(
+            parameters.clients,
+            parameters.replicas,
+            parameters.faulty_clients,
+            parameters.faulty_replicas,
+            parameters.transmission_delay,
+            parameters.consensus_duration,
+            parameters
+                .report_folder
+                .clone()
+                .unwrap_or_else(|| String::from(DEFAULT_REPORT_FOLDER)),
+            parameters.n_ack.unwrap_or(parameters.replicas),
+            parameters.write_logs,
+        );
+        network_info.set_seed(seed);
+        network_info.set_trace(parameters.trace);
+        if let Some(mode) = parameters.consensus {
+            network_info.set_consensus_mode(mode);
+        }
+        if let Some(distribution) = &parameters.delay_distribution {
+            network_info.set_transmission_delay_distribution(distribution.clone());
+        }
+        if let Some(topology) = &parameters.topology {
+            network_info
+                .set_topology(topology.clone())
+                .expect("The topology of a valid scenario fits its network");
+        }
+        if let Some(strategy) = &parameters.faulty_replica_strategy {
+            network_info.set_faulty_replicas_strategy(strategy.clone());
+        }
+        if let Some(strategy) = &parameters.faulty_client_strategy {
+            network_info.set_faulty_clients_strategy(strategy.clone());
+        }
+        network_info
+    }
+
+    pub fn nemesis(&self) -> Nemesis {
+        self.faults.iter().fold(Nemesis::new(), |nemesis, fault| {
+            nemesis.at(Duration::from_secs_f64(fault.at), fault.event.clone())
+        })
+    }
+
+    /// Returns the commands of the clients, generated from `seed` if needed
+    pub fn workload(&self, seed: u64) -> Scenario<A::Action> {
+        match &self.workload {
+            Workload::Items(items) => items.clone(),
+            Workload::Generated {
+                commands,
+                conflict_probability,
+            } => {
+                let rng = &mut StdRng::seed_from_u64(seed);
+                let proba = Uniform::new(0.0, 1.0);
+                let client_distr = Uniform::new(0, self.network.clients);
+                let mut items = Vec::new();
+                for _ in 0..*commands {
+                    let client = client_distr.sample(rng);
+                    let conflict = proba.sample(rng) < *conflict_probability;
+                    for action in A::generate(rng, client, conflict) {
+                        items.push((client, action));
+                    }
+                }
+                items
+            }
+        }
+    }
+
+    /// Sets up the network and the clients, and queues the workload
+    pub async fn simulation(&self) -> Simulation<A> {
+        let seed = self.seed.unwrap_or_else(|| thread_rng().gen());
+        let mut simulation =
+            Simulation::<A>::from_network_info(self.title.clone(), self.network_info(seed)).await;
+        for (client, action) in self.workload(seed) {
+            simulation.add_scenario_item(client, action);
+        }
+        simulation.set_nemesis(self.nemesis());
+        simulation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv::store::KvStore;
+
+    use super::*;
+
+    const EXAMPLE: &str = "scenarios/partitioned_replica.json";
+    const KV_EXAMPLE: &str = "scenarios/kv_shared_key.json";
+
+    #[test]
+    fn parses_the_example() {
+        let scenario: ScenarioFile = ScenarioFile::from_file(EXAMPLE).unwrap();
+        let network_info = scenario.network_info(scenario.seed.unwrap());
+        assert_eq!(network_info.nbr_replicas(), 6);
+        assert_eq!(network_info.consensus_mode(), ConsensusMode::Pbft);
+        assert!(scenario.network.virtual_time);
+        assert_eq!(scenario.nemesis().timeline().len(), 2);
+        assert_eq!(scenario.workload(1), scenario.workload(1));
+    }
+
+    #[test]
+    fn rejects_invalid_scenarios() {
+        let invalid = [
+            // Unknown field
+            r#"{ "title": "", "network": { "clients": 1, "replicas": 6, "transmission_delay": 1,
+                "replica": 2 }, "workload": { "Items": [] } }"#,
+            // Not resilient
+            r#"{ "title": "", "network": { "clients": 1, "replicas": 5, "faulty_replicas": 1,
+                "transmission_delay": 1 }, "workload": { "Items": [] } }"#,
+            // Unknown client
+            r#"{ "title": "", "network": { "clients": 1, "replicas": 6, "transmission_delay": 1 },
+                "workload": { "Items": [[1, "Register"]] } }"#,
+            // Topology of another network
+            r#"{ "title": "", "network": { "clients": 1, "replicas": 6, "transmission_delay": 1,
+                "topology": { "Matrix": [[0, 1], [1, 0]] } }, "workload": { "Items": [] } }"#,
+            // Fault in the past
+            r#"{ "title": "", "network": { "clients": 1, "replicas": 6, "transmission_delay": 1 },
+                "workload": { "Items": [] }, "faults": [{ "at": -1.0, "event": "Heal" }] }"#,
+            // Scenario of another application
+            r#"{ "title": "", "application": "Kv", "network": { "clients": 1, "replicas": 6,
+                "transmission_delay": 1 }, "workload": { "Items": [] } }"#,
+        ];
+        for json in invalid.iter() {
+            assert!(
+                ScenarioFile::<Banking>::from_json(json).is_err(),
+                "{} is valid",
+                json
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn runs_a_loaded_simulation() {
+        let mut simulation: Simulation = Simulation::from_file(EXAMPLE).await.unwrap();
+        simulation.simulate().await;
+        assert_eq!(simulation.applied_events().len(), 2);
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn runs_a_kv_scenario() {
+        let json = fs::read_to_string(KV_EXAMPLE).unwrap();
+        assert_eq!(
+            ApplicationKind::of_scenario(&json).unwrap(),
+            ApplicationKind::Kv
+        );
+        assert!(ScenarioFile::<Banking>::from_json(&json).is_err());
+
+        let scenario = ScenarioFile::<KvStore>::from_json(&json).unwrap();
+        let commands = scenario.workload(0).len();
+        let mut simulation = scenario.simulation().await;
+        let run = simulation.run().await;
+        assert_eq!(run.completions.len(), commands);
+        assert_eq!(run.divergence, None);
+    }
+}
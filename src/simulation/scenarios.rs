@@ -0,0 +1,803 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use rand_distr::{Distribution, Uniform};
+
+use crate::{
+    application::Operation,
+    banking::banking::Banking,
+    kv::store::KvStore,
+    network::{LatencyReport, Nemesis, Topology},
+    simulation::{
+        record::{Run, ScenarioRecord},
+        scenario_file::{ScenarioFile, Workload},
+        simulated::{ApplicationKind, Simulated},
+        simulation::Simulation,
+    },
+};
+
+/// Runs scenarios and reports them as text, CSV and JSON Lines.
+/// The CSV and JSON Lines reports are written next to the text report.
+pub struct Scenarios {
+    file: File,
+    csv: File,
+    jsonl: File,
+    records: Vec<ScenarioRecord>,
+    seed: u64,
+    rng: StdRng,
+    topology: Topology,
+    nemesis: Nemesis,
+}
+
+pub const GENERATION_LOOP: usize = 100;
+pub const CLIENTS: usize = 2;
+pub const REPLICAS: usize = 6;
+pub const FAULTY: usize = 1;
+pub const DEFAULT_PRECISION: usize = 1;
+pub const DEFAULT_REPORT_FOLDER: &str = "reports/scenarios/logs";
+pub const DEFAULT_TRANSMISSION_DELAY_MS: u64 = 300;
+pub const DEFAULT_CONSENSUS_DURATION_S: f64 = 5.0;
+
+/// Parameters of the generated scenarios
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScenarioParameters {
+    pub report_folder: String,
+    pub precision: usize,
+    /// in ms
+    pub transmission_delay: u64,
+    /// in s
+    pub consensus_duration: f64,
+    pub probability_of_conflict: f64,
+    pub clients: usize,
+    pub replicas: usize,
+    pub faulty_replicas: usize,
+    pub application: ApplicationKind,
+}
+
+impl Default for ScenarioParameters {
+    fn default() -> Self {
+        ScenarioParameters {
+            report_folder: String::from(DEFAULT_REPORT_FOLDER),
+            precision: DEFAULT_PRECISION,
+            transmission_delay: DEFAULT_TRANSMISSION_DELAY_MS,
+            consensus_duration: DEFAULT_CONSENSUS_DURATION_S,
+            probability_of_conflict: 0.0,
+            clients: CLIENTS,
+            replicas: REPLICAS,
+            faulty_replicas: FAULTY,
+            application: ApplicationKind::Banking,
+        }
+    }
+}
+
+/// Parameter varied by `Scenarios::sweep`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepParameter {
+    TransmissionDelay,
+    ConsensusDuration,
+    ConflictProbability,
+    Replicas,
+}
+
+impl SweepParameter {
+    /// Returns `parameters` where the swept parameter is set to `value`
+    pub fn apply(&self, parameters: &ScenarioParameters, value: f64) -> ScenarioParameters {
+        let mut parameters = parameters.clone();
+        match self {
+            SweepParameter::TransmissionDelay => parameters.transmission_delay = value as u64,
+            SweepParameter::ConsensusDuration => parameters.consensus_duration = value,
+            SweepParameter::ConflictProbability => parameters.probability_of_conflict = value,
+            SweepParameter::Replicas => parameters.replicas = value as usize,
+        }
+        parameters
+    }
+}
+
+impl FromStr for SweepParameter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transmission-delay" => Ok(SweepParameter::TransmissionDelay),
+            "consensus-duration" => Ok(SweepParameter::ConsensusDuration),
+            "conflict-probability" => Ok(SweepParameter::ConflictProbability),
+            "replicas" => Ok(SweepParameter::Replicas),
+            _ => Err(format!("Unknown parameter {:?}", s)),
+        }
+    }
+}
+
+impl Display for SweepParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepParameter::TransmissionDelay => write!(f, "transmission-delay"),
+            SweepParameter::ConsensusDuration => write!(f, "consensus-duration"),
+            SweepParameter::ConflictProbability => write!(f, "conflict-probability"),
+            SweepParameter::Replicas => write!(f, "replicas"),
+        }
+    }
+}
+
+/// Defines some testing scenarios
+impl Scenarios {
+    pub fn new(path: &str) -> Self {
+        Self::with_seed(path, thread_rng().gen())
+    }
+
+    /// The generated commands and the seeds of the simulations are all derived from `seed`,
+    /// which is written at the top of the report to replay the scenarios.
+    pub fn with_seed(path: &str, seed: u64) -> Self {
+        let path = format!("{}", path);
+        let path = Path::new(&path);
+        let display = path.display();
+
+        // Open a file in write-only mode, returns `io::Result<File>`
+        let file = match File::create(&path) {
+            Err(why) => panic!("Couldn't create {}: {}", display, why),
+            Ok(file) => file,
+        };
+        let mut csv = Self::create(path.with_extension("csv"));
+        writeln!(csv, "{}", ScenarioRecord::csv_header()).expect("Error while writing the scenario");
+        let jsonl = Self::create(path.with_extension("jsonl"));
+
+        let mut scenarios = Scenarios {
+            file,
+            csv,
+            jsonl,
+            records: Vec::new(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            topology: Topology::Uniform,
+            nemesis: Nemesis::new(),
+        };
+        scenarios.write(format!("SEED: {}", seed));
+        scenarios
+    }
+
+    fn create(path: PathBuf) -> File {
+        File::create(&path)
+            .unwrap_or_else(|why| panic!("Couldn't create {}: {}", path.display(), why))
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the records of the scenarios run so far
+    pub fn records(&self) -> &Vec<ScenarioRecord> {
+        &self.records
+    }
+
+    /// Sets the topology of the networks of the next scenarios
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    /// Sets the faults injected during the next scenarios
+    pub fn set_nemesis(&mut self, nemesis: Nemesis) {
+        self.nemesis = nemesis;
+    }
+
+    fn write(&mut self, line: String) {
+        write!(self.file, "{} \n", line).expect("Error while writing the scenario");
+    }
+
+    /// Writes the record to every report, with the faults applied during its runs
+    fn write_record<O: Operation>(&mut self, record: ScenarioRecord, runs: &[Run<O>]) {
+        for line in record.report() {
+            self.write(line);
+        }
+        let latencies = LatencyReport::new(runs.iter().flat_map(|run| run.completions.iter()));
+        self.write(String::from("\t > LATENCY PER ACTION AND PHASE:"));
+        for line in latencies.to_string().lines() {
+            self.write(format!("\t\t {}", line));
+        }
+        for (run, Run { applied_events, metrics, divergence, counterexample, .. }) in runs.iter().enumerate() {
+            for (time, event) in applied_events {
+                self.write(format!("\t > NEMESIS (run {}) at {:#?}: {}", run, time, event));
+            }
+            if let Some(divergence) = divergence {
+                self.write(format!("\t > SAFETY VIOLATION (run {}): {}", run, divergence));
+            }
+            if let Some(counterexample) = counterexample {
+                self.write(format!("\t > NOT LINEARIZABLE (run {}): {}", run, counterexample));
+            }
+            self.write(format!("\t > METRICS (run {}):", run));
+            for line in metrics.to_string().lines() {
+                self.write(format!("\t\t {}", line));
+            }
+        }
+        writeln!(self.csv, "{}", record.to_csv()).expect("Error while writing the scenario");
+        writeln!(self.jsonl, "{}", record.to_json()).expect("Error while writing the scenario");
+        self.records.push(record);
+    }
+
+    /// Runs `precision` simulations of the application, each executing `GENERATION_LOOP`
+    /// generated commands, see `Simulated::generate`
+    async fn parametrizable_scenario<A: Simulated>(
+        &mut self,
+        title: String,
+        parameters: ScenarioParameters,
+    ) -> Duration {
+        let probability_of_conflict = parameters.probability_of_conflict;
+        assert!(0.0 <= probability_of_conflict);
+        assert!(probability_of_conflict <= 1.0);
+        let proba = Uniform::new(0.0, 1.0);
+
+        let client_distr = Uniform::new(0.0, parameters.clients as f64);
+        let mut simulations: Vec<Simulation<A>> = Vec::new();
+        for _ in 0..parameters.precision {
+            let mut simulation = Simulation::<A>::with_seed(
+                title.clone(),
+                parameters.report_folder.clone(),
+                parameters.clients,
+                parameters.replicas,
+                parameters.faulty_replicas,
+                parameters.transmission_delay,
+                parameters.consensus_duration,
+                self.rng.gen(),
+                self.topology.clone(),
+            )
+            .await;
+            simulation.set_nemesis(self.nemesis.clone());
+            let rng = &mut self.rng;
+            for _ in 0..GENERATION_LOOP {
+                let p = proba.sample(rng);
+                let client = client_distr.sample(rng) as usize;
+                let conflict = probability_of_conflict > 0.0 && p < probability_of_conflict;
+                for action in A::generate(rng, client, conflict) {
+                    simulation.add_scenario_item(client, action);
+                }
+            }
+
+            simulations.push(simulation);
+        }
+        let parameters = ScenarioParameters {
+            application: A::KIND,
+            ..parameters
+        };
+        self.simulate_all(title, &parameters, &simulations).await
+    }
+
+    /// Runs the generated scenario of the application of `parameters`
+    async fn generated_scenario(
+        &mut self,
+        title: String,
+        parameters: ScenarioParameters,
+    ) -> Duration {
+        match parameters.application {
+            ApplicationKind::Banking => {
+                self.parametrizable_scenario::<Banking>(title, parameters)
+                    .await
+            }
+            ApplicationKind::Kv => {
+                self.parametrizable_scenario::<KvStore>(title, parameters)
+                    .await
+            }
+        }
+    }
+
+    async fn parametrizable_scenario_with_pb(
+        &mut self,
+        title: String,
+        report_folder: String,
+        precision: usize,
+        transmission_delay: u64,
+        consensus_duration: f64,
+        probability_of_conflict: u64,
+        nbr_clients: usize,
+        nbr_replicas: usize,
+        nbr_faulty_replicas: usize,
+    ) -> Duration {
+
+        let mut simulations: Vec<Simulation> = Vec::new();
+        for _ in 0..precision {
+            let mut simulation: Simulation = Simulation::with_seed(
+                title.clone(),
+                report_folder.clone(),
+                nbr_clients,
+                nbr_replicas,
+                nbr_faulty_replicas,
+                transmission_delay,
+                consensus_duration,
+                self.rng.gen(),
+                self.topology.clone(),
+            )
+            .await;
+            simulation.set_nemesis(self.nemesis.clone());
+            let proba = Uniform::new(0.0, 1.0);
+            let rng = &mut self.rng;
+            let indices: Vec<usize> = Vec::new();
+
+            let conflict = GENERATION_LOOP - 2 * probability_of_conflict as usize;
+            let mut i = 0;
+            while i < GENERATION_LOOP {
+                if i >= conflict {
+                    // Add a conflicting command
+                    simulation.get(i % nbr_clients);
+                    simulation.withdraw(i % nbr_clients, 10);
+                    i += 1;
+                    //   simulation.withdraw(id, amount)
+                } else {
+                    let action = proba.sample(rng);
+                    if action < 0.2 {
+                        simulation.get(i % nbr_clients);
+                    } else {
+                        simulation.deposit(i % nbr_clients, 10);
+                    }
+                }
+
+                i += 1;
+            }
+
+            simulations.push(simulation);
+        }
+        let parameters = ScenarioParameters {
+            report_folder,
+            precision,
+            transmission_delay,
+            consensus_duration,
+            probability_of_conflict: probability_of_conflict as f64 / 100.0,
+            clients: nbr_clients,
+            replicas: nbr_replicas,
+            faulty_replicas: nbr_faulty_replicas,
+            application: ApplicationKind::Banking,
+        };
+        self.simulate_all(title, &parameters, &simulations).await
+    }
+
+    /// Runs every simulation, records the scenario and returns the average time taken
+    async fn simulate_all<A: Simulated>(
+        &mut self,
+        title: String,
+        parameters: &ScenarioParameters,
+        simulations: &[Simulation<A>],
+    ) -> Duration {
+        let mut runs = Vec::new();
+        for simulation in simulations {
+            let mut simulation = simulation.clone().await;
+            runs.push(simulation.run().await);
+        }
+        let record = ScenarioRecord::new(
+            title,
+            self.seed,
+            parameters,
+            GENERATION_LOOP,
+            &self.topology,
+            &runs,
+        );
+        let time_taken = Duration::from_secs_f64(record.duration);
+        self.write_record(record, &runs);
+
+        time_taken
+    }
+
+    fn write_sep(&mut self) {
+        self.write(String::from("+---------------------------------------------------+"));
+    }
+    pub async fn with_inscreasing_transmission_delay(
+        &mut self,
+        low: u64,
+        high: u64,
+        step: u64,
+        consensus_duration: f64,
+        probability_of_conflict: f64,
+    ) {
+        let parameters = ScenarioParameters {
+            report_folder: format!("{}/transmission_delay", DEFAULT_REPORT_FOLDER),
+            consensus_duration,
+            probability_of_conflict,
+            ..ScenarioParameters::default()
+        };
+        self.sweep(
+            SweepParameter::TransmissionDelay,
+            low as f64,
+            high as f64,
+            step as f64,
+            &parameters,
+        )
+        .await;
+    }
+
+    /// Runs a scenario for every value of `parameter` in `[low, high)`, increasing by `step`,
+    /// the other parameters being those of `parameters`. Returns the time taken by each scenario.
+    pub async fn sweep(
+        &mut self,
+        parameter: SweepParameter,
+        low: f64,
+        high: f64,
+        step: f64,
+        parameters: &ScenarioParameters,
+    ) -> Vec<(f64, Duration)> {
+        assert!(step > 0.0, "The step of the sweep must be positive");
+        self.write_sep();
+        self.write(format!(
+            "START FUNCTION: f({}) on [{}, {}] with a step of {}",
+            parameter, low, high, step
+        ));
+        self.write_sep();
+        let mut scenarios: Vec<(f64, Duration)> = Vec::new();
+
+        let mut value = low;
+        while value < high {
+            let point = parameter.apply(parameters, value);
+            let scenario = self
+                .generated_scenario(format!("SWEEP {}: {}", parameter, value), point)
+                .await;
+            scenarios.push((value, scenario));
+            value += step;
+        }
+        self.write_sep();
+        self.write(String::from("RESULTS: "));
+        for (t, d) in scenarios.iter() {
+            self.write(format!("\t f({}) = {:#?}", t, d));
+        }
+        self.write_sep();
+        scenarios
+    }
+
+    /// Runs the simulation described by a scenario file
+    pub async fn scenario_file<A: Simulated>(&mut self, scenario: &ScenarioFile<A>) -> Duration {
+        let mut simulation = scenario.simulation().await;
+        let network_info = simulation.network.network_info().clone();
+        let parameters = ScenarioParameters {
+            report_folder: network_info.report_folder().clone(),
+            precision: 1,
+            transmission_delay: network_info.transmission_delay(),
+            consensus_duration: network_info.consensus_duration(),
+            probability_of_conflict: match scenario.workload {
+                Workload::Generated {
+                    conflict_probability,
+                    ..
+                } => conflict_probability,
+                Workload::Items(_) => 0.0,
+            },
+            clients: network_info.nbr_clients(),
+            replicas: network_info.nbr_replicas(),
+            faulty_replicas: network_info.nbr_faulty_replicas(),
+            application: A::KIND,
+        };
+        let commands = simulation.scenario.len();
+        // Simulated directly rather than cloned, as only one network can use virtual time
+        let runs = vec![simulation.run().await];
+        let record = ScenarioRecord::new(
+            scenario.title.clone(),
+            network_info.seed(),
+            &parameters,
+            commands,
+            network_info.topology(),
+            &runs,
+        );
+        self.write_record(record, &runs);
+
+        runs[0].duration
+    }
+
+    fn consensus_duration(transmission_delay_ms: u64, slowdown_factor: f64) -> f64 {
+        let consensus_duration_ms = transmission_delay_ms as f64 * slowdown_factor;
+        consensus_duration_ms / 1000.0
+    }
+    /// Scenario: We seek for the relationship S between the transmission delay T and the consensus latency C such that the algorithm is worth it
+    /// In other word, we seek for the S such that C = S*T and for a given probability of conflict P, the time taken with and without conflict is the same.
+    /// We simulate it for P = 0.01, 0.05, 0.1, 0.2 and for P = 0.00 (no conflict)
+    pub async fn slowdown_factor(&mut self, low: f64, high: f64, step: f64, transmission_delay: u64){
+        let mut factor = low as f64;
+        let probabilities: [u64; 5] = [0, 1, 5, 10, 20]; // In %
+        self.write_sep();
+        self.write(format!("START FUNCTION: f(slowdown_factor) on [{}, {}]s with a step of {}ms ",
+            low, high, step
+        ));
+        self.write_sep();
+        let mut scenarios: HashMap<u64, Vec<(f64, Duration)>> = HashMap::new();
+        for p in probabilities {
+            scenarios.insert(p, Vec::new());
+        }
+        while factor < high {
+            for p in probabilities {
+                println!("FACTOR {}, CONSENSUS {}, PROBABILITY {}%", factor, Self::consensus_duration(transmission_delay, factor), p);
+                let scenario = self
+                .parametrizable_scenario_with_pb(
+                    format!("SLOWDOWN FACTOR (P = {}): {}", p, factor),
+                    format!("{}", DEFAULT_REPORT_FOLDER),
+                    DEFAULT_PRECISION,
+                    transmission_delay,
+                    Self::consensus_duration(transmission_delay, factor),
+                    p,
+                    CLIENTS,
+                    REPLICAS,
+                    FAULTY,
+                )
+                .await;
+            scenarios.get_mut(&p).unwrap().push((factor, scenario));
+            }
+            factor *= step;
+        }
+        self.write(String::from("+---------------------------------------------------+"));
+        self.write(format!("RESULTS: "));
+        for (p, vec) in scenarios {
+            for (t, d) in vec {
+                self.write(format!("\t \t f_{}({}) = {:#?}", p, t, d));
+            }
+            self.write(String::from(""));
+        }
+        self.write(String::from("+---------------------------------------------------+"));
+    }
+
+    pub async fn expected_latency(&mut self, p: f64) {
+        self.write_sep();
+        self.write(format!("EXPECTED LATENCY WITH TRANSMISSION DELAY {}ms AND CONSENSUS DURATION {}s, P = {}",
+            DEFAULT_TRANSMISSION_DELAY_MS, DEFAULT_CONSENSUS_DURATION_S, p
+        ));
+        self.write_sep();
+        let parameters = ScenarioParameters {
+            precision: 5,
+            probability_of_conflict: p,
+            ..ScenarioParameters::default()
+        };
+        let scenario = self
+            .parametrizable_scenario::<Banking>(
+                format!("EXPECTED LATENCY WITH P_CONFLICT = {}", p),
+                parameters,
+            )
+            .await;
+        self.write(String::from("+---------------------------------------------------+"));
+        self.write(format!("RESULTS: "));
+        self.write(format!("\t DURATION FOR 100 COMMANDS : {:#?}", scenario));
+        let record = self.records.last().expect("The scenario is recorded").clone();
+        self.write(format!("\t EXPECTED LATENCY: p50 {}ms, p90 {}ms, p99 {}ms, max {}ms", record.latency_p50, record.latency_p90, record.latency_p99, record.latency_max));
+        self.write(String::from("+---------------------------------------------------+"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::timeout;
+
+    use crate::{
+        kv::{
+            action::{KvAction, Reply},
+            store::KvStore,
+        },
+        network::network::Network,
+        peer::{
+            consensus::ConsensusMode,
+            handler::{FaultyClientStrategy, FaultyReplicaStrategy},
+            peer::PeerId,
+        },
+        talk::CommandResult,
+        tests::util::Utils,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test() {
+        test_bug().await;
+    }
+    async fn test_bug() {
+        let mut scenarios: Scenarios = Scenarios::new("resources/test_bug.txt");
+        let parameters = ScenarioParameters {
+            report_folder: String::from("resources/test"),
+            precision: 3,
+            transmission_delay: 10,
+            consensus_duration: 0.1,
+            probability_of_conflict: 0.2,
+            clients: 3,
+            replicas: 4,
+            faulty_replicas: 0,
+            application: ApplicationKind::Banking,
+        };
+        let t = scenarios
+            .parametrizable_scenario::<Banking>(String::from("test"), parameters)
+            .await;
+        println!("{:?}", t);
+    }
+
+    #[tokio::test]
+    async fn test2() {
+        let mut i = 0;
+        while i < 30 {
+            i += 1;
+            if let Err(_) = timeout(Duration::from_secs(100), test_bug()).await {
+                panic!();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test3() {
+        let mut i = 0;
+        while i < 30 {
+            i += 1;
+            if let Err(_) = timeout(Duration::from_secs(100), test_bug()).await {
+                panic!();
+            }
+        }
+    }
+
+    #[test]
+    fn poisson() {
+        let dist = Uniform::new(0.0, 1.0);
+        let rng = &mut thread_rng();
+        let mut i = 0;
+        let mut count10 = 0;
+        let mut count50 = 0;
+        let mut count1 = 0;
+        let mut count5 = 0;
+        while i < 100 {
+            i += 1;
+            let nbr = dist.sample(rng);
+            if nbr < 0.1 {
+                count10 += 1;
+            }
+            if nbr < 0.5 {
+                count50 += 1;
+            }
+            if nbr < 0.01 {
+                count1 += 1;
+            }
+            if nbr < 0.05 {
+                count5 += 1;
+            }
+        }
+        println!("1%: {}, 5%: {}, 10%: {}, 50%: {}", count1, count5, count10, count50);
+    }
+
+    #[tokio::test]
+    async fn test_td() {
+        let mut scenarios = Scenarios::new("reports/scenarios/transmission_delay");
+        scenarios.with_inscreasing_transmission_delay(10, 50, 10, 1.0, 0.1).await;
+    }
+
+    #[tokio::test]
+    async fn expected_latency() {
+        let mut scenarios = Scenarios::new("reports/scenarios/expected_latency2.txt");
+        scenarios.expected_latency(0.01).await;
+        scenarios.expected_latency(0.05).await;
+        scenarios.expected_latency(0.10).await;
+        scenarios.expected_latency(0.20).await;
+        scenarios.expected_latency(0.50).await;
+    }
+    
+    #[tokio::test]
+    async fn expected_latency3() {
+        let mut scenarios = Scenarios::new("reports/scenarios/expected_latency3.txt");
+        scenarios.expected_latency(0.1).await;
+    }
+
+    #[tokio::test]
+    async fn sweeps_generate_the_workload_of_the_application() {
+        let path = std::env::temp_dir().join("genericbft_kv_sweep.txt");
+        let mut scenarios = Scenarios::with_seed(path.to_str().unwrap(), 1);
+        let parameters = ScenarioParameters {
+            report_folder: String::from("resources/test"),
+            transmission_delay: 10,
+            consensus_duration: 0.1,
+            probability_of_conflict: 0.2,
+            application: ApplicationKind::Kv,
+            ..ScenarioParameters::default()
+        };
+        scenarios
+            .sweep(SweepParameter::Replicas, 6.0, 7.0, 1.0, &parameters)
+            .await;
+        let record = &scenarios.records()[0];
+        assert_eq!(record.application, "kv");
+        assert!(record.fast_path + record.slow_path >= GENERATION_LOOP);
+        assert_eq!(record.divergences, 0);
+    }
+
+    /// Each client writes and reads its own key, and races the others on a shared key
+    fn kv_workload(network: &mut Network<KvStore>, clients: PeerId) {
+        for i in 0..clients {
+            let key = format!("k{}", i);
+            for action in [
+                KvAction::Put(key.clone(), String::from("0")),
+                KvAction::CompareAndSwap {
+                    key: key.clone(),
+                    expected: Some(String::from("0")),
+                    new: String::from("1"),
+                },
+                KvAction::Put(String::from("shared"), i.to_string()),
+                KvAction::Get(String::from("shared")),
+                KvAction::Batch(vec![
+                    KvAction::Get(key.clone()),
+                    KvAction::Delete(key.clone()),
+                ]),
+                KvAction::Get(key),
+            ] {
+                network.execute_action(i, action);
+            }
+        }
+    }
+
+    /// Checks the results of the operations of the correct clients on their own key
+    fn check_kv_results(network: &Network<KvStore>, clients: PeerId) {
+        let one = || Some(Reply::Value(String::from("1")));
+        for i in 0..clients {
+            let results: Vec<CommandResult<KvAction>> = network
+                .history()
+                .operations()
+                .iter()
+                .filter(|operation| *operation.command.issuer() == i)
+                .map(|operation| operation.response.clone().expect("Pending operation").1)
+                .collect();
+            assert_eq!(results.len(), 6);
+            assert_eq!(results[0], CommandResult::Success(None));
+            assert_eq!(results[1], CommandResult::Success(None));
+            assert!(matches!(results[2], CommandResult::Success(_)));
+            assert!(matches!(
+                results[3],
+                CommandResult::Success(Some(Reply::Value(_)))
+            ));
+            assert_eq!(
+                results[4],
+                CommandResult::Success(Some(Reply::Batch(vec![one(), one()])))
+            );
+            assert_eq!(results[5], CommandResult::Success(None));
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn kv_end_to_end_test_with_every_consensus() {
+        for mode in [
+            ConsensusMode::Oracle,
+            ConsensusMode::Simulated,
+            ConsensusMode::Pbft,
+        ] {
+            let mut network_info = Utils::network_info(3, 0, 0.1);
+            network_info.set_consensus_mode(mode);
+            let mut network: Network<KvStore> = Network::setup(network_info).await;
+            kv_workload(&mut network, 3);
+            Utils::execute_and_shutdown(&mut network, Duration::from_secs(600), mode).await;
+            check_kv_results(&network, 3);
+            assert_eq!(network.check_safety(), Ok(()), "{:?}", mode);
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn kv_end_to_end_test_with_byzantine_replicas() {
+        for strategy in [
+            FaultyReplicaStrategy::EquivocateAcknowledgements,
+            FaultyReplicaStrategy::TruncateBroadcasts,
+            FaultyReplicaStrategy::ForgeCommands,
+            FaultyReplicaStrategy::WrongResults,
+            FaultyReplicaStrategy::GarbageProposals,
+            FaultyReplicaStrategy::Delay(100),
+        ] {
+            let mut network_info = Utils::network_info(2, 0, 0.1);
+            network_info.set_faulty_replicas_strategy(strategy.clone());
+            let mut network: Network<KvStore> = Network::setup(network_info).await;
+            kv_workload(&mut network, 2);
+            Utils::execute_and_shutdown(&mut network, Duration::from_secs(600), &strategy).await;
+            check_kv_results(&network, 2);
+            assert_eq!(network.check_safety(), Ok(()), "{:?}", strategy);
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn kv_end_to_end_test_with_adversarial_clients() {
+        for strategy in [
+            FaultyClientStrategy::Flood(5),
+            FaultyClientStrategy::DuplicateIds,
+            FaultyClientStrategy::Replay,
+        ] {
+            let mut network_info = Utils::network_info(2, 1, 0.1);
+            network_info.set_faulty_clients_strategy(strategy.clone());
+            let mut network: Network<KvStore> = Network::setup(network_info).await;
+            // The faulty client races the correct ones on the shared key
+            kv_workload(&mut network, 2);
+            network.execute_action(2, KvAction::Put(String::from("shared"), String::from("2")));
+            Utils::execute_and_shutdown(&mut network, Duration::from_secs(600), &strategy).await;
+            check_kv_results(&network, 2);
+            assert_eq!(network.check_safety(), Ok(()), "{:?}", strategy);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::ScenarioError,
+    network::{network::Network, Counterexample},
+    peer::{handler::Adversarial, peer::PeerId},
+};
+
+/// Application replicated by the network of a simulation, as named by the scenario files
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApplicationKind {
+    Banking,
+    Kv,
+}
+
+impl ApplicationKind {
+    /// Reads the application of a scenario file, before parsing its workload
+    pub fn of_scenario(json: &str) -> Result<Self, ScenarioError> {
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(default)]
+            application: ApplicationKind,
+        }
+
+        let header: Header = serde_json::from_str(json)?;
+        Ok(header.application)
+    }
+}
+
+impl Default for ApplicationKind {
+    fn default() -> Self {
+        ApplicationKind::Banking
+    }
+}
+
+impl FromStr for ApplicationKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "banking" => Ok(ApplicationKind::Banking),
+            "kv" => Ok(ApplicationKind::Kv),
+            _ => Err(format!("Unknown application {:?}", s)),
+        }
+    }
+}
+
+impl Display for ApplicationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplicationKind::Banking => write!(f, "banking"),
+            ApplicationKind::Kv => write!(f, "kv"),
+        }
+    }
+}
+
+/// Application that a `Simulation` can replicate: its clients are set up before the scenario,
+/// and `Scenarios` and the scenario files generate its workloads
+pub trait Simulated: Adversarial {
+    const KIND: ApplicationKind;
+
+    /// Actions that the client executes before the scenario, e.g. to open its account
+    fn setup(_client: PeerId) -> Vec<Self::Action> {
+        Vec::new()
+    }
+
+    /// Next actions of the client in a generated workload: two actions that conflict if
+    /// `conflict`, otherwise one action that commutes with those of the other clients
+    fn generate<R: Rng>(rng: &mut R, client: PeerId, conflict: bool) -> Vec<Self::Action>;
+
+    /// Checks the history of the correct clients against a sequential model of the
+    /// application. Histories are not checked by default.
+    fn check_history(_network: &Network<Self>) -> Result<(), Counterexample> {
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+use std::{path::Path, time::Duration};
+
+use rand::{thread_rng, Rng};
+use tokio::time::{sleep, Instant};
+
+use crate::{
+    banking::{
+        action::Action,
+        banking::{Banking, Money},
+    },
+    error::ScenarioError,
+    network::{
+        network::Network, Counterexample, Divergence, Nemesis, NemesisEvent, NetworkInfo, Topology,
+    },
+    peer::peer::PeerId,
+    simulation::{record::Run, scenario_file::ScenarioFile, simulated::Simulated},
+    talk::Command,
+};
+
+pub type ScenarioItem<O = Action> = (PeerId, O);
+pub type Scenario<O = Action> = Vec<ScenarioItem<O>>;
+const WRITE_LOGS: bool = false;
+pub struct Simulation<A: Simulated = Banking> {
+    pub title: String,
+    pub network: Network<A>,
+    pub scenario: Scenario<A::Action>,
+    pub nemesis: Nemesis,
+    /// Set by `simulate` if the correct replicas disagree at the end of the run
+    pub divergence: Option<Divergence>,
+    /// Set by `simulate` if the history of a correct client is not linearizable
+    pub counterexample: Option<Counterexample>,
+}
+
+impl<A: Simulated> Simulation<A> {
+    pub async fn new(
+        title: String,
+        report_folder: String,
+        nbr_clients: usize,
+        nbr_replicas: usize,
+        nbr_faulty_replicas: usize,
+        transmission_delay: u64,
+        consensus_duration: f64,
+    ) -> Self {
+        Self::with_seed(
+            title,
+            report_folder,
+            nbr_clients,
+            nbr_replicas,
+            nbr_faulty_replicas,
+            transmission_delay,
+            consensus_duration,
+            thread_rng().gen(),
+            Topology::Uniform,
+        )
+        .await
+    }
+
+    /// Creates a simulation of the given topology whose random decisions are all derived from `seed`
+    pub async fn with_seed(
+        title: String,
+        report_folder: String,
+        nbr_clients: usize,
+        nbr_replicas: usize,
+        nbr_faulty_replicas: usize,
+        transmission_delay: u64,
+        consensus_duration: f64,
+        seed: u64,
+        topology: Topology,
+    ) -> Self {
+        let mut network_info = NetworkInfo::default_parameters(
+            nbr_clients,
+            nbr_replicas,
+            0,
+            nbr_faulty_replicas,
+            transmission_delay,
+            consensus_duration,
+            report_folder,
+        );
+        network_info.set_write_logs(WRITE_LOGS);
+        network_info.set_seed(seed);
+        network_info
+            .set_topology(topology)
+            .expect("The topology does not fit the network");
+
+        Self::from_network_info(title, network_info).await
+    }
+
+    /// Sets up the network and every client, see `Simulated::setup`
+    pub async fn from_network_info(title: String, network_info: NetworkInfo) -> Self {
+        let mut simulation = Self {
+            title,
+            network: Network::setup(network_info).await,
+            scenario: Vec::new(),
+            nemesis: Nemesis::new(),
+            divergence: None,
+            counterexample: None,
+        };
+
+        simulation.setup_clients().await;
+
+        simulation
+    }
+
+    pub fn add_scenario_item(&mut self, client: PeerId, action: A::Action) {
+        self.scenario.push((client, action.clone()));
+        let id = self.network.network_info().generate_command_id();
+        self.network
+            .execute(client, Command::with_id(id, client, action));
+    }
+
+    /// Executes the setup actions of every client, and leaves them time to complete
+    pub async fn setup_clients(&mut self) {
+        let mut setup = false;
+        for client in 0..self.network.network_info().nbr_clients() {
+            for action in A::setup(client) {
+                self.network.execute_action(client, action);
+                self.network.execute_next(client).await;
+                setup = true;
+            }
+        }
+
+        if setup {
+            sleep(Duration::from_secs_f64(
+                self.network.network_info().consensus_duration() * 3.0,
+            ))
+            .await;
+        }
+    }
+
+    /* Fault injection */
+
+    pub fn drop_messages(&mut self, probability: f64) {
+        self.network
+            .network_info()
+            .faults()
+            .set_drop_probability(probability);
+    }
+
+    pub fn duplicate_messages(&mut self, probability: f64) {
+        self.network
+            .network_info()
+            .faults()
+            .set_duplicate_probability(probability);
+    }
+
+    /// Partitions the groups of peers for `window` if given, or until `heal` is called
+    pub fn partition(&mut self, groups: Vec<Vec<PeerId>>, window: Option<Duration>) {
+        self.network
+            .network_info()
+            .faults()
+            .partition(groups, window);
+    }
+
+    pub fn heal(&mut self) {
+        self.network.network_info().faults().heal();
+    }
+
+    /// Schedules a fault at the given time of the simulation
+    pub fn at(&mut self, time: Duration, event: NemesisEvent) {
+        self.nemesis = std::mem::take(&mut self.nemesis).at(time, event);
+    }
+
+    pub fn set_nemesis(&mut self, nemesis: Nemesis) {
+        self.nemesis = nemesis;
+    }
+
+    /// Returns the faults applied during the simulation, with the time they were applied at
+    pub fn applied_events(&self) -> Vec<(Duration, NemesisEvent)> {
+        self.network.applied_events()
+    }
+
+    /// Loads a simulation from a scenario file, see `ScenarioFile`
+    pub async fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ScenarioError> {
+        Ok(ScenarioFile::<A>::from_file(path)?.simulation().await)
+    }
+
+    pub async fn simulate(&mut self) -> Duration {
+        let time = Instant::now();
+        self.network.set_nemesis(self.nemesis.clone());
+        self.network.execute_all().await;
+        let _ = self.network.shutdown().await;
+        let time = time.elapsed();
+        self.divergence = self.network.check_safety().err();
+        if let Some(divergence) = &self.divergence {
+            println!("{}: safety violation: {}", self.title, divergence);
+        }
+        self.counterexample = A::check_history(&self.network).err();
+        if let Some(counterexample) = &self.counterexample {
+            println!("{}: {}", self.title, counterexample);
+        }
+        time
+    }
+
+    /// Simulates the scenario and returns what happened during the execution
+    pub async fn run(&mut self) -> Run<A::Action> {
+        let duration = self.simulate().await;
+        Run {
+            duration,
+            completions: self.network.completions().clone(),
+            applied_events: self.applied_events(),
+            metrics: self.network.metrics(),
+            divergence: self.divergence.clone(),
+            counterexample: self.counterexample.clone(),
+        }
+    }
+
+    pub async fn clone(&self) -> Self {
+        let mut s =
+            Self::from_network_info(self.title.clone(), self.network.network_info().fork()).await;
+        for (client, action) in self.scenario.iter() {
+            s.add_scenario_item(*client, action.clone());
+        }
+        s.set_nemesis(self.nemesis.clone());
+
+        s
+    }
+}
+
+impl Simulation {
+    /* Banking operations */
+
+    pub fn deposit(&mut self, id: PeerId, amount: Money) {
+        self.add_scenario_item(id, Action::Deposit(amount));
+    }
+
+    pub fn register(&mut self, id: PeerId) {
+        self.add_scenario_item(id, Action::Register);
+    }
+
+    pub fn get(&mut self, id: PeerId) {
+        self.add_scenario_item(id, Action::Get);
+    }
+
+    pub fn withdraw(&mut self, id: PeerId, amount: Money) {
+        self.add_scenario_item(id, Action::Withdraw(amount));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        network::network_info::DEFAULT_REPORT_FOLDER,
+        simulation::scenarios::{CLIENTS, FAULTY, REPLICAS},
+    };
+
+    use super::*;
+    #[tokio::test]
+    async fn consensus_overhead() {
+        let simulation: Simulation = Simulation::new(
+            String::from("TEST"),
+            String::from(DEFAULT_REPORT_FOLDER),
+            CLIENTS,
+            REPLICAS,
+            FAULTY,
+            10,
+            0.1,
+        )
+        .await;
+        let mut without_overhead = simulation.clone().await;
+        let mut with_overhead = simulation.clone().await;
+
+        without_overhead.deposit(0, 10);
+        without_overhead.withdraw(0, 5);
+        for _ in 0..100 {
+            without_overhead.get(0);
+            with_overhead.get(0);
+        }
+
+        with_overhead.deposit(0, 10);
+        with_overhead.withdraw(0, 5);
+
+        let without_overhead = without_overhead.simulate().await.as_secs_f64();
+        let with_overhead = with_overhead.simulate().await.as_secs_f64();
+
+        println!("Diff = {}s", with_overhead - without_overhead);
+    }
+
+    #[tokio::test]
+    async fn test12() {
+        let simulation: Simulation = Simulation::new(
+            String::from("TEST"),
+            String::from(DEFAULT_REPORT_FOLDER),
+            CLIENTS,
+            REPLICAS,
+            FAULTY,
+            10,
+            0.1,
+        )
+        .await;
+    }
+}
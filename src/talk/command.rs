@@ -10,19 +10,25 @@ use crate::{
 
 use super::CommandId;
 
+/// Operation `O` issued by a client, `Action` for the banking application
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub struct Command {
+pub struct Command<O = Action> {
     issuer: PeerId,
-    action: Action,
+    action: O,
     id: CommandId,
 }
 
-impl Command {
-    pub fn new(issuer: PeerId, action: Action) -> Self {
+impl<O> Command<O> {
+    pub fn new(issuer: PeerId, action: O) -> Self {
         let id = Uuid::new_v4();
         Command { id, issuer, action }
     }
 
+    /// Creates a command with the given identifier, e.g. to reuse the identifier of another one
+    pub fn with_id(id: CommandId, issuer: PeerId, action: O) -> Self {
+        Command { id, issuer, action }
+    }
+
     pub fn issue() -> Self {
         todo!()
     }
@@ -35,9 +41,17 @@ impl Command {
         &self.issuer
     }
 
-    pub fn action(&self) -> &Action {
+    pub fn action(&self) -> &O {
         &self.action
     }
+
+    /// Applies `f` to the operation, keeping the issuer and the identifier
+    pub fn map<P, F: FnOnce(&O) -> P>(&self, f: F) -> Command<P> {
+        Command::with_id(self.id, self.issuer, f(&self.action))
+    }
+}
+
+impl Command {
     pub fn generate_id() -> Uuid {
         Uuid::new_v4()
     }
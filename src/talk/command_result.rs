@@ -3,20 +3,20 @@ use std::hash::Hash;
 
 use serde::{Deserialize, Serialize};
 
-use crate::banking::banking::Money;
+use crate::{application::Operation, banking::action::Action};
 
-type Data = Money;
 #[derive(Clone, Serialize, Deserialize, Debug, PartialOrd, Ord)]
-pub enum CommandResult {
-    Success(Option<Data>),
+#[serde(bound = "")]
+pub enum CommandResult<O: Operation = Action> {
+    Success(Option<O::Output>),
     Failure(String),
 }
 
-impl Display for CommandResult {
+impl<O: Operation> Display for CommandResult<O> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match self {
             CommandResult::Success(data) => match data {
-                Some(amount) => format!("SUCCESS <DATA: {}>", *amount),
+                Some(data) => format!("SUCCESS <DATA: {}>", data),
                 None => format!("SUCCESS"),
             },
             CommandResult::Failure(reason) => format!("FAILURE <{}>", *reason),
@@ -25,9 +25,9 @@ impl Display for CommandResult {
     }
 }
 
-impl Eq for CommandResult {}
+impl<O: Operation> Eq for CommandResult<O> {}
 
-impl PartialEq for CommandResult {
+impl<O: Operation> PartialEq for CommandResult<O> {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::Success(l0), Self::Success(r0)) => l0 == r0,
@@ -37,7 +37,7 @@ impl PartialEq for CommandResult {
     }
 }
 
-impl Hash for CommandResult {
+impl<O: Operation> Hash for CommandResult<O> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         match self {
             CommandResult::Success(_) => core::mem::discriminant(self).hash(state),
@@ -1,18 +1,19 @@
 use serde::{Deserialize, Serialize};
 
-use crate::peer::{peer::PeerId};
+use crate::{application::Operation, banking::action::Action, peer::peer::PeerId};
 
-use super::CommandResult;
+use super::Receipt;
 
-#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
-pub enum Feedback {
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(bound = "")]
+pub enum Feedback<O: Operation = Action> {
     Error(PeerId, String),
     Acknowledgement(PeerId),
-    Result(PeerId, CommandResult),
+    Result(PeerId, Receipt<O>),
     ShutdownComplete(PeerId),
 }
 
-impl Feedback {
+impl<O: Operation> Feedback<O> {
     pub fn from(&self) -> PeerId {
         match self {
             Feedback::Error(id, _) => *id,
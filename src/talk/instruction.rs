@@ -1,7 +1,9 @@
-use super::{Command};
+use crate::banking::action::Action;
 
-pub enum Instruction {
-    Execute(Command),
+use super::Command;
+
+pub enum Instruction<O = Action> {
+    Execute(Command<O>),
     Testing, // Only for testing purposes
     Shutdown,
 }
@@ -1,16 +1,63 @@
-
 use serde::{Deserialize, Serialize};
+use talk::crypto::primitives::sign::Signature;
 
-use crate::database::replica_database::Set;
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    database::replica_database::{Set, SignedSet},
+    peer::consensus::pbft::{SignedProposal, SignedViewChange, ViewChange},
+};
 
-use super::{Command, CommandResult, Phase, RoundNumber};
+use super::{Command, CommandResult, Phase, RoundNumber, ViewNumber};
 
 /// Peers exchange Message.
 /// This is defined to work for talk unicast systems.
-#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum Message {
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(bound = "")]
+pub enum Message<O: Operation = Action> {
     Testing, // Only for debugging/testing purposes
-    Command(Command),
-    CommandAcknowledgement(Command, RoundNumber, CommandResult, Phase),
-    ReplicaBroadcast(RoundNumber, Set, Phase),
+    // Commands are signed by their issuer, see `crypto::statements`.
+    Command(Command<O>, Option<Signature>),
+    // Sent again by a client whose command is not acknowledged in time, for the replicas to
+    // decide it through the consensus. Signed like `Command`.
+    CheckCommand(Command<O>, Option<Signature>),
+    // The signature covers (command id, round, result, phase), see `crypto::statements`.
+    CommandAcknowledgement(Command<O>, RoundNumber, CommandResult<O>, Phase, Signature),
+    // Each command carries the signature of its issuer, see `crypto::statements`.
+    ReplicaBroadcast(RoundNumber, SignedSet<O>, Phase),
+    // Consensus messages, exchanged between the consensus instances of the replicas.
+    // The pair of sets is a (NCSet, CSet) proposal or decision.
+    // The signature of a proposal covers (round, NCSet, CSet), see `crypto::statements`.
+    ConsensusProposal(RoundNumber, Set<O>, Set<O>, Signature),
+    // The pre-prepare carries the signed proposals its value aggregates and, after the first
+    // view, the view changes of a quorum (NEW-VIEW).
+    ConsensusPrePrepare(
+        RoundNumber,
+        ViewNumber,
+        Set<O>,
+        Set<O>,
+        Vec<SignedProposal<O>>,
+        Vec<SignedViewChange<O>>,
+    ),
+    // The signature covers (round, view, NCSet, CSet), see `crypto::statements`.
+    ConsensusPrepare(RoundNumber, ViewNumber, Set<O>, Set<O>, Signature),
+    ConsensusCommit(RoundNumber, ViewNumber, Set<O>, Set<O>),
+    ConsensusViewChange(ViewChange<O>, Signature),
+    // Decision of a round, sent to the replicas still running it.
+    ConsensusDecision(RoundNumber, Set<O>, Set<O>),
+}
+
+impl<O: Operation> Message<O> {
+    /// Returns true if the message is exchanged between the consensus instances of the replicas
+    pub fn is_consensus(&self) -> bool {
+        matches!(
+            self,
+            Message::ConsensusProposal(..)
+                | Message::ConsensusPrePrepare(..)
+                | Message::ConsensusPrepare(..)
+                | Message::ConsensusCommit(..)
+                | Message::ConsensusViewChange(..)
+                | Message::ConsensusDecision(..)
+        )
+    }
 }
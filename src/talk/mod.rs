@@ -4,20 +4,25 @@ use tokio::sync::mpsc;
 
 use uuid::Uuid;
 
+use crate::{application::Operation, banking::action::Action};
+
 pub mod command;
 pub mod command_result;
 pub mod feedback;
 pub mod instruction;
 pub mod message;
+pub mod receipt;
 
 pub use command::Command;
 pub use command_result::CommandResult;
 pub use feedback::Feedback;
 pub use instruction::Instruction;
 pub use message::Message;
+pub use receipt::Receipt;
 
 pub type CommandId = Uuid;
 pub type RoundNumber = usize;
+pub type ViewNumber = usize;
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Phase {
@@ -28,11 +33,11 @@ pub enum Phase {
 #[derive(Debug)]
 pub struct FeedbackChannel {}
 impl FeedbackChannel {
-    pub fn channel() -> (FeedbackSender, FeedbackReceiver) {
+    pub fn channel<O: Operation>() -> (FeedbackSender<O>, FeedbackReceiver<O>) {
         mpsc::channel(32)
     }
 }
 
-pub type FeedbackSender = mpsc::Sender<Feedback>;
+pub type FeedbackSender<O = Action> = mpsc::Sender<Feedback<O>>;
 
-pub type FeedbackReceiver = mpsc::Receiver<Feedback>;
+pub type FeedbackReceiver<O = Action> = mpsc::Receiver<Feedback<O>>;
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use talk::crypto::{primitives::sign::Signature, Identity};
+
+use crate::{
+    application::Operation,
+    banking::action::Action,
+    crypto::{identity_table::IdentityTable, statements::CommandAcknowledgement},
+};
+
+use super::{CommandId, CommandResult, Phase, RoundNumber};
+
+/// Quorum certificate of a completed command.
+/// It gathers the signed acknowledgements that allowed the client to accept the result,
+/// so that anyone holding the `IdentityTable` can check it afterwards.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(bound = "")]
+pub struct Receipt<O: Operation = Action> {
+    id: CommandId,
+    round: RoundNumber,
+    result: CommandResult<O>,
+    phase: Phase,
+    signatures: Vec<(Identity, Signature)>,
+}
+
+impl<O: Operation> Receipt<O> {
+    pub fn new(
+        id: CommandId,
+        round: RoundNumber,
+        result: CommandResult<O>,
+        phase: Phase,
+        signatures: Vec<(Identity, Signature)>,
+    ) -> Self {
+        Receipt {
+            id,
+            round,
+            result,
+            phase,
+            signatures,
+        }
+    }
+
+    pub fn id(&self) -> &CommandId {
+        &self.id
+    }
+
+    pub fn round(&self) -> &RoundNumber {
+        &self.round
+    }
+
+    pub fn result(&self) -> &CommandResult<O> {
+        &self.result
+    }
+
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    pub fn signatures(&self) -> &Vec<(Identity, Signature)> {
+        &self.signatures
+    }
+
+    /// Returns the replicas that acknowledged the result
+    pub fn voters(&self) -> HashSet<&Identity> {
+        self.signatures.iter().map(|(replica, _)| replica).collect()
+    }
+
+    /// Returns true if at least `quorum` distinct replicas signed the acknowledgement.
+    /// Signatures of unknown peers or that fail to verify are not counted.
+    pub fn verify(&self, identity_table: &IdentityTable, quorum: usize) -> bool {
+        let statement = CommandAcknowledgement {
+            id: &self.id,
+            round: &self.round,
+            result: &self.result,
+            phase: &self.phase,
+        };
+        let mut signers = HashSet::new();
+        for (replica, signature) in self.signatures.iter() {
+            let valid = identity_table.is_replica(replica)
+                && identity_table
+                    .keycard(replica)
+                    .map(|keycard| signature.verify(keycard, &statement).is_ok())
+                    .unwrap_or(false);
+            if valid {
+                signers.insert(replica.clone());
+            }
+        }
+        signers.len() >= quorum
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt::Debug;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use uuid::Uuid;
+
+use crate::{
+    banking::action::Action,
+    kv::action::KvAction,
+    peer::{coordinator::ProposalSet, peer::PeerId},
+    relation::{conflict::ConflictingRelation, Relation},
+    talk::Command,
+};
+
+/// Number of random cases checked by a property
+pub const CASES: u64 = 256;
+
+/// Number of elements compared pairwise by `Generator::check_relation`
+pub const RELATED: usize = 12;
+
+/// Seeded generator of random commands and consensus proposals, for property tests.
+/// A failing case is reproduced with the seed reported by `Generator::check`.
+pub struct Generator {
+    rng: StdRng,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Generator {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Runs the property on `CASES` generators, and reports the seed of the first failing case
+    pub fn check<F: Fn(&mut Generator) -> Result<(), String>>(property: F) {
+        for seed in 0..CASES {
+            if let Err(reason) = property(&mut Generator::new(seed)) {
+                panic!("Property failed with seed {}: {}", seed, reason);
+            }
+        }
+    }
+
+    /// Checks that the relation `R` is symmetric and irreflexive on elements made by `element`
+    pub fn check_relation<R, T, F>(element: F)
+    where
+        R: Relation<T>,
+        T: Clone + Debug,
+        F: Fn(&mut Generator) -> T,
+    {
+        Self::check(|generator| {
+            let elements: Vec<T> = (0..RELATED).map(|_| element(generator)).collect();
+            for x in elements.iter() {
+                if R::is_related(x, &x.clone()) {
+                    return Err(format!("{:?} is related to itself", x));
+                }
+                for y in elements.iter() {
+                    if R::is_related(x, y) != R::is_related(y, x) {
+                        return Err(format!("is_related is not symmetric on {:?}, {:?}", x, y));
+                    }
+                }
+            }
+            Ok(())
+        });
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    pub fn action(&mut self) -> Action {
+        match self.rng.gen_range(0..4) {
+            0 => Action::Register,
+            1 => Action::Get,
+            2 => Action::Deposit(self.rng.gen_range(0..100)),
+            _ => Action::Withdraw(self.rng.gen_range(0..100)),
+        }
+    }
+
+    /// Command of one of the `clients` first clients, with a reproducible identifier
+    pub fn command(&mut self, clients: PeerId) -> Command {
+        let issuer = self.rng.gen_range(0..clients);
+        let action = self.action();
+        Command::with_id(Uuid::from_bytes(self.rng.gen()), issuer, action)
+    }
+
+    /// Key-value action on one of a few keys, so that many of them access the same keys
+    pub fn kv_action(&mut self) -> KvAction {
+        let key = format!("k{}", self.rng.gen_range(0..3));
+        let value = self.rng.gen_range(0..3).to_string();
+        match self.rng.gen_range(0..5) {
+            0 => KvAction::Get(key),
+            1 => KvAction::Put(key, value),
+            2 => KvAction::Delete(key),
+            3 => {
+                let expected = if self.rng.gen_bool(0.5) {
+                    Some(self.rng.gen_range(0..3).to_string())
+                } else {
+                    None
+                };
+                KvAction::CompareAndSwap {
+                    key,
+                    expected,
+                    new: value,
+                }
+            }
+            _ => {
+                let size = self.rng.gen_range(0..3);
+                KvAction::Batch((0..size).map(|_| self.kv_action()).collect())
+            }
+        }
+    }
+
+    /// Key-value command of one of the `clients` first clients, with a reproducible identifier
+    pub fn kv_command(&mut self, clients: PeerId) -> Command<KvAction> {
+        let issuer = self.rng.gen_range(0..clients);
+        let action = self.kv_action();
+        Command::with_id(Uuid::from_bytes(self.rng.gen()), issuer, action)
+    }
+
+    /// Up to `max` commands of a few clients, so that many of them are related
+    pub fn commands(&mut self, max: usize) -> ProposalSet {
+        let clients = self.rng.gen_range(1..4);
+        let size = self.rng.gen_range(0..=max);
+        (0..size).map(|_| self.command(clients)).collect()
+    }
+
+    /// Random subset of the commands
+    pub fn subset(&mut self, commands: &ProposalSet) -> ProposalSet {
+        commands
+            .iter()
+            .filter(|_| self.rng.gen_bool(0.5))
+            .cloned()
+            .collect()
+    }
+
+    /// (NCSet, CSet) proposals of `n_ack` correct replicas, among the commands.
+    /// As with the pending set of `ReplicaHandler`, the NCSet of a replica never contains two
+    /// related commands: the received commands that conflict with it go to its CSet.
+    pub fn proposals(
+        &mut self,
+        commands: &ProposalSet,
+        n_ack: usize,
+    ) -> Vec<(ProposalSet, ProposalSet)> {
+        (0..n_ack)
+            .map(|_| {
+                let mut received: Vec<Command> = self.subset(commands).into_iter().collect();
+                received.shuffle(&mut self.rng);
+
+                let (mut nc_set, mut c_set) = (ProposalSet::new(), ProposalSet::new());
+                for command in received {
+                    if nc_set
+                        .iter()
+                        .any(|other| ConflictingRelation::is_related(other, &command))
+                    {
+                        c_set.insert(command);
+                    } else {
+                        nc_set.insert(command);
+                    }
+                }
+                (nc_set, c_set)
+            })
+            .collect()
+    }
+}
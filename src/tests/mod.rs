@@ -1,4 +1,3 @@
-pub mod scenarios;
-pub mod simulation;
+pub mod generator;
 pub mod util;
-pub mod slowdown_factor;
\ No newline at end of file
+pub mod slowdown_factor;
@@ -1,4 +1,4 @@
-use super::scenarios::Scenarios;
+use crate::simulation::scenarios::Scenarios;
 
 const MAX_CONSENSUS: u64 = 2000;
 
@@ -1,10 +1,24 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
 use network_info::NetworkInfo;
-use talk::{crypto::Identity, unicast::test::UnicastSystem};
+use talk::{
+    crypto::{Identity, KeyChain},
+    unicast::test::UnicastSystem,
+};
+use tokio::time::timeout;
 
 use crate::{
     crypto::identity_table::IdentityTableBuilder,
-    network::network_info,
-    peer::{handler::Communicator, peer::PeerId},
+    network::{network::Network, network_info},
+    peer::{
+        handler::{Adversarial, Communicator},
+        peer::PeerId,
+    },
     talk::{FeedbackChannel, FeedbackReceiver, Message},
     types::*,
 };
@@ -15,20 +29,18 @@ impl Utils {
     pub async fn communicator(
         network_info: NetworkInfo,
         id: PeerId,
-    ) -> (
-        Communicator<Message>,
-        UnicastReceiver<Message>,
-        FeedbackReceiver,
-    ) {
+    ) -> (Communicator, UnicastReceiver<Message>, FeedbackReceiver) {
         let (key, sender, receiver) = Utils::unicast_channel().await;
 
         let (feedback_inlet, feedback_outlet) = FeedbackChannel::channel();
         let identity_table = IdentityTableBuilder::new(network_info.clone()).build();
+        let keychain = Arc::new(Utils::keychain(&key));
 
         (
             Communicator::new(
                 id,
                 key,
+                keychain,
                 sender,
                 feedback_inlet,
                 network_info,
@@ -44,7 +56,7 @@ impl Utils {
             mut keys,
             mut senders,
             mut receivers,
-        } = UnicastSystem::<Message>::setup(1).await;
+        } = Utils::unicast_system(1).await;
 
         (
             keys.pop().unwrap(),
@@ -64,10 +76,37 @@ impl Utils {
             keys,
             senders,
             receivers,
-        } = UnicastSystem::<Message>::setup(size).await;
+        } = Utils::unicast_system(size).await;
         (keys, senders, receivers)
     }
 
+    /// Sets up a mock network of `size` peers, whose `KeyChain` is then given by `Utils::keychain`
+    pub async fn unicast_system(size: usize) -> UnicastSystem<Message> {
+        let keychains: Vec<KeyChain> = (0..size).map(|_| KeyChain::random()).collect();
+        let mut registry = Self::keychains().lock().unwrap();
+        for keychain in keychains.iter() {
+            registry.insert(keychain.keycard().identity(), keychain.clone());
+        }
+        drop(registry);
+        UnicastSystem::<Message>::setup_with_keychains(keychains).await
+    }
+
+    /// Returns the talk `KeyChain` behind the key of a peer of a mock network
+    pub fn keychain(key: &Identity) -> KeyChain {
+        Self::keychains()
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .expect("The key does not belong to a mock network")
+    }
+
+    /// Key material of the peers of every mock network, by identity
+    fn keychains() -> &'static Mutex<HashMap<Identity, KeyChain>> {
+        static KEYCHAINS: OnceLock<Mutex<HashMap<Identity, KeyChain>>> = OnceLock::new();
+        KEYCHAINS.get_or_init(|| Mutex::new(HashMap::new()))
+    }
+
     pub fn pop(
         keys: &mut Vec<Identity>,
         senders: &mut Vec<UnicastSender<Message>>,
@@ -89,4 +128,50 @@ impl Utils {
             system.receivers.pop().unwrap(),
         )
     }
+
+    /// Network of the end-to-end tests: 6 correct replicas and a faulty one, along with
+    /// `clients` correct clients and `faulty_clients` faulty ones
+    pub fn network_info(
+        clients: usize,
+        faulty_clients: usize,
+        consensus_duration: f64,
+    ) -> NetworkInfo {
+        NetworkInfo::default_parameters(
+            clients,
+            6,
+            faulty_clients,
+            1,
+            10,
+            consensus_duration,
+            String::from("resources/test3"),
+        )
+    }
+
+    /// Registers the correct clients, then makes each of them deposit and withdraw 1,
+    /// `operations` times
+    pub fn deposit_and_withdraw(network: &mut Network, operations: usize) {
+        network.register_all();
+        for i in 0..network.network_info().nbr_clients() {
+            for _ in 0..operations {
+                network.deposit(i, 1);
+                network.withdraw(i, 1);
+            }
+        }
+    }
+
+    /// Executes the pending commands, which must complete within `limit`, then shuts the
+    /// network down. `context` describes the run if the clients do not complete.
+    pub async fn execute_and_shutdown<A: Adversarial, C: Debug>(
+        network: &mut Network<A>,
+        limit: Duration,
+        context: C,
+    ) {
+        timeout(limit, network.execute_all())
+            .await
+            .unwrap_or_else(|_| panic!("{:?}: the clients did not complete in time", context));
+        network
+            .shutdown()
+            .await
+            .expect("Failed to deliver the time elapsed");
+    }
 }
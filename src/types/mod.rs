@@ -6,7 +6,16 @@ pub use tokio::sync::{
     mpsc::{Receiver as MPSCReceiver, Sender as MPSCSender},
 };
 
-use crate::talk::Instruction;
+use talk::crypto::Identity;
 
-pub type InstructionSender = MPSCSender<Instruction>;
-pub type InstructionReceiver = MPSCReceiver<Instruction>;
+use crate::{
+    banking::action::Action,
+    talk::{Instruction, Message},
+};
+
+pub type InstructionSender<O = Action> = MPSCSender<Instruction<O>>;
+pub type InstructionReceiver<O = Action> = MPSCReceiver<Instruction<O>>;
+
+/// Carries the consensus messages received by a replica to its consensus instance
+pub type ConsensusInlet<O = Action> = MPSCSender<(Identity, Message<O>)>;
+pub type ConsensusOutlet<O = Action> = MPSCReceiver<(Identity, Message<O>)>;